@@ -1,88 +1,110 @@
-window.gen_saved_url = function (callback) {
+window.save_clipboard = function (text) {
     new Promise(async (resolve) => {
         try {
-            const rust = document.querySelector("#rust").value;
-            const tmpl = document.querySelector("#tmpl").value;
-            const data = new TextEncoder("utf-8").encode(
-                "v1" + "\0" + rust + "\0" + tmpl
-            );
-            const comprStrm = new Blob([data])
-                .stream()
-                .pipeThrough(new CompressionStream("deflate"));
-            const comprArray = await new Response(comprStrm).arrayBuffer();
-            const comprBytes = new Uint8Array(comprArray);
-            const base64 = btoa(String.fromCharCode(...comprBytes));
-            const urlsafe = base64.replace(
-                /[+/=]/g,
-                (c) => ({ "+": "-", "/": "_", "=": "." }[c])
-            );
-            const url = new URL("?saved=" + urlsafe, location);
-            resolve(String(url));
+            const clipboard = window.navigator.clipboard;
+            if (clipboard) {
+                await clipboard.writeText(text);
+            } else {
+                alert("Clipboard is not available.");
+            }
         } catch (e) {
-            console.error("could not calculate state", e);
+            console.error("could not store to clipboard", e);
         } finally {
             resolve();
         }
-    }).then(callback);
+    });
+};
+
+window.read_files = function (files, callback) {
+    Promise.all(
+        Array.from(files, async (file) => [file.name, await file.text()])
+    ).then(callback, (e) => console.error("could not read files", e));
+};
+
+window.read_binary_files = function (files, callback) {
+    Promise.all(
+        Array.from(files, async (file) => [file.name, new Uint8Array(await file.arrayBuffer())])
+    ).then(callback, (e) => console.error("could not read files", e));
+};
+
+window.read_data_url = function (file, callback) {
+    const reader = new FileReader();
+    reader.onload = () => callback(file.name, reader.result);
+    reader.onerror = () => console.error("could not read file", reader.error);
+    reader.readAsDataURL(file);
 };
 
-window.read_saved_url = function (callback) {
+window.download_file = function (name, type, data) {
+    const url = URL.createObjectURL(new Blob([data], { type }));
+    const link = document.createElement("a");
+    link.href = url;
+    link.download = name;
+    link.click();
+    window.setTimeout(() => URL.revokeObjectURL(url), 0);
+};
+
+let database;
+
+function open_database() {
+    database ??= new Promise((resolve, reject) => {
+        const request = indexedDB.open("play-rinja", 1);
+        request.onupgradeneeded = () => {
+            const db = request.result;
+            db.createObjectStore("workspaces", { keyPath: "id", autoIncrement: true });
+            db.createObjectStore("snapshots", { keyPath: "id", autoIncrement: true });
+        };
+        request.onsuccess = () => resolve(request.result);
+        request.onerror = () => reject(request.error);
+    });
+    return database;
+}
+
+async function database_request(store, mode, action) {
+    const db = await open_database();
+    return await new Promise((resolve, reject) => {
+        const request = action(db.transaction(store, mode).objectStore(store));
+        request.onsuccess = () => resolve(request.result);
+        request.onerror = () => reject(request.error);
+    });
+}
+
+// The records are exchanged as JSON, an `undefined` result means that the database is unavailable.
+window.database_get_all = function (store, callback) {
     new Promise(async (resolve) => {
         try {
-            const url = new URL(location);
-            const urlsafe = url.searchParams.get("saved");
-            url.search = "";
-            history.replaceState(null, "", url);
-            if (!urlsafe) {
-                return;
-            }
-
-            const base64 = urlsafe.replace(
-                /[-_.]/g,
-                (c) => ({ "-": "+", _: "/", ".": "=" }[c])
-            );
-            const comprStream = await fetch(
-                "data:application/octet-binary;base64," + base64
-            );
-            const plainStream = comprStream.body.pipeThrough(
-                new DecompressionStream("deflate")
-            );
-            const data = await new Response(plainStream).text();
-            const [version, rust, tmpl] = data.split("\0", 3);
-            if (version != "v1") {
-                return;
-            }
-            resolve([rust, tmpl]);
+            const records = await database_request(store, "readonly", (s) => s.getAll());
+            resolve(JSON.stringify(records));
         } catch (e) {
-            console.error("could not read state", e);
+            console.error("could not read database", e);
         } finally {
             resolve();
         }
-    }).then(function (data) {
-        if (data) {
-            const [rust, tmpl] = data;
-            callback(rust, tmpl);
-        } else {
-            callback();
+    }).then(callback);
+};
+
+window.database_put = function (store, record, callback) {
+    new Promise(async (resolve) => {
+        try {
+            const value = JSON.parse(record);
+            resolve(await database_request(store, "readwrite", (s) => s.put(value)));
+        } catch (e) {
+            console.error("could not write database", e);
+        } finally {
+            resolve();
         }
-    });
+    }).then(callback);
 };
 
-window.save_clipboard = function (text) {
+window.database_delete = function (store, id, callback) {
     new Promise(async (resolve) => {
         try {
-            const clipboard = window.navigator.clipboard;
-            if (clipboard) {
-                await clipboard.writeText(text);
-            } else {
-                alert("Clipboard is not available.");
-            }
+            await database_request(store, "readwrite", (s) => s.delete(id));
         } catch (e) {
-            console.error("could not store to clipboard", e);
+            console.error("could not write database", e);
         } finally {
             resolve();
         }
-    });
+    }).then(callback);
 };
 
 window.toggle_element = function (event, elementId) {
@@ -101,11 +123,16 @@ window.handle_blur = function (event, elementId) {
     }
 };
 
-window.reset_code = function (event, text) {
-    const input =
-        event.target.parentElement.parentElement.querySelector("textarea");
-    input.value = text;
-    input.dispatchEvent(new Event("input"));
+window.replace_text = function (element, start, end, text, selStart, selEnd) {
+    element.setSelectionRange(start, end);
+    if (start !== end || text) {
+        // `execCommand()` keeps the browser's undo stack intact.
+        if (!document.execCommand(text ? "insertText" : "delete", false, text)) {
+            element.setRangeText(text, start, end, "end");
+            element.dispatchEvent(new Event("input"));
+        }
+    }
+    element.setSelectionRange(selStart, selEnd);
 };
 
 const state = history.state || {};
@@ -2,93 +2,537 @@ use std::rc::Rc;
 use std::time::Duration;
 
 use prettyplease::unparse;
-use proc_macro2::TokenStream;
+use proc_macro2::{TokenStream, TokenTree};
 use rinja_derive_standalone::derive_template;
-use syn::{parse_quote, parse2};
+use syn::ext::IdentExt;
+use syn::{Expr, Fields, Item, LitStr, parse_file, parse_quote, parse2};
+use syntect::highlighting::Theme;
 use wasm_bindgen::prelude::wasm_bindgen;
-use web_sys::js_sys::{Function, JSON};
+use web_sys::js_sys::{Array, Function, JSON, Uint8Array};
 use web_sys::wasm_bindgen::prelude::Closure;
 use web_sys::wasm_bindgen::{JsCast, JsValue};
-use web_sys::{FocusEvent, HtmlDialogElement, HtmlSelectElement, Storage, window};
+use web_sys::{
+    DragEvent, File, FileList, FocusEvent, HtmlDialogElement, HtmlElement, HtmlInputElement,
+    HtmlSelectElement, MessageEvent, Storage, Url, window,
+};
 use yew::{
-    Callback, Event, Html, MouseEvent, Properties, SubmitEvent, UseStateHandle, function_component,
-    html, use_effect_with, use_state,
+    AttrValue, Callback, Event, Html, KeyboardEvent, MouseEvent, Properties, SubmitEvent,
+    UseStateHandle, classes, function_component, html, use_effect_with, use_memo, use_mut_ref,
+    use_state,
 };
 
+use crate::commands::{Command, CommandPalette, Shortcut, Shortcuts};
+use crate::config::{Delimiters, delimiters};
 use crate::editor::Editor;
-use crate::{ASSETS, ThrowAt};
+use crate::embed::{EMBED, EmbedMessage, HostMessage, duration_ms};
+use crate::examples::EXAMPLES;
+use crate::highlight::theme_css;
+use crate::keymap::{Keymap, SharedRegisters};
+use crate::preferences::{EditorFont, FONT_SIZES, Preferences, TAB_WIDTHS};
+use crate::project::{self, Project};
+use crate::report::{NEW_ISSUE_URL, Report};
+use crate::semantic;
+use crate::share::{Workspace, url_warning};
+use crate::snippet;
+use crate::theme_editor::ThemeEditor;
+use crate::themes::{
+    CustomThemes, ThemePair, ThemeRef, all_themes, check_theme, chrome_css, is_dark, parse_tmtheme,
+    use_prefers_dark,
+};
+use crate::workspaces::WorkspacesMenu;
+use crate::{ASSETS, ThrowAt, template_syntax};
 
 #[derive(Properties, PartialEq, Clone)]
 struct Props {
     theme: Rc<str>,
     rust: Rc<str>,
     tmpl: Rc<str>,
+    compiled: Compiled,
+    timeout: Option<i32>,
+}
+
+#[derive(PartialEq, Clone)]
+struct Compiled {
     code: Rc<str>,
     duration: Option<Duration>,
-    timeout: Option<i32>,
+    /// Messages of the `compile_error!()`s in the generated code
+    diagnostics: Rc<[Rc<str>]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputTab {
+    Code,
+    Diagnostics,
 }
 
 #[function_component]
 pub fn App() -> Html {
+    let read_only = EMBED.as_ref().is_some_and(|embed| embed.read_only);
     let state = use_state(|| {
         let (theme, rust, tmpl) = get_last_editor_state().unwrap_or_default();
         let theme = theme.unwrap_or_else(|| Rc::from(DEFAULT_THEME));
         let rust = rust.unwrap_or_else(|| Rc::from(STRUCT_SOURCE));
         let tmpl = tmpl.unwrap_or_else(|| Rc::from(TMPL_SOURCE));
-        let (code, duration) = convert_source(&rust, &tmpl);
+        let compiled = convert_source(&rust, &tmpl);
         Props {
             theme,
             rust,
             tmpl,
-            code: Rc::from(code),
-            duration,
+            compiled,
             timeout: None,
         }
     });
+    let split_layout = use_state(|| {
+        local_storage()
+            .and_then(|storage| storage.get_item(LAYOUT_KEY).ok().flatten())
+            .is_some_and(|layout| layout == "split")
+    });
+    let output_tab = use_state(|| OutputTab::Code);
+    let custom_themes = use_state(|| Rc::new(CustomThemes::load()));
+    let theme_editor_open = use_state(|| false);
+    let import_error = use_state(|| Option::<String>::None);
+    let preferences = use_state(|| Rc::new(Preferences::load()));
+    let registers = use_state(SharedRegisters::default);
+    let config =
+        use_state(|| load_editor_state(CONFIG_KEY).unwrap_or_else(|| Rc::from(CONFIG_SOURCE)));
+
+    // Replaces both sources at once, e.g. for examples and resets.
+    let set_sources = {
+        let state = state.clone();
+        Callback::from(move |(rust, tmpl): (Rc<str>, Rc<str>)| {
+            save_editor_state(STRUCT_SOURCE_KEY, &rust);
+            save_editor_state(TMPL_SOURCE_KEY, &tmpl);
+            if let Some(timeout) = state.timeout {
+                window().unwrap_at().clear_timeout_with_handle(timeout);
+            }
+            let compiled = convert_source(&rust, &tmpl);
+            state.set(Props {
+                theme: Rc::clone(&state.theme),
+                rust,
+                tmpl,
+                compiled,
+                timeout: None,
+            });
+        })
+    };
+
+    let onsubmit = Callback::from(|ev: SubmitEvent| {
+        ev.prevent_default();
+        ev.stop_propagation();
+    });
+
+    let oninput = |storage_name: &'static str, edit: fn(&mut Props, String)| {
+        let state = state.clone();
+        Callback::from(move |data: String| {
+            save_editor_state(storage_name, &data);
+            let mut new_state = Props::clone(&*state);
+            edit(&mut new_state, data);
+            replace_timeout(&mut new_state, state.clone());
+            state.set(new_state);
+        })
+    };
+    let oninput_rust = oninput(STRUCT_SOURCE_KEY, |new_state, data| {
+        new_state.rust = Rc::from(data);
+    });
+    let oninput_tmpl = oninput(TMPL_SOURCE_KEY, |new_state, data| {
+        new_state.tmpl = Rc::from(data);
+    });
+    let oninput_config = {
+        let config = config.clone();
+        Callback::from(move |data: String| {
+            save_editor_state(CONFIG_KEY, &data);
+            config.set(Rc::from(data));
+        })
+    };
+
+    let prefers_dark = use_prefers_dark();
+    let theme_pair = use_state(ThemePair::load);
+
+    // If the theme follows the system's color scheme, the theme of the current scheme is replaced.
+    let set_theme = {
+        let state = state.clone();
+        let theme_pair = theme_pair.clone();
+        Callback::from(move |data: Rc<str>| {
+            if let Some(pair) = &*theme_pair {
+                let mut pair = pair.clone();
+                *pair.get_mut(prefers_dark) = data;
+                ThemePair::save(Some(&pair));
+                theme_pair.set(Some(pair));
+                return;
+            }
+            save_editor_state(THEME_SOURCE_KEY, &data);
+            state.set(Props {
+                theme: data,
+                ..Props::clone(&state)
+            })
+        })
+    };
+
+    let onchange_theme = {
+        let set_theme = set_theme.clone();
+        move |ev: Event| {
+            let Some(target) = ev.target() else {
+                return;
+            };
+            let target: HtmlSelectElement = target.unchecked_into();
+            set_theme.emit(target.value().into());
+        }
+    };
+
+    let theme_name = match &*theme_pair {
+        Some(pair) => Rc::clone(pair.get(prefers_dark)),
+        None => Rc::clone(&state.theme),
+    };
+    let themes = all_themes(&custom_themes);
+    let (theme_idx, theme) = match themes.iter().position(|(key, _)| **key == *theme_name) {
+        Some(theme_idx) => (theme_idx, themes[theme_idx].1.clone()),
+        None => {
+            set_theme.emit(Rc::from(DEFAULT_THEME));
+            (0, themes[0].1.clone()) // index does not matter, will be rerendered immediately
+        }
+    };
+    // Switching the theme only swaps the stylesheet, the highlighted code stays the same.
+    let theme_style = use_memo(theme.clone(), |theme| theme_css(theme));
+    let chrome_style = use_memo(theme.clone(), |theme| chrome_css(theme));
+
+    // The page around the editors is dark if the theme is.
+    use_effect_with(is_dark(&theme), |&dark| {
+        if let Some(root) = window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.document_element())
+        {
+            let _ = root.class_list().toggle_with_force("dark", dark);
+        }
+    });
+
+    // Following the system pairs the current theme with a bundled theme of the other scheme.
+    let onchange_follow_system = {
+        let state = state.clone();
+        let theme_pair = theme_pair.clone();
+        let theme_name = Rc::clone(&theme_name);
+        let dark = is_dark(&theme);
+        move |ev: Event| {
+            let Some(target) = ev.target() else {
+                return;
+            };
+            let target: HtmlInputElement = target.unchecked_into();
+            let pair = target.checked().then(|| match dark {
+                true => ThemePair {
+                    light: Rc::from(DEFAULT_LIGHT_THEME),
+                    dark: Rc::clone(&theme_name),
+                },
+                false => ThemePair {
+                    light: Rc::clone(&theme_name),
+                    dark: Rc::from(DEFAULT_THEME),
+                },
+            });
+            ThemePair::save(pair.as_ref());
+            theme_pair.set(pair);
+            save_editor_state(THEME_SOURCE_KEY, &theme_name);
+            state.set(Props {
+                theme: Rc::clone(&theme_name),
+                ..Props::clone(&state)
+            });
+        }
+    };
+    let onchange_pair = |dark: bool| {
+        let theme_pair = theme_pair.clone();
+        move |ev: Event| {
+            let (Some(target), Some(pair)) = (ev.target(), &*theme_pair) else {
+                return;
+            };
+            let target: HtmlSelectElement = target.unchecked_into();
+            let mut pair = pair.clone();
+            *pair.get_mut(dark) = target.value().into();
+            ThemePair::save(Some(&pair));
+            theme_pair.set(Some(pair));
+        }
+    };
+
+    // Imported themes are selected right away, the last one if there are many.
+    let import_themes = {
+        let custom_themes = custom_themes.clone();
+        let import_error = import_error.clone();
+        let set_theme = set_theme.clone();
+        Callback::from(move |files: Vec<(String, String)>| {
+            let mut new_themes = CustomThemes::clone(&custom_themes);
+            let mut errors = Vec::new();
+            let mut imported = None;
+            for (file_name, source) in files {
+                match parse_tmtheme(&source) {
+                    Ok(theme) => {
+                        let name = theme
+                            .name
+                            .as_deref()
+                            .map(str::trim)
+                            .filter(|name| !name.is_empty())
+                            .unwrap_or_else(|| file_name.trim_end_matches(".tmTheme"));
+                        let name = new_themes.unused_name(name);
+                        new_themes.insert(Rc::clone(&name), theme);
+                        imported = Some(name);
+                    }
+                    Err(err) => errors.push(format!("{file_name}: {err}")),
+                }
+            }
+            import_error.set((!errors.is_empty()).then(|| errors.join("\n")));
+            if let Some(name) = imported {
+                new_themes.save();
+                custom_themes.set(Rc::new(new_themes));
+                set_theme.emit(name);
+            }
+        })
+    };
+    let onimport = {
+        let import_themes = import_themes.clone();
+        move |ev: Event| {
+            let Some(target) = ev.target() else {
+                return;
+            };
+            let target: HtmlInputElement = target.unchecked_into();
+            if let Some(files) = target.files() {
+                read_theme_files(&files, import_themes.clone());
+            }
+            target.set_value("");
+        }
+    };
+
+    let preferences_style = use_memo(Rc::clone(&*preferences), |preferences| preferences.css());
+    let set_preferences = {
+        let preferences = preferences.clone();
+        Callback::from(move |new_preferences: Preferences| {
+            let new_preferences = new_preferences.clamp();
+            new_preferences.save();
+            if new_preferences.font != preferences.font {
+                new_preferences.save_font();
+            }
+            preferences.set(Rc::new(new_preferences));
+        })
+    };
+    let edit_preferences = |edit: fn(&mut Preferences, &HtmlInputElement)| {
+        let preferences = preferences.clone();
+        let set_preferences = set_preferences.clone();
+        move |ev: Event| {
+            let Some(target) = ev.target() else {
+                return;
+            };
+            let mut new_preferences = Preferences::clone(&preferences);
+            edit(&mut new_preferences, &target.unchecked_into());
+            set_preferences.emit(new_preferences);
+        }
+    };
+    let onchange_keymap = {
+        let preferences = preferences.clone();
+        let set_preferences = set_preferences.clone();
+        move |ev: Event| {
+            let Some(target) = ev.target() else {
+                return;
+            };
+            let target: HtmlSelectElement = target.unchecked_into();
+            if let Some(keymap) = Keymap::from_key(&target.value()) {
+                set_preferences.emit(Preferences {
+                    keymap,
+                    ..Preferences::clone(&preferences)
+                });
+            }
+        }
+    };
+    let onchange_font = {
+        let preferences = preferences.clone();
+        let set_preferences = set_preferences.clone();
+        move |ev: Event| {
+            let Some(target) = ev.target() else {
+                return;
+            };
+            let target: HtmlInputElement = target.unchecked_into();
+            if let Some(file) = target.files().and_then(|files| files.get(0)) {
+                let preferences = preferences.clone();
+                let set_preferences = set_preferences.clone();
+                let callback = Closure::once(move |name: String, data: String| {
+                    set_preferences.emit(Preferences {
+                        font: Some(Rc::new(EditorFont { name, data })),
+                        ..Preferences::clone(&preferences)
+                    });
+                });
+                read_data_url(&file, callback.into_js_value().unchecked_ref());
+            }
+            target.set_value("");
+        }
+    };
+    let onreset_font = (preferences.font.is_some()).then(|| {
+        let preferences = preferences.clone();
+        let set_preferences = set_preferences.clone();
+        move |_: MouseEvent| {
+            set_preferences.emit(Preferences {
+                font: None,
+                ..Preferences::clone(&preferences)
+            });
+        }
+    });
+
+    // Edits of a bundled theme are saved as a copy, so that the original stays available.
+    let theme_editor = {
+        let is_custom = custom_themes.contains(&theme_name);
+        let onchange = {
+            let custom_themes = custom_themes.clone();
+            let set_theme = set_theme.clone();
+            let name = Rc::clone(&theme_name);
+            Callback::from(move |mut theme: Theme| {
+                let mut new_themes = CustomThemes::clone(&custom_themes);
+                let name = match is_custom {
+                    true => Rc::clone(&name),
+                    false => {
+                        let name = new_themes.unused_name(&format!("{name} (edited)"));
+                        theme.name = Some(name.to_string());
+                        name
+                    }
+                };
+                new_themes.insert(Rc::clone(&name), theme);
+                new_themes.save();
+                custom_themes.set(Rc::new(new_themes));
+                if !is_custom {
+                    set_theme.emit(name);
+                }
+            })
+        };
+        let ondelete = is_custom.then(|| {
+            let custom_themes = custom_themes.clone();
+            let set_theme = set_theme.clone();
+            let name = Rc::clone(&theme_name);
+            Callback::from(move |()| {
+                let mut new_themes = CustomThemes::clone(&custom_themes);
+                new_themes.remove(&name);
+                new_themes.save();
+                custom_themes.set(Rc::new(new_themes));
+                set_theme.emit(Rc::from(DEFAULT_THEME));
+            })
+        });
+        let onclose = {
+            let theme_editor_open = theme_editor_open.clone();
+            Callback::from(move |()| theme_editor_open.set(false))
+        };
+        html! {
+            <ThemeEditor
+                open={*theme_editor_open}
+                name={AttrValue::from(Rc::clone(&theme_name))}
+                theme={theme.clone()}
+                {onchange}
+                {ondelete}
+                {onclose}
+            />
+        }
+    };
+    // Restores a shared workspace. Only the state that the link contains is replaced.
+    let open_workspace = {
+        let state = state.clone();
+        let config = config.clone();
+        let custom_themes = custom_themes.clone();
+        let theme_pair = theme_pair.clone();
+        Callback::from(move |workspace: Workspace| {
+            if let Some(new_config) = workspace.config {
+                save_editor_state(CONFIG_KEY, &new_config);
+                config.set(new_config);
+            }
+
+            // A different theme of the same name is kept, the shared one is added as a copy.
+            let mut theme = workspace.theme;
+            if let (Some(name), Some(shared)) = (&theme, workspace.custom_theme) {
+                let mut new_themes = CustomThemes::clone(&custom_themes);
+                let name = match new_themes.get(name) {
+                    Some(existing) if *existing != shared => new_themes.unused_name(name),
+                    _ => Rc::clone(name),
+                };
+                if !new_themes.contains(&name) {
+                    new_themes.insert(Rc::clone(&name), Theme::clone(&shared));
+                    new_themes.save();
+                    custom_themes.set(Rc::new(new_themes));
+                }
+                theme = Some(name);
+            }
+            let theme = match (theme, &*theme_pair) {
+                (Some(theme), Some(pair)) => {
+                    let mut pair = pair.clone();
+                    *pair.get_mut(prefers_dark) = theme;
+                    ThemePair::save(Some(&pair));
+                    theme_pair.set(Some(pair));
+                    Rc::clone(&state.theme)
+                }
+                (Some(theme), None) => {
+                    save_editor_state(THEME_SOURCE_KEY, &theme);
+                    theme
+                }
+                (None, _) => Rc::clone(&state.theme),
+            };
+
+            let rust = workspace.rust.unwrap_or_else(|| Rc::clone(&state.rust));
+            let tmpl = workspace.tmpl.unwrap_or_else(|| Rc::clone(&state.tmpl));
+            save_editor_state(STRUCT_SOURCE_KEY, &rust);
+            save_editor_state(TMPL_SOURCE_KEY, &tmpl);
+            if let Some(timeout) = state.timeout {
+                window().unwrap_at().clear_timeout_with_handle(timeout);
+            }
+            let compiled = convert_source(&rust, &tmpl);
+            state.set(Props {
+                theme,
+                rust,
+                tmpl,
+                compiled,
+                timeout: None,
+            });
+        })
+    };
+
+    // The state that a shared link restores
+    let current_workspace = {
+        let (rust, tmpl) = (Rc::clone(&state.rust), Rc::clone(&state.tmpl));
+        let config = Rc::clone(&config);
+        let theme = Rc::clone(&theme_name);
+        let custom_theme = custom_themes.get(&theme_name).cloned();
+        Rc::new(move || Workspace {
+            rust: Some(Rc::clone(&rust)),
+            tmpl: Some(Rc::clone(&tmpl)),
+            config: Some(Rc::clone(&config)),
+            theme: Some(Rc::clone(&theme)),
+            custom_theme: custom_theme.clone(),
+            rinja: Some(Rc::from(env!("RINJA_DESCR"))),
+        })
+    };
 
     // share_dialog
-    let (saved_url, saved_url_open, saved_url_onclose, saved_url_close, saved_url_copy);
+    let (saved_url, share, saved_url_onclose, saved_url_close, saved_url_copy);
+    // The page was opened with a shared link, so the link becomes a new workspace.
+    let from_link = use_state(|| false);
     #[allow(clippy::let_unit_value)]
     let _ = {
         use_effect_with((), {
-            let state = state.clone();
+            let open_workspace = open_workspace.clone();
+            let custom_themes = Rc::clone(&custom_themes);
+            let from_link = from_link.clone();
             move |_| {
-                let callback: Closure<dyn Fn(Option<String>, Option<String>)> =
-                    Closure::new(move |rust: Option<String>, tmpl: Option<String>| {
-                        let (Some(rust), Some(tmpl)) = (rust, tmpl) else {
-                            return;
-                        };
-                        if let Some(storage) = local_storage() {
-                            // Doesn't matter whether or not it succeeded.
-                            let _ = save_to_local_storage(&storage, STRUCT_SOURCE_KEY, &rust);
-                            let _ = save_to_local_storage(&storage, TMPL_SOURCE_KEY, &tmpl);
-                        }
-                        let (code, duration) = convert_source(&rust, &tmpl);
-                        state.set(Props {
-                            theme: Rc::clone(&state.theme),
-                            rust: Rc::from(rust),
-                            tmpl: Rc::from(tmpl),
-                            code: Rc::from(code),
-                            duration,
-                            timeout: None,
-                        });
-                    });
-                read_saved_url(callback.into_js_value().unchecked_ref());
+                let message = match take_saved_param().as_deref().map(Workspace::from_url_param) {
+                    Some(Ok(workspace)) => {
+                        let (workspace, notes) = review_link(workspace, &custom_themes);
+                        open_workspace.emit(workspace);
+                        from_link.set(true);
+                        notes.join("\n")
+                    }
+                    Some(Err(err)) => err.to_string(),
+                    None => String::new(),
+                };
+                if let (Some(window), false) = (window(), message.is_empty()) {
+                    let _ = window.alert_with_message(&message);
+                }
             }
         });
 
         saved_url = use_state(|| Option::<Rc<str>>::None);
 
-        saved_url_open = {
+        share = {
             let saved_url = saved_url.clone();
-            move |_: MouseEvent| {
-                let saved_url = saved_url.clone();
-                let callback: Closure<dyn Fn(Option<String>)> =
-                    Closure::new(move |url: Option<String>| {
-                        saved_url.set(url.map(Rc::from));
-                    });
-                gen_saved_url(callback.into_js_value().unchecked_ref());
-            }
+            let current_workspace = Rc::clone(&current_workspace);
+            Callback::from(move |()| {
+                let workspace = current_workspace();
+                saved_url.set(shared_url(&workspace.to_url_param()).map(Rc::from));
+            })
         };
 
         saved_url_onclose = {
@@ -97,7 +541,7 @@ pub fn App() -> Html {
         };
 
         saved_url_close = move |_: MouseEvent| {
-            if let Some(share_dialog) = share_dialog() {
+            if let Some(share_dialog) = get_dialog("share_dialog") {
                 let _ = share_dialog.close();
             }
         };
@@ -108,214 +552,880 @@ pub fn App() -> Html {
             .map(|saved_url| move |_: MouseEvent| save_clipboard(&saved_url));
 
         if saved_url.is_some() {
-            if let Some(share_dialog) = share_dialog() {
-                let share_dialog: HtmlDialogElement = share_dialog.unchecked_into();
+            if let Some(share_dialog) = get_dialog("share_dialog") {
                 let _ = share_dialog.show_modal();
             }
         }
     };
 
-    let onsubmit = Callback::from(|ev: SubmitEvent| {
-        ev.prevent_default();
-        ev.stop_propagation();
-    });
+    // report_dialog
+    let (report, report_issue, report_onclose, report_close, report_copy);
+    #[allow(clippy::let_unit_value)]
+    let _ = {
+        report = use_state(|| Option::<Rc<str>>::None);
 
-    let oninput = |storage_name: &'static str, edit: fn(&mut Props, String)| {
-        let state = state.clone();
-        move |data: String| {
-            if let Some(storage) = local_storage() {
-                save_to_local_storage(&storage, storage_name, &data);
+        report_issue = {
+            let report = report.clone();
+            let (rust, tmpl) = (Rc::clone(&state.rust), Rc::clone(&state.tmpl));
+            let config = Rc::clone(&config);
+            let compiled = state.compiled.clone();
+            let current_workspace = Rc::clone(&current_workspace);
+            Callback::from(move |()| {
+                let tmpl_lang = template_args(&rust).ext.unwrap_or_default();
+                let diagnostics = compiled
+                    .diagnostics
+                    .iter()
+                    .map(|msg| &**msg)
+                    .collect::<Vec<_>>();
+                let share_url = shared_url(&current_workspace().to_url_param());
+                let browser = browser_info();
+                let markdown = Report {
+                    rust: &rust,
+                    tmpl: &tmpl,
+                    tmpl_lang: &tmpl_lang,
+                    config: &config,
+                    code: &compiled.code,
+                    diagnostics: &diagnostics,
+                    share_url: share_url.as_deref(),
+                    browser: browser.as_deref(),
+                }
+                .to_markdown();
+                report.set(Some(Rc::from(markdown)));
+            })
+        };
+
+        report_onclose = {
+            let report = report.clone();
+            move |_: Event| report.set(None)
+        };
+
+        report_close = move |_: MouseEvent| {
+            if let Some(report_dialog) = get_dialog("report_dialog") {
+                let _ = report_dialog.close();
+            }
+        };
+
+        report_copy = report
+            .as_ref()
+            .map(Rc::clone)
+            .map(|report| move |_: MouseEvent| save_clipboard(&report));
+
+        if report.is_some() {
+            if let Some(report_dialog) = get_dialog("report_dialog") {
+                let _ = report_dialog.show_modal();
             }
-            let mut new_state = Props::clone(&*state);
-            edit(&mut new_state, data);
-            replace_timeout(&mut new_state, state.clone());
-            state.set(new_state);
         }
     };
-    let oninput_rust = oninput(STRUCT_SOURCE_KEY, |new_state, data| {
-        new_state.rust = Rc::from(data);
-    });
-    let oninput_tmpl = oninput(TMPL_SOURCE_KEY, |new_state, data| {
-        new_state.tmpl = Rc::from(data);
-    });
 
-    let onchange_theme = {
-        let state = state.clone();
+    // The page around an embedded playground sets the state, and is told about every compilation.
+    {
+        let snapshot = || {
+            (
+                Props::clone(&state),
+                Rc::clone(&config),
+                Rc::clone(&custom_themes),
+            )
+        };
+        let current = use_mut_ref(snapshot);
+        *current.borrow_mut() = snapshot();
+        let open_workspace = open_workspace.clone();
+        use_effect_with((), move |_| {
+            let listener = Closure::<dyn Fn(MessageEvent)>::new(move |ev: MessageEvent| {
+                let Some(embed) = &*EMBED else {
+                    return;
+                };
+                if !embed.accepts(&ev) {
+                    return;
+                }
+                let (state, config, custom_themes) = &*current.borrow();
+                match HostMessage::parse(&ev.data()) {
+                    Ok(HostMessage::Open(workspace)) => open_workspace.emit(workspace),
+                    Ok(HostMessage::OpenLink { saved }) => {
+                        match Workspace::from_url_param(&saved) {
+                            Ok(workspace) => {
+                                let (workspace, notes) = review_link(workspace, custom_themes);
+                                open_workspace.emit(workspace);
+                                for message in notes {
+                                    embed.post(&EmbedMessage::Note { message });
+                                }
+                            }
+                            Err(err) => embed.post(&EmbedMessage::Error {
+                                message: err.to_string(),
+                            }),
+                        }
+                    }
+                    Ok(HostMessage::Get) => embed.post(&compiled_message(state, config)),
+                    Err(message) => embed.post(&EmbedMessage::Error { message }),
+                }
+            });
+            let window = window().unwrap_at();
+            if let Some(embed) = &*EMBED {
+                let _ = window
+                    .add_event_listener_with_callback("message", listener.as_ref().unchecked_ref());
+                embed.post(&EmbedMessage::Ready);
+            }
+            move || {
+                let _ = window.remove_event_listener_with_callback(
+                    "message",
+                    listener.as_ref().unchecked_ref(),
+                );
+            }
+        });
+        use_effect_with((state.compiled.clone(), Rc::clone(&config)), {
+            let state = Props::clone(&state);
+            move |(_, config)| {
+                if let Some(embed) = &*EMBED {
+                    embed.post(&compiled_message(&state, config));
+                }
+            }
+        });
+    }
+
+    // Files of a crate replace the struct, the template or the configuration they contain.
+    let import_files = {
+        let open_workspace = open_workspace.clone();
+        Callback::from(move |files: Vec<(String, Vec<u8>)>| {
+            let message = match project::import_files(files) {
+                Ok((workspace, notes)) => {
+                    open_workspace.emit(workspace);
+                    notes.join("\n")
+                }
+                Err(err) => err,
+            };
+            if let (Some(window), false) = (window(), message.is_empty()) {
+                let _ = window.alert_with_message(&message);
+            }
+        })
+    };
+    let onimport_files = {
+        let import_files = import_files.clone();
         move |ev: Event| {
             let Some(target) = ev.target() else {
                 return;
             };
-            let target: HtmlSelectElement = target.unchecked_into();
-            let data = target.value();
-
-            if let Some(storage) = local_storage() {
-                save_to_local_storage(&storage, THEME_SOURCE_KEY, &data);
+            let target: HtmlInputElement = target.unchecked_into();
+            if let Some(files) = target.files() {
+                read_project_files(&files, import_files.clone());
             }
-            state.set(Props {
-                theme: data.into(),
-                ..Props::clone(&state)
-            })
+            target.set_value("");
+        }
+    };
+    // Themes are imported if there are any among the dropped files.
+    let ondragover = move |ev: DragEvent| {
+        if !read_only
+            && ev
+                .data_transfer()
+                .is_some_and(|data| data.types().includes(&JsValue::from_str("Files"), 0))
+        {
+            ev.prevent_default();
+        }
+    };
+    let ondrop = move |ev: DragEvent| {
+        if read_only {
+            return;
+        }
+        if let Some(files) = dragged_theme_files(&ev) {
+            ev.prevent_default();
+            read_theme_files(&files, import_themes.clone());
+        } else if let Some(files) = ev.data_transfer().and_then(|data| data.files()) {
+            ev.prevent_default();
+            read_project_files(&files, import_files.clone());
         }
     };
 
-    let theme = state.theme.as_ref();
-    let (_, themes) = *ASSETS;
-    let (theme_idx, theme) = match themes
-        .iter()
-        .enumerate()
-        .find_map(|(idx, &(key, value))| (key == theme).then_some((idx, value)))
-    {
-        Some((theme_idx, theme)) => (theme_idx, theme),
-        None => {
-            state.set(Props {
-                theme: DEFAULT_THEME.into(),
-                ..Props::clone(&state)
+    // Downloads the struct, the template and the configuration as a Cargo project.
+    let export_project = {
+        let (rust, tmpl) = (Rc::clone(&state.rust), Rc::clone(&state.tmpl));
+        let config = Rc::clone(&config);
+        Callback::from(move |()| match Project::new(&rust, &tmpl, &config) {
+            Ok(project) => download_bytes(
+                &format!("{}.zip", project.name),
+                "application/zip",
+                &project.to_zip(),
+            ),
+            Err(err) => {
+                if let Some(window) = window() {
+                    let _ = window.alert_with_message(&err);
+                }
+            }
+        })
+    };
+
+    let download_code = {
+        let code = Rc::clone(&state.compiled.code);
+        Callback::from(move |()| download_file("generated.rs", "text/rust", &code))
+    };
+
+    // The text around the template tags is highlighted in the language of the template's `ext`,
+    // the tags with the delimiters of its `syntax`.
+    let tmpl_syntax = use_memo(
+        (Rc::clone(&state.rust), Rc::clone(&*config)),
+        |(rust, config)| {
+            let args = template_args(rust);
+            let (delimiters, err) = match delimiters(config, args.syntax.as_deref()) {
+                Ok(delimiters) => (delimiters, None),
+                Err(err) => (Delimiters::default(), Some(err)),
+            };
+            let syntax = template_syntax(args.ext.as_deref(), &delimiters);
+            (syntax, err, delimiters, args.fields)
+        },
+    );
+    // Fields, local variables, macros and filters, as rinja's parser sees them.
+    let tmpl_tokens = use_memo(
+        (Rc::clone(&state.tmpl), Rc::clone(&tmpl_syntax)),
+        |(tmpl, tmpl_syntax)| {
+            let (_, _, delimiters, fields) = &**tmpl_syntax;
+            semantic::tokens(tmpl, delimiters, fields.as_deref())
+        },
+    );
+    let (tmpl_syntax, config_error) = (tmpl_syntax.0.clone(), tmpl_syntax.1.clone());
+
+    // command_palette
+    let (palette, run_command);
+    #[allow(clippy::let_unit_value)]
+    let _ = {
+        let palette_open = use_state(|| false);
+        let shortcuts = use_state(|| Rc::new(Shortcuts::load()));
+
+        run_command = {
+            let state = state.clone();
+            let palette_open = palette_open.clone();
+            let split_layout = split_layout.clone();
+            let output_tab = output_tab.clone();
+            let share = share.clone();
+            let report_issue = report_issue.clone();
+            let export_project = export_project.clone();
+            let download_code = download_code.clone();
+            let set_sources = set_sources.clone();
+            let set_theme = set_theme.clone();
+            let theme_editor_open = theme_editor_open.clone();
+            let preferences = preferences.clone();
+            let set_preferences = set_preferences.clone();
+            let set_font_size = move |font_size: fn(u32) -> u32| {
+                set_preferences.emit(Preferences {
+                    font_size: font_size(preferences.font_size),
+                    ..Preferences::clone(&preferences)
+                });
+            };
+            let theme_names = themes
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>();
+            Callback::from(move |command: Command| match command {
+                Command::ShowCommands => palette_open.set(true),
+                Command::CompileNow => {
+                    let mut new_state = Props::clone(&state);
+                    if let Some(timeout) = new_state.timeout.take() {
+                        window().unwrap_at().clear_timeout_with_handle(timeout);
+                    }
+                    new_state.compiled = convert_source(&new_state.rust, &new_state.tmpl);
+                    state.set(new_state);
+                }
+                Command::Share => share.emit(()),
+                Command::ReportIssue => report_issue.emit(()),
+                Command::ExportProject => export_project.emit(()),
+                Command::DownloadCode => download_code.emit(()),
+                Command::CopyCode => save_clipboard(&state.compiled.code),
+                Command::NextTheme => {
+                    let key = &theme_names[(theme_idx + 1) % theme_names.len()];
+                    set_theme.emit(Rc::from(key.as_str()));
+                }
+                Command::PreviousTheme => {
+                    let len = theme_names.len();
+                    let key = &theme_names[(theme_idx + len - 1) % len];
+                    set_theme.emit(Rc::from(key.as_str()));
+                }
+                Command::EditTheme => theme_editor_open.set(true),
+                Command::IncreaseFontSize => set_font_size(|size| size + 1),
+                Command::DecreaseFontSize => set_font_size(|size| size.saturating_sub(1)),
+                Command::ResetFontSize => set_font_size(|_| Preferences::default().font_size),
+                Command::SwitchLayout => {
+                    if let Some(storage) = local_storage() {
+                        let layout = match *split_layout {
+                            true => "stacked",
+                            false => "split",
+                        };
+                        // Doesn't matter whether or not it succeeded.
+                        let _ = storage.set_item(LAYOUT_KEY, layout);
+                    }
+                    split_layout.set(!*split_layout);
+                }
+                Command::ResetEditors => {
+                    if !read_only {
+                        set_sources.emit((Rc::from(STRUCT_SOURCE), Rc::from(TMPL_SOURCE)));
+                    }
+                }
+                Command::OpenExamples => open_menu("examples-menu"),
+                Command::ToggleOutputTab => output_tab.set(match *output_tab {
+                    OutputTab::Code => OutputTab::Diagnostics,
+                    OutputTab::Diagnostics => OutputTab::Code,
+                }),
+            })
+        };
+
+        // The window listener outlives this render, so it reads the current bindings from here.
+        let current = use_mut_ref(|| (Rc::clone(&*shortcuts), run_command.clone()));
+        *current.borrow_mut() = (Rc::clone(&*shortcuts), run_command.clone());
+        use_effect_with((), move |_| {
+            let listener = Closure::<dyn Fn(KeyboardEvent)>::new(move |ev: KeyboardEvent| {
+                if ev.default_prevented() || ev.repeat() {
+                    return;
+                }
+                let (shortcuts, run_command) = &*current.borrow();
+                if let Some(command) = shortcuts.find(&ev) {
+                    ev.prevent_default();
+                    run_command.emit(command);
+                }
             });
-            (0, themes[0].1) // index does not matter, will be rerendered immediately
-        }
+            let window = window().unwrap_at();
+            let _ = window
+                .add_event_listener_with_callback("keydown", listener.as_ref().unchecked_ref());
+            move || {
+                let _ = window.remove_event_listener_with_callback(
+                    "keydown",
+                    listener.as_ref().unchecked_ref(),
+                );
+            }
+        });
+
+        let onrebind = {
+            let shortcuts = shortcuts.clone();
+            Callback::from(move |(command, shortcut): (Command, Option<Shortcut>)| {
+                let mut new_shortcuts = Shortcuts::clone(&shortcuts);
+                new_shortcuts.set(command, shortcut);
+                new_shortcuts.save();
+                shortcuts.set(Rc::new(new_shortcuts));
+            })
+        };
+        let onclose = {
+            let palette_open = palette_open.clone();
+            Callback::from(move |()| palette_open.set(false))
+        };
+
+        palette = html! {
+            <CommandPalette
+                open={*palette_open}
+                shortcuts={Rc::clone(&*shortcuts)}
+                onrun={run_command.clone()}
+                {onrebind}
+                {onclose}
+            />
+        };
+    };
+
+    let theme_select = match &*theme_pair {
+        Some(pair) => html! {
+            <>
+                <label>
+                    <strong>{"Light theme: "}</strong>
+                    <select onchange={onchange_pair(false)} onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}>
+                        {theme_options(&themes, &pair.light)}
+                    </select>
+                </label>
+                <label>
+                    <strong>{"Dark theme: "}</strong>
+                    <select onchange={onchange_pair(true)} onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}>
+                        {theme_options(&themes, &pair.dark)}
+                    </select>
+                </label>
+            </>
+        },
+        None => html! {
+            <label>
+                <strong>{"Theme: "}</strong>
+                <select onchange={onchange_theme} id="theme" onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}>
+                    {theme_options(&themes, &theme_name)}
+                </select>
+            </label>
+        },
     };
 
-    let themes = themes
+    // What the editors show, which the workspaces menu saves.
+    let content = use_memo(
+        (
+            Rc::clone(&state.rust),
+            Rc::clone(&state.tmpl),
+            Rc::clone(&*config),
+            Rc::clone(&theme_name),
+        ),
+        |(rust, tmpl, config, theme)| Workspace {
+            rust: Some(Rc::clone(rust)),
+            tmpl: Some(Rc::clone(tmpl)),
+            config: Some(Rc::clone(config)),
+            theme: Some(Rc::clone(theme)),
+            ..Workspace::default()
+        },
+    );
+
+    let examples = EXAMPLES
         .iter()
-        .copied()
-        .enumerate()
-        .map(|(i, (value, _))| {
+        .map(|example| {
+            let onclick = {
+                let set_sources = set_sources.clone();
+                move |_: MouseEvent| {
+                    set_sources.emit((Rc::from(example.rust), Rc::from(example.tmpl)));
+                    close_menu("examples-menu");
+                }
+            };
             html! {
-                <option value={value} selected={i == theme_idx}>
-                    {value}
-                </option>
+                <button type="button" {onclick}>{example.name}</button>
             }
         })
         .collect::<Html>();
 
-    html! {
-        <div>
-            <header>
+    let select_tab = |tab: OutputTab| {
+        let output_tab = output_tab.clone();
+        move |_: MouseEvent| output_tab.set(tab)
+    };
+    let tab_class = |tab: OutputTab| classes!("tab", (*output_tab == tab).then_some("active"));
+
+    // Every editor can be downloaded as highlighted HTML or SVG, in the colors of the theme.
+    let snippets = [
+        ("Struct", "struct", &state.rust, AttrValue::Static("Rust")),
+        ("Template", "template", &state.tmpl, tmpl_syntax.clone()),
+        ("rinja.toml", "rinja", &*config, AttrValue::Static("TOML")),
+        (
+            "Generated code",
+            "generated",
+            &state.compiled.code,
+            AttrValue::Static("Rust"),
+        ),
+    ]
+    .into_iter()
+    .map(|(title, file_name, text, syntax)| {
+        let download = |svg: bool| {
+            let (text, syntax, theme) = (Rc::clone(text), syntax.clone(), theme.clone());
+            let tab_width = preferences.tab_width;
+            move |_: MouseEvent| match svg {
+                true => download_file(
+                    &format!("{file_name}.svg"),
+                    "image/svg+xml",
+                    &snippet::to_svg(&text, &syntax, &theme, tab_width),
+                ),
+                false => download_file(
+                    &format!("{file_name}.html"),
+                    "text/html",
+                    &snippet::to_html(&text, &syntax, &theme, tab_width),
+                ),
+            }
+        };
+        html! {
+            <div class="snippet">
+                <span>{title}</span>
                 <button
-                    id="settings-menu"
                     type="button"
-                    class="dropdown-menu"
-                    onclick={|event: MouseEvent| toggle_element(event, "settings-menu")}
-                    onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                    onclick={download(false)}
+                    onblur={|event: FocusEvent| handle_blur(event, "export-menu")}
                 >
-                    {"Settings"}
-                    <div tabindex="-1" onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}>
-                        <label>
-                            <strong>{"Theme: "}</strong>
-                            <select onchange={onchange_theme} id="theme" onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}>
-                                {themes}
-                            </select>
-                        </label>
-                    </div>
+                    {"HTML"}
                 </button>
                 <button
-                    id="info-menu"
                     type="button"
-                    class="dropdown-menu"
-                    onclick={|event: MouseEvent| toggle_element(event, "info-menu")}
-                    onblur={|event: FocusEvent| handle_blur(event, "info-menu")}
+                    onclick={download(true)}
+                    onblur={|event: FocusEvent| handle_blur(event, "export-menu")}
                 >
-                    {"Info"}
-                    <div tabindex="-1" onblur={|event: FocusEvent| handle_blur(event, "info-menu")}>
-                        <a href="https://crates.io/crates/rinja" title="Crates.io">
-                            <img
-                                src="https://img.shields.io/crates/v/rinja?logo=rust&style=flat-square&logoColor=white"
-                                alt="Crates.io"
-                            />
-                        </a>
-                        <a
-                            href="https://github.com/rinja-rs/rinja/actions/workflows/rust.yml"
-                            title="GitHub Workflow Status"
-                        >
-                            <img
-                                src="https://img.shields.io/github/actions/workflow/status/rinja-rs/rinja/rust.yml?\
-                                    branch=master&logo=github&style=flat-square&logoColor=white"
-                                alt="GitHub Workflow Status"
-                            />
-                        </a>
-                        <a href="https://rinja.readthedocs.io/" title="Book">
-                            <img
-                                src="https://img.shields.io/readthedocs/rinja?label=book&logo=readthedocs&style=flat-square&logoColor=white"
-                                alt="Book"
-                            />
-                        </a>
-                        <a href="https://docs.rs/rinja/" title="docs.rs">
-                            <img
-                                src="https://img.shields.io/docsrs/rinja?logo=docsdotrs&style=flat-square&logoColor=white"
-                                alt="docs.rs"
-                            />
-                        </a>
-                        <p>
-                            <strong>{"Rinja Revision:"}</strong>
-                            <br />
-                            <a href={TREE_URL} target="_blank">
-                                {env!("RINJA_DESCR")}
+                    {"SVG"}
+                </button>
+            </div>
+        }
+    })
+    .collect::<Html>();
+    let diagnostics = &state.compiled.diagnostics;
+    let output = match *output_tab {
+        OutputTab::Code => html! {
+            <Editor
+                text={Rc::clone(&state.compiled.code)}
+                syntax="Rust"
+                id="code"
+                theme={theme.clone()}
+                preferences={Rc::clone(&*preferences)}
+            />
+        },
+        OutputTab::Diagnostics if diagnostics.is_empty() => html! {
+            <p class="diagnostics">{"No diagnostics."}</p>
+        },
+        OutputTab::Diagnostics => html! {
+            <ul class="diagnostics">
+                {for diagnostics.iter().map(|msg| html!(<li><pre>{Rc::clone(msg)}</pre></li>))}
+            </ul>
+        },
+    };
+
+    html! {
+        <div class={classes!(EMBED.is_some().then_some("embed"))} {ondragover} {ondrop}>
+            <style>{AttrValue::clone(&chrome_style)}</style>
+            <style>{AttrValue::clone(&theme_style)}</style>
+            <style>{AttrValue::clone(&preferences_style)}</style>
+            if EMBED.is_none() {
+                <header>
+                    <button
+                        id="settings-menu"
+                        type="button"
+                        class="dropdown-menu"
+                        onclick={|event: MouseEvent| toggle_element(event, "settings-menu")}
+                        onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                    >
+                        {"Settings"}
+                        <div tabindex="-1" onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}>
+                            <label>
+                                <input
+                                    type="checkbox"
+                                    checked={theme_pair.is_some()}
+                                    onchange={onchange_follow_system}
+                                    onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                />
+                                {"Follow the system's light/dark mode"}
+                            </label>
+                            {theme_select}
+                            <div class="theme-buttons">
+                                <button
+                                    type="button"
+                                    onclick={run_command.reform(|_| Command::EditTheme)}
+                                    onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                >
+                                    {"Edit theme…"}
+                                </button>
+                                <label class="button" title="Or drop a .tmTheme file onto the page">
+                                    {"Import .tmTheme…"}
+                                    <input
+                                        type="file"
+                                        accept=".tmTheme"
+                                        multiple=true
+                                        onchange={onimport}
+                                        onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                    />
+                                </label>
+                            </div>
+                            {import_error.as_ref().map(|err| html!(<p class="error">{err}</p>))}
+                            <hr />
+                            <label>
+                                <strong>{"Font size: "}</strong>
+                                <input
+                                    type="number"
+                                    min={FONT_SIZES.start().to_string()}
+                                    max={FONT_SIZES.end().to_string()}
+                                    value={preferences.font_size.to_string()}
+                                    onchange={edit_preferences(|preferences, input| {
+                                        if let Ok(font_size) = input.value().parse() {
+                                            preferences.font_size = font_size;
+                                        }
+                                    })}
+                                    onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                />
+                                {"px"}
+                            </label>
+                            <label>
+                                <strong>{"Tab width: "}</strong>
+                                <input
+                                    type="number"
+                                    min={TAB_WIDTHS.start().to_string()}
+                                    max={TAB_WIDTHS.end().to_string()}
+                                    value={preferences.tab_width.to_string()}
+                                    onchange={edit_preferences(|preferences, input| {
+                                        if let Ok(tab_width) = input.value().parse() {
+                                            preferences.tab_width = tab_width;
+                                        }
+                                    })}
+                                    onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                />
+                            </label>
+                            <label>
+                                <input
+                                    type="checkbox"
+                                    checked={preferences.wrap}
+                                    onchange={edit_preferences(|preferences, input| {
+                                        preferences.wrap = input.checked();
+                                    })}
+                                    onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                />
+                                {"Wrap long lines"}
+                            </label>
+                            <label>
+                                <input
+                                    type="checkbox"
+                                    checked={preferences.show_whitespace}
+                                    onchange={edit_preferences(|preferences, input| {
+                                        preferences.show_whitespace = input.checked();
+                                    })}
+                                    onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                />
+                                {"Show whitespace"}
+                            </label>
+                            <label>
+                                <strong>{"Keys: "}</strong>
+                                <select
+                                    onchange={onchange_keymap}
+                                    onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                >
+                                    {for Keymap::ALL.iter().map(|&keymap| html! {
+                                        <option value={keymap.key()} selected={keymap == preferences.keymap}>
+                                            {keymap.title()}
+                                        </option>
+                                    })}
+                                </select>
+                            </label>
+                            <div class="theme-buttons">
+                                <label class="button" title="A monospace font works best">
+                                    {match &preferences.font {
+                                        Some(font) => format!("Font: {}", font.name),
+                                        None => "Upload font…".to_owned(),
+                                    }}
+                                    <input
+                                        type="file"
+                                        accept=".woff2,.woff,.ttf,.otf"
+                                        onchange={onchange_font}
+                                        onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                    />
+                                </label>
+                                if let Some(onreset_font) = onreset_font {
+                                    <button
+                                        type="button"
+                                        onclick={onreset_font}
+                                        onblur={|event: FocusEvent| handle_blur(event, "settings-menu")}
+                                    >
+                                        {"Use Hack"}
+                                    </button>
+                                }
+                            </div>
+                        </div>
+                    </button>
+                    <button
+                        id="examples-menu"
+                        type="button"
+                        class="dropdown-menu"
+                        onclick={|event: MouseEvent| toggle_element(event, "examples-menu")}
+                        onblur={|event: FocusEvent| handle_blur(event, "examples-menu")}
+                    >
+                        {"Examples"}
+                        <div tabindex="-1" onblur={|event: FocusEvent| handle_blur(event, "examples-menu")}>
+                            {examples}
+                        </div>
+                    </button>
+                    <WorkspacesMenu
+                        content={Rc::clone(&content)}
+                        onopen={open_workspace.clone()}
+                        from_link={*from_link}
+                    />
+                    <button
+                        id="info-menu"
+                        type="button"
+                        class="dropdown-menu"
+                        onclick={|event: MouseEvent| toggle_element(event, "info-menu")}
+                        onblur={|event: FocusEvent| handle_blur(event, "info-menu")}
+                    >
+                        {"Info"}
+                        <div tabindex="-1" onblur={|event: FocusEvent| handle_blur(event, "info-menu")}>
+                            <a href="https://crates.io/crates/rinja" title="Crates.io">
+                                <img
+                                    src="https://img.shields.io/crates/v/rinja?logo=rust&style=flat-square&logoColor=white"
+                                    alt="Crates.io"
+                                />
                             </a>
-                        </p>
+                            <a
+                                href="https://github.com/rinja-rs/rinja/actions/workflows/rust.yml"
+                                title="GitHub Workflow Status"
+                            >
+                                <img
+                                    src="https://img.shields.io/github/actions/workflow/status/rinja-rs/rinja/rust.yml?\
+                                        branch=master&logo=github&style=flat-square&logoColor=white"
+                                    alt="GitHub Workflow Status"
+                                />
+                            </a>
+                            <a href="https://rinja.readthedocs.io/" title="Book">
+                                <img
+                                    src="https://img.shields.io/readthedocs/rinja?label=book&logo=readthedocs&style=flat-square&logoColor=white"
+                                    alt="Book"
+                                />
+                            </a>
+                            <a href="https://docs.rs/rinja/" title="docs.rs">
+                                <img
+                                    src="https://img.shields.io/docsrs/rinja?logo=docsdotrs&style=flat-square&logoColor=white"
+                                    alt="docs.rs"
+                                />
+                            </a>
+                            <p>
+                                <strong>{"Rinja Revision:"}</strong>
+                                <br />
+                                <a href={TREE_URL} target="_blank">
+                                    {env!("RINJA_DESCR")}
+                                </a>
+                            </p>
+                        </div>
+                    </button>
+                    <button type="button" onclick={share.reform(|_| ())}>
+                        {"Share"}
+                    </button>
+                    <button
+                        type="button"
+                        title="Describe the current state for an issue of rinja"
+                        onclick={report_issue.reform(|_| ())}
+                    >
+                        {"Report issue"}
+                    </button>
+                    <button
+                        id="export-menu"
+                        type="button"
+                        class="dropdown-menu"
+                        onclick={|event: MouseEvent| toggle_element(event, "export-menu")}
+                        onblur={|event: FocusEvent| handle_blur(event, "export-menu")}
+                    >
+                        {"Export"}
+                        <div tabindex="-1" onblur={|event: FocusEvent| handle_blur(event, "export-menu")}>
+                            <button
+                                type="button"
+                                title="Download the struct and the template as a Cargo project"
+                                onclick={export_project.reform(|_| ())}
+                                onblur={|event: FocusEvent| handle_blur(event, "export-menu")}
+                            >
+                                {"Cargo project (.zip)"}
+                            </button>
+                            <button
+                                type="button"
+                                onclick={download_code.reform(|_| ())}
+                                onblur={|event: FocusEvent| handle_blur(event, "export-menu")}
+                            >
+                                {"Generated code (.rs)"}
+                            </button>
+                            <button
+                                type="button"
+                                onclick={run_command.reform(|_| Command::CopyCode)}
+                                onblur={|event: FocusEvent| handle_blur(event, "export-menu")}
+                            >
+                                {"Copy generated code"}
+                            </button>
+                            <hr />
+                            <p class="hint">{"Highlighted in the current theme:"}</p>
+                            {snippets}
+                        </div>
+                    </button>
+                    <label
+                        class="button"
+                        title="Struct, template and rinja.toml files, or a zip of a crate. \
+                            Or drop them onto the page."
+                    >
+                        {"Import…"}
+                        <input
+                            type="file"
+                            accept=".rs,.toml,.zip,.html,.htm,.txt,.xml,.svg,.md,.j2,.jinja"
+                            multiple=true
+                            onchange={onimport_files}
+                        />
+                    </label>
+                    <button
+                        type="button"
+                        title="Show all commands"
+                        onclick={run_command.reform(|_| Command::ShowCommands)}
+                    >
+                        {"Commands"}
+                    </button>
+                    <div id="fork">
+                        <a href="https://github.com/rinja-rs/play-rinja" title="Fork me on GitHub">
+                            <svg viewBox="0 0 250 250" aria-hidden="true">
+                                <path d="M0,0 L115,115 L130,115 L142,142 L250,250 L250,0 Z" />
+                                <path fill="currentColor" class="octo-arm" d="M128.3,109.0 C113.8,99.7 119.0,89.6 119.0,89.6 C122.0,82.7 120.5,78.6 120.5,78.6 C119.2,72.0 123.4,76.3 123.4,76.3 C127.3,80.9 125.5,87.3 125.5,87.3 C122.9,97.6 130.6,101.9 134.4,103.2" />
+                                <path fill="currentColor" class="octo-body" d="M115.0,115.0 C114.9,115.1 118.7,116.5 119.8,115.4 L133.7,101.6 C136.9,99.2 139.9,98.4 142.2,98.6 C133.8,88.0 127.5,74.4 143.8,58.0 C148.5,53.4 154.0,51.2 159.7,51.0 C160.3,49.4 163.2,43.6 171.4,40.1 C171.4,40.1 176.1,42.5 178.8,56.2 C183.1,58.6 187.2,61.8 190.9,65.4 C194.5,69.0 197.7,73.2 200.1,77.6 C213.8,80.2 216.3,84.9 216.3,84.9 C212.7,93.1 206.9,96.0 205.4,96.6 C205.1,102.4 203.0,107.8 198.3,112.5 C181.9,128.9 168.3,122.5 157.7,114.1 C157.9,116.9 156.7,120.9 152.7,124.9 L141.0,136.5 C139.8,137.7 141.6,141.9 141.8,141.8 Z" />
+                            </svg>
+                        </a>
                     </div>
-                </button>
-                <button type="button" onclick={saved_url_open}>
-                    {"Share"}
-                </button>
-                <div id="fork">
-                    <a href="https://github.com/rinja-rs/play-rinja" title="Fork me on GitHub">
-                        <svg viewBox="0 0 250 250" aria-hidden="true">
-                            <path d="M0,0 L115,115 L130,115 L142,142 L250,250 L250,0 Z" />
-                            <path fill="currentColor" class="octo-arm" d="M128.3,109.0 C113.8,99.7 119.0,89.6 119.0,89.6 C122.0,82.7 120.5,78.6 120.5,78.6 C119.2,72.0 123.4,76.3 123.4,76.3 C127.3,80.9 125.5,87.3 125.5,87.3 C122.9,97.6 130.6,101.9 134.4,103.2" />
-                            <path fill="currentColor" class="octo-body" d="M115.0,115.0 C114.9,115.1 118.7,116.5 119.8,115.4 L133.7,101.6 C136.9,99.2 139.9,98.4 142.2,98.6 C133.8,88.0 127.5,74.4 143.8,58.0 C148.5,53.4 154.0,51.2 159.7,51.0 C160.3,49.4 163.2,43.6 171.4,40.1 C171.4,40.1 176.1,42.5 178.8,56.2 C183.1,58.6 187.2,61.8 190.9,65.4 C194.5,69.0 197.7,73.2 200.1,77.6 C213.8,80.2 216.3,84.9 216.3,84.9 C212.7,93.1 206.9,96.0 205.4,96.6 C205.1,102.4 203.0,107.8 198.3,112.5 C181.9,128.9 168.3,122.5 157.7,114.1 C157.9,116.9 156.7,120.9 152.7,124.9 L141.0,136.5 C139.8,137.7 141.6,141.9 141.8,141.8 Z" />
-                        </svg>
-                    </a>
-                </div>
-            </header>
-            <form id="content" method="GET" action="javascript:;" {onsubmit}>
+                </header>
+            }
+            <form
+                id="content"
+                class={classes!((*split_layout).then_some("split"))}
+                method="GET"
+                action="javascript:;"
+                {onsubmit}
+            >
                 <div id="top">
                     <div>
                         <h3>
-                            <button class="reset" onclick={|event| reset_code(event, STRUCT_SOURCE)}>
-                                {"Reset code"}
-                            </button>
+                            if !read_only {
+                                <button class="reset" onclick={reset_code(&oninput_rust, STRUCT_SOURCE)}>
+                                    {"Reset code"}
+                                </button>
+                            }
                             {"Your struct:"}
                         </h3>
                         <Editor
                             text={Rc::clone(&state.rust)}
-                            oninput={oninput_rust}
+                            oninput={(!read_only).then_some(oninput_rust)}
                             syntax="Rust"
                             id="rust"
-                            {theme}
+                            history_key={EMBED.is_none().then_some(STRUCT_HISTORY_KEY)}
+                            theme={theme.clone()}
+                            preferences={Rc::clone(&*preferences)}
+                            registers={SharedRegisters::clone(&registers)}
                         />
                     </div>
                     <div>
                         <h3>
-                            <button class="reset" onclick={|event| reset_code(event, TMPL_SOURCE)}>
-                                {"Reset code"}
-                            </button>
+                            if !read_only {
+                                <button class="reset" onclick={reset_code(&oninput_tmpl, TMPL_SOURCE)}>
+                                    {"Reset code"}
+                                </button>
+                            }
                             {"Your template:"}
                         </h3>
                         <Editor
                             text={Rc::clone(&state.tmpl)}
-                            oninput={oninput_tmpl}
-                            syntax="HTML (Jinja2)"
+                            oninput={(!read_only).then_some(oninput_tmpl)}
+                            syntax={tmpl_syntax}
+                            tokens={Rc::clone(&*tmpl_tokens)}
                             id="tmpl"
-                            {theme}
+                            history_key={EMBED.is_none().then_some(TMPL_HISTORY_KEY)}
+                            theme={theme.clone()}
+                            preferences={Rc::clone(&*preferences)}
+                            registers={SharedRegisters::clone(&registers)}
                         />
+                        <details id="config">
+                            <summary>
+                                if !read_only {
+                                    <button class="reset" onclick={reset_code(&oninput_config, CONFIG_SOURCE)}>
+                                        {"Reset config"}
+                                    </button>
+                                }
+                                {"rinja.toml"}
+                            </summary>
+                            <Editor
+                                text={Rc::clone(&*config)}
+                                oninput={(!read_only).then_some(oninput_config)}
+                                syntax="TOML"
+                                id="config"
+                                theme={theme.clone()}
+                                preferences={Rc::clone(&*preferences)}
+                                registers={SharedRegisters::clone(&registers)}
+                            />
+                            {config_error.map(|err| html!(<p class="error">{err}</p>))}
+                        </details>
                     </div>
                 </div>
-                <div>
+                <div id="output">
                     <h3>
-                        {"Generated code:"}
-                        {state.duration.map(|d| format!(" (duration: {d:?})"))}
+                        <button type="button" class={tab_class(OutputTab::Code)} onclick={select_tab(OutputTab::Code)}>
+                            {"Generated code"}
+                        </button>
+                        <button type="button" class={tab_class(OutputTab::Diagnostics)} onclick={select_tab(OutputTab::Diagnostics)}>
+                            {format!("Diagnostics ({})", diagnostics.len())}
+                        </button>
+                        {state.compiled.duration.map(|d| format!(" (duration: {d:?})"))}
                     </h3>
-                    <Editor
-                        text={Rc::clone(&state.code)}
-                        syntax="Rust"
-                        id="code"
-                        {theme}
-                    />
+                    {output}
                 </div>
             </form>
             <dialog id="share_dialog" onclose={saved_url_onclose}>
                 <h3> {"Editor State URL"} </h3>
                 <p id="generated-url">{saved_url.as_ref().map(Rc::clone)}</p>
+                if let Some(saved_url) = &*saved_url {
+                    <p class="url-length">
+                        {format!("{} characters", saved_url.len())}
+                    </p>
+                    if let Some(warning) = url_warning(saved_url.len()) {
+                        <p class="warning">
+                            {warning}
+                            {" Consider sending the struct and the template as files instead."}
+                        </p>
+                    }
+                }
                 <div class="dialog-buttons">
                     <button type="button" onclick={saved_url_copy} autofocus=true>
                         {"copy"}
@@ -325,19 +1435,98 @@ pub fn App() -> Html {
                     </button>
                 </div>
             </dialog>
+            <dialog id="report_dialog" onclose={report_onclose}>
+                <h3>{"Issue Report"}</h3>
+                <p>
+                    {"Copy the report into a "}
+                    <a href={NEW_ISSUE_URL} target="_blank">{"new issue of rinja"}</a>
+                    {", and describe what you expected to happen."}
+                </p>
+                <textarea id="report-markdown" readonly=true value={report.as_ref().map(Rc::clone)} />
+                <div class="dialog-buttons">
+                    <button type="button" onclick={report_copy} autofocus=true>
+                        {"copy"}
+                    </button>
+                    <button type="button" onclick={report_close}>
+                        {"close"}
+                    </button>
+                </div>
+            </dialog>
+            {palette}
+            {theme_editor}
         </div>
     }
 }
 
 const THEME_SOURCE_KEY: &str = "play-rinja-theme";
+pub const CUSTOM_THEMES_KEY: &str = "play-rinja-custom-themes";
+pub const THEME_PAIR_KEY: &str = "play-rinja-theme-pair";
+pub const SHORTCUTS_KEY: &str = "play-rinja-shortcuts";
+pub const PREFERENCES_KEY: &str = "play-rinja-preferences";
+pub const EDITOR_FONT_KEY: &str = "play-rinja-editor-font";
+pub const WORKSPACE_KEY: &str = "play-rinja-workspace";
+pub const WORKSPACE_BASE_KEY: &str = "play-rinja-workspace-base";
+const LAYOUT_KEY: &str = "play-rinja-layout";
 const STRUCT_SOURCE_KEY: &str = "play-rinja-struct";
 const TMPL_SOURCE_KEY: &str = "play-rinja-template";
+const STRUCT_HISTORY_KEY: &str = "play-rinja-struct-history";
+const TMPL_HISTORY_KEY: &str = "play-rinja-template-history";
+const CONFIG_KEY: &str = "play-rinja-config";
+const TAB_KEY: &str = "play-rinja-tab";
+const EDITOR_STATE_KEYS: &[&str] = &[
+    THEME_SOURCE_KEY,
+    STRUCT_SOURCE_KEY,
+    TMPL_SOURCE_KEY,
+    CONFIG_KEY,
+];
 
-fn local_storage() -> Option<Storage> {
+pub fn local_storage() -> Option<Storage> {
     window()?.local_storage().unwrap_or_default()
 }
 
-fn save_to_local_storage(storage: &Storage, key: &str, data: &str) {
+pub fn session_storage() -> Option<Storage> {
+    window()?.session_storage().unwrap_or_default()
+}
+
+/// Where this tab keeps the state of its editors, so that other tabs don't overwrite it.
+/// A new tab starts with a copy of the state of the tab that was edited last.
+fn editor_storage() -> Option<Storage> {
+    // An embedded playground shows what the host page gives it, and doesn't keep it.
+    if EMBED.is_some() {
+        return None;
+    }
+    let Some(session) = session_storage() else {
+        return local_storage();
+    };
+    if let Ok(None) = session.get_item(TAB_KEY) {
+        if let Some(local) = local_storage() {
+            for key in EDITOR_STATE_KEYS {
+                if let Ok(Some(value)) = local.get_item(key) {
+                    let _ = session.set_item(key, &value);
+                }
+            }
+        }
+        let _ = session.set_item(TAB_KEY, "1");
+    }
+    Some(session)
+}
+
+/// Saves a part of the editors' state for this tab, and as the starting point of new tabs.
+fn save_editor_state(key: &str, data: &str) {
+    if EMBED.is_some() {
+        return;
+    }
+    for storage in [editor_storage(), local_storage()].into_iter().flatten() {
+        save_to_local_storage(&storage, key, data);
+    }
+}
+
+fn load_editor_state(key: &str) -> Option<Rc<str>> {
+    let raw = editor_storage()?.get_item(key).ok().flatten()?;
+    Some(Rc::from(JSON::parse(&raw).ok()?.as_string()?))
+}
+
+pub fn save_to_local_storage(storage: &Storage, key: &str, data: &str) {
     if let Ok(data) = JSON::stringify(&JsValue::from_str(data)) {
         if let Some(data) = data.as_string() {
             // Doesn't matter whether or not it succeeded.
@@ -346,21 +1535,103 @@ fn save_to_local_storage(storage: &Storage, key: &str, data: &str) {
     }
 }
 
-fn share_dialog() -> Option<HtmlDialogElement> {
+/// Removes the shared workspace from the address, and returns it, if there was one.
+fn take_saved_param() -> Option<String> {
+    let window = window()?;
+    let url = Url::new(&window.location().href().ok()?).ok()?;
+    let param = url.search_params().get("saved");
+    url.search_params().delete("saved");
+    let history = window.history().ok()?;
+    let state = history.state().unwrap_or(JsValue::NULL);
+    let _ = history.replace_state_with_url(&state, "", Some(&url.href()));
+    param
+}
+
+fn shared_url(param: &str) -> Option<String> {
+    let location = window()?.location().href().ok()?;
+    Some(
+        Url::new_with_base(&format!("?saved={param}"), &location)
+            .ok()?
+            .href(),
+    )
+}
+
+/// A shared link comes from someone else, so a theme it contains is only added if it is harmless
+/// and the user agrees. Returns what the user should know about the link.
+fn review_link(mut workspace: Workspace, custom_themes: &CustomThemes) -> (Workspace, Vec<String>) {
+    let mut notes = Vec::from_iter(workspace.revision_note());
+    if let Some(theme) = workspace.custom_theme.take() {
+        let name = workspace.theme.as_deref().unwrap_or_default();
+        let known = custom_themes
+            .get(name)
+            .is_some_and(|existing| *existing == theme);
+        let question = format!("Add the theme {name:?} of the link to your themes?");
+        match check_theme(name, &theme) {
+            // An embedded playground doesn't keep the theme, so there is nothing to agree to.
+            Ok(()) if known || EMBED.is_some() || confirm(&question) => {
+                workspace.custom_theme = Some(theme);
+            }
+            Ok(()) => {}
+            Err(err) => notes.push(format!("The theme of the link was left out: {err}")),
+        }
+        if workspace.custom_theme.is_none() {
+            workspace.theme = None;
+        }
+    }
+    (workspace, notes)
+}
+
+fn confirm(message: &str) -> bool {
+    window().is_some_and(|window| window.confirm_with_message(message).unwrap_or_default())
+}
+
+fn get_dialog(id: &str) -> Option<HtmlDialogElement> {
     Some(
         window()?
             .document()?
-            .get_element_by_id("share_dialog")?
+            .get_element_by_id(id)?
             .unchecked_into(),
     )
 }
 
+/// The browser, its language and the platform, as far as the browser tells.
+fn browser_info() -> Option<String> {
+    let navigator = window()?.navigator();
+    let mut info = navigator.user_agent().ok()?;
+    if let Some(language) = navigator.language() {
+        info = format!("{info} ({language})");
+    }
+    Some(info)
+}
+
+fn open_menu(id: &str) {
+    let Some(menu) = window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id(id))
+    else {
+        return;
+    };
+    let _ = menu.class_list().add_1("display");
+    if let Some(menu) = menu.dyn_ref::<HtmlElement>() {
+        let _ = menu.focus();
+    }
+}
+
+fn close_menu(id: &str) {
+    if let Some(menu) = window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id(id))
+    {
+        let _ = menu.class_list().remove_1("display");
+    }
+}
+
 // Read last editor state from local storage.
 // Then delete the known editor state.
 // Then, if the app did not crash while processing the retrieved state, save it again.
 fn get_last_editor_state() -> Option<(Option<Rc<str>>, Option<Rc<str>>, Option<Rc<str>>)> {
     let window = window()?;
-    let storage = window.local_storage().ok().flatten()?;
+    let storage = editor_storage()?;
 
     let mut theme = None;
     let mut rust = None;
@@ -408,6 +1679,74 @@ fn get_last_editor_state() -> Option<(Option<Rc<str>>, Option<Rc<str>>, Option<R
     Some((theme, rust, tmpl))
 }
 
+/// The `<option>`s of the bundled themes, followed by a group of the custom themes.
+fn theme_options(themes: &[(AttrValue, ThemeRef)], selected: &str) -> Html {
+    let option = |(value, _): &(AttrValue, ThemeRef)| {
+        html! {
+            <option value={value.clone()} selected={**value == *selected}>
+                {value.clone()}
+            </option>
+        }
+    };
+    let (_, bundled) = *ASSETS;
+    let (bundled, custom) = themes.split_at(bundled.len());
+    html! {
+        <>
+            {for bundled.iter().map(option)}
+            if !custom.is_empty() {
+                <optgroup label="Custom">
+                    {for custom.iter().map(option)}
+                </optgroup>
+            }
+        </>
+    }
+}
+
+/// The dragged files, if there are `.tmTheme` files among them.
+fn dragged_theme_files(ev: &DragEvent) -> Option<FileList> {
+    let files = ev.data_transfer()?.files()?;
+    (0..files.length())
+        .filter_map(|idx| files.get(idx))
+        .any(|file| file.name().to_ascii_lowercase().ends_with(".tmtheme"))
+        .then_some(files)
+}
+
+/// Reads the names and contents of `files`.
+fn read_theme_files(files: &FileList, onload: Callback<Vec<(String, String)>>) {
+    let callback = Closure::once(move |files: Array| {
+        let files = files
+            .iter()
+            .filter_map(|file| {
+                let file: Array = file.unchecked_into();
+                Some((file.get(0).as_string()?, file.get(1).as_string()?))
+            })
+            .collect();
+        onload.emit(files);
+    });
+    read_files(files, callback.into_js_value().unchecked_ref());
+}
+
+/// Reads the names and contents of `files`, which may be binary.
+fn read_project_files(files: &FileList, onload: Callback<Vec<(String, Vec<u8>)>>) {
+    let callback = Closure::once(move |files: Array| {
+        let files = files
+            .iter()
+            .filter_map(|file| {
+                let file: Array = file.unchecked_into();
+                let data: Uint8Array = file.get(1).dyn_into().ok()?;
+                Some((file.get(0).as_string()?, data.to_vec()))
+            })
+            .collect();
+        onload.emit(files);
+    });
+    read_binary_files(files, callback.into_js_value().unchecked_ref());
+}
+
+// Goes through `oninput`, so the editor's history can undo the reset.
+fn reset_code(oninput: &Callback<String>, text: &'static str) -> Callback<MouseEvent> {
+    oninput.reform(move |_: MouseEvent| text.to_owned())
+}
+
 fn replace_timeout(new_state: &mut Props, state: UseStateHandle<Props>) {
     let handler = Closure::<dyn Fn()>::new({
         let theme = Rc::clone(&new_state.theme);
@@ -415,13 +1754,11 @@ fn replace_timeout(new_state: &mut Props, state: UseStateHandle<Props>) {
         let tmpl = Rc::clone(&new_state.tmpl);
         let state = state.clone();
         move || {
-            let (code, duration) = convert_source(&rust, &tmpl);
             state.set(Props {
                 theme: Rc::clone(&theme),
                 rust: Rc::clone(&rust),
                 tmpl: Rc::clone(&tmpl),
-                code: Rc::from(code),
-                duration,
+                compiled: convert_source(&rust, &tmpl),
                 timeout: None,
             });
         }
@@ -439,13 +1776,101 @@ fn replace_timeout(new_state: &mut Props, state: UseStateHandle<Props>) {
         .ok();
 }
 
-fn convert_source(rust: &str, tmpl: &str) -> (String, Option<Duration>) {
+fn convert_source(rust: &str, tmpl: &str) -> Compiled {
     let mut code: TokenStream = parse_quote! { #[template(source = #tmpl)] };
     code.extend(rust.parse::<TokenStream>());
     let (code, duration) = time_it(|| derive_template(code));
+    let mut diagnostics = Vec::new();
+    collect_diagnostics(code.clone(), &mut diagnostics);
     let mut code = unparse(&parse2(code).unwrap_at());
     code.truncate(code.trim_end().len());
-    (code, duration)
+    Compiled {
+        code: Rc::from(code),
+        duration,
+        diagnostics: Rc::from(diagnostics),
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct TemplateArgs {
+    ext: Option<String>,
+    syntax: Option<String>,
+    /// The named fields of the struct, if it is one
+    fields: Option<Vec<String>>,
+}
+
+/// The arguments of the `#[template]` attribute in the struct's source that matter for highlighting.
+fn template_args(rust: &str) -> TemplateArgs {
+    let mut args = TemplateArgs::default();
+    let Ok(file) = parse_file(rust) else {
+        return args;
+    };
+    for item in file.items {
+        let (attrs, fields) = match item {
+            Item::Enum(item) => (item.attrs, None),
+            Item::Struct(item) => (item.attrs, Some(item.fields)),
+            Item::Union(item) => (item.attrs, None),
+            _ => continue,
+        };
+        if let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident("template")) {
+            let _ = attr.parse_nested_meta(|meta| {
+                let value = meta.value()?;
+                if meta.path.is_ident("ext") {
+                    args.ext = Some(value.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("syntax") {
+                    args.syntax = Some(value.parse::<LitStr>()?.value());
+                } else {
+                    value.parse::<Expr>()?;
+                }
+                Ok(())
+            });
+            args.fields = match fields {
+                Some(Fields::Named(fields)) => Some(
+                    fields
+                        .named
+                        .iter()
+                        .filter_map(|field| Some(field.ident.as_ref()?.unraw().to_string()))
+                        .collect(),
+                ),
+                Some(Fields::Unit) => Some(Vec::new()),
+                _ => None,
+            };
+            return args;
+        }
+    }
+    args
+}
+
+fn compiled_message<'a>(state: &'a Props, config: &'a str) -> EmbedMessage<'a> {
+    EmbedMessage::Compiled {
+        rust: &state.rust,
+        tmpl: &state.tmpl,
+        config,
+        code: &state.compiled.code,
+        diagnostics: &state.compiled.diagnostics,
+        duration: duration_ms(state.compiled.duration),
+    }
+}
+
+fn collect_diagnostics(code: TokenStream, diagnostics: &mut Vec<Rc<str>>) {
+    let tokens = code.into_iter().collect::<Vec<_>>();
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Group(group) => collect_diagnostics(group.stream(), diagnostics),
+            TokenTree::Ident(ident) if ident == "compile_error" => {
+                let [TokenTree::Punct(bang), TokenTree::Group(args), ..] = &tokens[idx + 1..]
+                else {
+                    continue;
+                };
+                if bang.as_char() == '!' {
+                    if let Ok(message) = parse2::<LitStr>(args.stream()) {
+                        diagnostics.push(Rc::from(message.value()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 fn time_it<F: FnOnce() -> R, R>(func: F) -> (R, Option<Duration>) {
@@ -461,10 +1886,11 @@ fn time_it<F: FnOnce() -> R, R>(func: F) -> (R, Option<Duration>) {
 }
 
 const DEFAULT_THEME: &str = "Monokai Extended Origin";
+const DEFAULT_LIGHT_THEME: &str = "Monokai Extended Light";
 
-const TREE_URL: &str = concat!(env!("RINJA_URL"), "/tree/", env!("RINJA_REV"));
+pub const TREE_URL: &str = concat!(env!("RINJA_URL"), "/tree/", env!("RINJA_REV"));
 
-const TMPL_SOURCE: &str = r##"<div class="example">
+pub const TMPL_SOURCE: &str = r##"<div class="example">
     Hello, <strong>{{user}}</strong>!
     {%~ if first_visit -%}
         <br />
@@ -472,7 +1898,7 @@ const TMPL_SOURCE: &str = r##"<div class="example">
     {%~ endif -%}
 </div>"##;
 
-const STRUCT_SOURCE: &str = r##"#[derive(Template)]
+pub const STRUCT_SOURCE: &str = r##"#[derive(Template)]
 #[template(ext = "html")]
 // in the preview, the `source="…"` or `path="…"` argument is provided for you
 struct HelloWorld<'a> {
@@ -480,12 +1906,30 @@ struct HelloWorld<'a> {
     first_visit: bool,
 }"##;
 
+pub const CONFIG_SOURCE: &str = r##"# The playground can't read a config file when compiling, but it uses
+# the `[[syntax]]` delimiters to highlight your template.
+
+[general]
+default_syntax = "default"
+
+[[syntax]]
+name = "custom"
+block_start = "<%"
+block_end = "%>"
+expr_start = "<<"
+expr_end = ">>"
+comment_start = "<#"
+comment_end = "#>""##;
+
 #[wasm_bindgen]
 extern "C" {
-    fn gen_saved_url(callback: &Function);
-    fn read_saved_url(callback: &Function);
     fn save_clipboard(text: &str);
+    fn download_file(name: &str, mime: &str, data: &str);
+    #[wasm_bindgen(js_name = download_file)]
+    fn download_bytes(name: &str, mime: &str, data: &[u8]);
+    fn read_files(files: &FileList, callback: &Function);
+    fn read_binary_files(files: &FileList, callback: &Function);
+    fn read_data_url(file: &File, callback: &Function);
     fn toggle_element(event: MouseEvent, elementId: &str);
     fn handle_blur(event: FocusEvent, elementId: &str);
-    fn reset_code(event: MouseEvent, text: &str);
 }
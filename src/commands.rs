@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use web_sys::wasm_bindgen::JsCast;
+use web_sys::{HtmlDialogElement, HtmlInputElement};
+use yew::{
+    Callback, Event, Html, InputEvent, KeyboardEvent, MouseEvent, Properties, classes,
+    function_component, html, use_effect_with, use_node_ref, use_state,
+};
+
+use crate::app::{SHORTCUTS_KEY, local_storage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Command {
+    ShowCommands,
+    CompileNow,
+    Share,
+    ReportIssue,
+    ExportProject,
+    DownloadCode,
+    CopyCode,
+    NextTheme,
+    PreviousTheme,
+    EditTheme,
+    IncreaseFontSize,
+    DecreaseFontSize,
+    ResetFontSize,
+    SwitchLayout,
+    ResetEditors,
+    OpenExamples,
+    ToggleOutputTab,
+}
+
+impl Command {
+    pub const ALL: &[Self] = &[
+        Self::ShowCommands,
+        Self::CompileNow,
+        Self::Share,
+        Self::ReportIssue,
+        Self::ExportProject,
+        Self::DownloadCode,
+        Self::CopyCode,
+        Self::NextTheme,
+        Self::PreviousTheme,
+        Self::EditTheme,
+        Self::IncreaseFontSize,
+        Self::DecreaseFontSize,
+        Self::ResetFontSize,
+        Self::SwitchLayout,
+        Self::ResetEditors,
+        Self::OpenExamples,
+        Self::ToggleOutputTab,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::ShowCommands => "Show all commands",
+            Self::CompileNow => "Compile now",
+            Self::Share => "Share editor state",
+            Self::ReportIssue => "Report an issue of rinja",
+            Self::ExportProject => "Export as Cargo project",
+            Self::DownloadCode => "Download generated code",
+            Self::CopyCode => "Copy generated code",
+            Self::NextTheme => "Switch to next theme",
+            Self::PreviousTheme => "Switch to previous theme",
+            Self::EditTheme => "Edit theme",
+            Self::IncreaseFontSize => "Increase editor font size",
+            Self::DecreaseFontSize => "Decrease editor font size",
+            Self::ResetFontSize => "Reset editor font size",
+            Self::SwitchLayout => "Switch layout",
+            Self::ResetEditors => "Reset struct and template",
+            Self::OpenExamples => "Open examples",
+            Self::ToggleOutputTab => "Toggle generated code / diagnostics",
+        }
+    }
+
+    /// Ctrl+Alt is AltGr on Windows, which types characters, so no default uses both.
+    fn default_shortcut(self) -> Option<&'static str> {
+        match self {
+            Self::ShowCommands => Some("Mod+Shift+P"),
+            Self::CompileNow => Some("Mod+Enter"),
+            Self::Share => Some("Mod+S"),
+            Self::ExportProject
+            | Self::DownloadCode
+            | Self::ReportIssue
+            | Self::CopyCode
+            | Self::NextTheme
+            | Self::PreviousTheme
+            | Self::EditTheme
+            | Self::IncreaseFontSize
+            | Self::DecreaseFontSize
+            | Self::ResetFontSize
+            | Self::SwitchLayout
+            | Self::ResetEditors
+            | Self::OpenExamples
+            | Self::ToggleOutputTab => None,
+        }
+    }
+}
+
+/// A key combination. "Mod" is Ctrl, or Cmd on a Mac.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortcut {
+    modifier: bool,
+    alt: bool,
+    shift: bool,
+    key: String,
+}
+
+impl Shortcut {
+    /// Returns `None` if only modifier keys are pressed, or if AltGr is held to type a character.
+    pub fn from_event(ev: &KeyboardEvent) -> Option<Self> {
+        if ev.get_modifier_state("AltGraph") {
+            return None;
+        }
+        let key = match ev.key().as_str() {
+            "" | "Alt" | "AltGraph" | "Control" | "Meta" | "Shift" | "Unidentified" => {
+                return None;
+            }
+            // Alt composes other characters on a Mac, e.g. "∑" for Alt+W, so the physical key
+            // is meant.
+            key if ev.alt_key() && (key == "Dead" || !key.is_ascii()) => physical_key(&ev.code())?,
+            "Dead" => return None,
+            key => key.to_lowercase(),
+        };
+        Some(Self {
+            modifier: ev.ctrl_key() || ev.meta_key(),
+            alt: ev.alt_key(),
+            shift: ev.shift_key(),
+            key,
+        })
+    }
+
+    /// Keys without Ctrl, Cmd or Alt are left to the editors and inputs.
+    pub fn has_modifier(&self) -> bool {
+        self.modifier || self.alt
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let mut shortcut = Self {
+            modifier: false,
+            alt: false,
+            shift: false,
+            key: String::new(),
+        };
+        for part in s.split('+') {
+            match part {
+                "Mod" => shortcut.modifier = true,
+                "Alt" => shortcut.alt = true,
+                "Shift" => shortcut.shift = true,
+                key if shortcut.key.is_empty() && !key.is_empty() => {
+                    shortcut.key = key.to_lowercase()
+                }
+                _ => return None,
+            }
+        }
+        (!shortcut.key.is_empty()).then_some(shortcut)
+    }
+}
+
+/// The letter or digit of a `KeyboardEvent.code`, like "w" for "KeyW".
+fn physical_key(code: &str) -> Option<String> {
+    if let Some(letter) = code.strip_prefix("Key") {
+        Some(letter.to_ascii_lowercase())
+    } else {
+        code.strip_prefix("Digit").map(str::to_owned)
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (active, name) in [
+            (self.modifier, "Mod+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+        ] {
+            if active {
+                f.write_str(name)?;
+            }
+        }
+        let mut key = self.key.chars();
+        if let Some(first) = key.next() {
+            write!(f, "{}{}", first.to_uppercase(), key.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shortcuts(HashMap<Command, Option<Shortcut>>);
+
+impl Shortcuts {
+    /// Defaults, overridden by the bindings the user saved.
+    pub fn load() -> Self {
+        let saved = local_storage()
+            .and_then(|storage| storage.get_item(SHORTCUTS_KEY).ok().flatten())
+            .and_then(|data| serde_json::from_str::<HashMap<String, String>>(&data).ok())
+            .unwrap_or_default();
+        // Bindings of commands that don't exist anymore are dropped.
+        let saved = saved
+            .into_iter()
+            .filter_map(|(command, shortcut)| {
+                let command = serde_json::from_value(serde_json::Value::String(command)).ok()?;
+                Some((command, shortcut))
+            })
+            .collect::<HashMap<Command, _>>();
+        let map = Command::ALL
+            .iter()
+            .map(|&command| {
+                let shortcut = match saved.get(&command) {
+                    Some(shortcut) => Shortcut::parse(shortcut),
+                    None => command.default_shortcut().and_then(Shortcut::parse),
+                };
+                (command, shortcut)
+            })
+            .collect();
+        Self(map)
+    }
+
+    pub fn save(&self) {
+        let saved = self
+            .0
+            .iter()
+            .map(|(&command, shortcut)| {
+                let shortcut = shortcut.as_ref().map(|s| s.to_string());
+                (command, shortcut.unwrap_or_default())
+            })
+            .collect::<HashMap<_, _>>();
+        if let (Some(storage), Ok(data)) = (local_storage(), serde_json::to_string(&saved)) {
+            // Doesn't matter whether or not it succeeded.
+            let _ = storage.set_item(SHORTCUTS_KEY, &data);
+        }
+    }
+
+    pub fn get(&self, command: Command) -> Option<&Shortcut> {
+        self.0.get(&command)?.as_ref()
+    }
+
+    /// Binds `command` to `shortcut`, removing the shortcut from any other command.
+    pub fn set(&mut self, command: Command, shortcut: Option<Shortcut>) {
+        if let Some(shortcut) = &shortcut {
+            for value in self.0.values_mut() {
+                if value.as_ref() == Some(shortcut) {
+                    *value = None;
+                }
+            }
+        }
+        self.0.insert(command, shortcut);
+    }
+
+    pub fn find(&self, ev: &KeyboardEvent) -> Option<Command> {
+        let pressed = Shortcut::from_event(ev)?;
+        Command::ALL
+            .iter()
+            .copied()
+            .find(|&command| self.get(command) == Some(&pressed))
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct CommandPaletteProps {
+    pub open: bool,
+    pub shortcuts: Rc<Shortcuts>,
+    pub onrun: Callback<Command>,
+    pub onrebind: Callback<(Command, Option<Shortcut>)>,
+    pub onclose: Callback<()>,
+}
+
+#[function_component]
+pub fn CommandPalette(props: &CommandPaletteProps) -> Html {
+    let dialog = use_node_ref();
+    let input = use_node_ref();
+    let filter = use_state(String::new);
+    let selected = use_state(|| 0_usize);
+    let rebinding = use_state(|| Option::<Command>::None);
+
+    use_effect_with(props.open, {
+        let dialog = dialog.clone();
+        let input = input.clone();
+        let filter = filter.clone();
+        let selected = selected.clone();
+        let rebinding = rebinding.clone();
+        move |&open| {
+            let Some(dialog) = dialog.cast::<HtmlDialogElement>() else {
+                return;
+            };
+            if open {
+                filter.set(String::new());
+                selected.set(0);
+                rebinding.set(None);
+                let _ = dialog.show_modal();
+                if let Some(input) = input.cast::<HtmlInputElement>() {
+                    let _ = input.focus();
+                }
+            } else if dialog.open() {
+                dialog.close();
+            }
+        }
+    });
+
+    let needle = filter.to_lowercase();
+    let commands = Command::ALL
+        .iter()
+        .copied()
+        .filter(|&command| command != Command::ShowCommands)
+        .filter(|command| command.title().to_lowercase().contains(&needle))
+        .collect::<Vec<_>>();
+    let selected_idx = (*selected).min(commands.len().saturating_sub(1));
+
+    let run = {
+        let onrun = props.onrun.clone();
+        let onclose = props.onclose.clone();
+        move |command: Command| {
+            onclose.emit(());
+            onrun.emit(command);
+        }
+    };
+
+    let oninput = {
+        let filter = filter.clone();
+        let selected = selected.clone();
+        move |ev: InputEvent| {
+            let Some(target) = ev.target() else { return };
+            let target: HtmlInputElement = target.unchecked_into();
+            filter.set(target.value());
+            selected.set(0);
+        }
+    };
+
+    let onkeydown = {
+        let commands = commands.clone();
+        let selected = selected.clone();
+        let rebinding = rebinding.clone();
+        let onrebind = props.onrebind.clone();
+        let run = run.clone();
+        move |ev: KeyboardEvent| {
+            if let Some(command) = *rebinding {
+                ev.prevent_default();
+                ev.stop_propagation();
+                match ev.key().as_str() {
+                    "Escape" => rebinding.set(None),
+                    "Backspace" | "Delete" => {
+                        onrebind.emit((command, None));
+                        rebinding.set(None);
+                    }
+                    _ => {
+                        let shortcut = Shortcut::from_event(&ev);
+                        if let Some(shortcut) = shortcut.filter(Shortcut::has_modifier) {
+                            onrebind.emit((command, Some(shortcut)));
+                            rebinding.set(None);
+                        }
+                    }
+                }
+                return;
+            }
+
+            match ev.key().as_str() {
+                "ArrowDown" => selected.set((selected_idx + 1) % commands.len().max(1)),
+                "ArrowUp" => selected.set(
+                    selected_idx
+                        .checked_sub(1)
+                        .unwrap_or(commands.len().saturating_sub(1)),
+                ),
+                "Enter" => match commands.get(selected_idx) {
+                    Some(&command) => run(command),
+                    None => return,
+                },
+                _ => return,
+            }
+            ev.prevent_default();
+        }
+    };
+
+    let items = commands
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(idx, command)| {
+            let shortcut = match *rebinding == Some(command) {
+                true => "Press a key with Mod or Alt…".to_owned(),
+                false => props
+                    .shortcuts
+                    .get(command)
+                    .map_or_else(|| "—".to_owned(), |s| s.to_string()),
+            };
+            let onclick = {
+                let run = run.clone();
+                move |_: MouseEvent| run(command)
+            };
+            let onrebind = {
+                let rebinding = rebinding.clone();
+                move |_: MouseEvent| rebinding.set(Some(command))
+            };
+            html! {
+                <li class={classes!((idx == selected_idx).then_some("selected"))}>
+                    <button type="button" class="command" {onclick}>
+                        {command.title()}
+                    </button>
+                    <button
+                        type="button"
+                        class="shortcut"
+                        title="Change shortcut (Backspace removes it)"
+                        onclick={onrebind}
+                    >
+                        <kbd>{shortcut}</kbd>
+                    </button>
+                </li>
+            }
+        })
+        .collect::<Html>();
+
+    let onclose = {
+        let onclose = props.onclose.clone();
+        move |_: Event| onclose.emit(())
+    };
+
+    html! {
+        <dialog id="command_palette" ref={dialog} {onclose} {onkeydown}>
+            <input
+                ref={input}
+                type="search"
+                placeholder="Type a command…"
+                value={(*filter).clone()}
+                {oninput}
+            />
+            <ul>{items}</ul>
+            <p class="hint">{"Mod is Ctrl, or ⌘ on a Mac. Click a shortcut to change it."}</p>
+        </dialog>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        let shortcut = Shortcut::parse("Mod+Shift+P").unwrap();
+        assert!(shortcut.has_modifier());
+        assert_eq!(shortcut.to_string(), "Mod+Shift+P");
+        assert_eq!(
+            Shortcut::parse("Alt+enter").unwrap().to_string(),
+            "Alt+Enter"
+        );
+        assert!(!Shortcut::parse("Shift+x").unwrap().has_modifier());
+        assert_eq!(Shortcut::parse("Mod+"), None);
+        assert_eq!(Shortcut::parse("Mod+a+b"), None);
+    }
+
+    #[test]
+    fn defaults() {
+        for &command in Command::ALL {
+            if let Some(default) = command.default_shortcut() {
+                let shortcut = Shortcut::parse(default).unwrap();
+                assert!(shortcut.has_modifier(), "{command:?}");
+                assert!(!(shortcut.modifier && shortcut.alt), "{command:?}");
+                assert_eq!(shortcut.to_string(), default);
+            }
+        }
+    }
+}
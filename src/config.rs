@@ -0,0 +1,88 @@
+use serde::Deserialize;
+
+/// The delimiters of a template syntax, as configured in a `[[syntax]]` table of `rinja.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Delimiters {
+    pub block_start: String,
+    pub block_end: String,
+    pub expr_start: String,
+    pub expr_end: String,
+    pub comment_start: String,
+    pub comment_end: String,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Self {
+            block_start: "{%".to_owned(),
+            block_end: "%}".to_owned(),
+            expr_start: "{{".to_owned(),
+            expr_end: "}}".to_owned(),
+            comment_start: "{#".to_owned(),
+            comment_end: "#}".to_owned(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawConfig {
+    #[serde(default)]
+    general: General,
+    #[serde(default)]
+    syntax: Vec<RawSyntax>,
+}
+
+#[derive(Default, Deserialize)]
+struct General {
+    default_syntax: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawSyntax {
+    name: String,
+    block_start: Option<String>,
+    block_end: Option<String>,
+    expr_start: Option<String>,
+    expr_end: Option<String>,
+    comment_start: Option<String>,
+    comment_end: Option<String>,
+}
+
+/// The delimiters of the syntax `name`, or of the `default_syntax`, as configured in `config`.
+pub fn delimiters(config: &str, name: Option<&str>) -> Result<Delimiters, String> {
+    let config = basic_toml::from_str::<RawConfig>(config).map_err(|err| err.to_string())?;
+    let Some(name) = name.or(config.general.default_syntax.as_deref()) else {
+        return Ok(Delimiters::default());
+    };
+    let Some(syntax) = config.syntax.into_iter().find(|syntax| syntax.name == name) else {
+        return match name {
+            "default" => Ok(Delimiters::default()),
+            name => Err(format!("syntax {name:?} does not exist")),
+        };
+    };
+
+    let default = Delimiters::default();
+    let delimiters = Delimiters {
+        block_start: syntax.block_start.unwrap_or(default.block_start),
+        block_end: syntax.block_end.unwrap_or(default.block_end),
+        expr_start: syntax.expr_start.unwrap_or(default.expr_start),
+        expr_end: syntax.expr_end.unwrap_or(default.expr_end),
+        comment_start: syntax.comment_start.unwrap_or(default.comment_start),
+        comment_end: syntax.comment_end.unwrap_or(default.comment_end),
+    };
+    for (kind, delimiter) in [
+        ("block_start", &delimiters.block_start),
+        ("block_end", &delimiters.block_end),
+        ("expr_start", &delimiters.expr_start),
+        ("expr_end", &delimiters.expr_end),
+        ("comment_start", &delimiters.comment_start),
+        ("comment_end", &delimiters.comment_end),
+    ] {
+        if delimiter.len() < 2 {
+            return Err(format!(
+                "delimiter {kind} must be at least two characters long"
+            ));
+        }
+    }
+    Ok(delimiters)
+}
@@ -0,0 +1,591 @@
+use std::ops::Range;
+
+/// The default number of spaces per indentation level.
+pub const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Template,
+}
+
+impl Language {
+    pub fn from_syntax(syntax: &str) -> Self {
+        match syntax {
+            "Rust" => Self::Rust,
+            _ => Self::Template,
+        }
+    }
+}
+
+/// Replace `range` with `text`, then select `selection`.
+///
+/// All offsets are byte offsets; `range` refers to the old text, `selection` to the new one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+    pub selection: Range<usize>,
+}
+
+impl Edit {
+    fn insert(sel: &Range<usize>, text: String, cursor: usize) -> Self {
+        let cursor = sel.start + cursor;
+        Self {
+            range: sel.clone(),
+            text,
+            selection: cursor..cursor,
+        }
+    }
+
+    fn select(selection: Range<usize>) -> Self {
+        Self {
+            range: selection.start..selection.start,
+            text: String::new(),
+            selection,
+        }
+    }
+}
+
+pub fn indent(text: &str, sel: Range<usize>, tab_width: usize) -> Edit {
+    let lines = selected_lines(text, &sel);
+    if !text[sel.clone()].contains('\n') {
+        let column = text[lines.start..sel.start].chars().count();
+        let width = tab_width - column % tab_width;
+        return Edit::insert(&sel, " ".repeat(width), width);
+    }
+
+    let edits = line_starts(text, lines.clone())
+        .filter(|&start| !is_blank_line(text, start))
+        .map(|start| (start..start, " ".repeat(tab_width)))
+        .collect::<Vec<_>>();
+    apply_edits(text, lines, edits, sel)
+}
+
+pub fn outdent(text: &str, sel: Range<usize>, tab_width: usize) -> Edit {
+    let lines = selected_lines(text, &sel);
+    let edits = line_starts(text, lines.clone())
+        .filter_map(|start| {
+            let width = text[start..]
+                .bytes()
+                .take(tab_width)
+                .take_while(|&c| c == b' ')
+                .count();
+            let width = match (width, text.as_bytes().get(start)) {
+                (0, Some(b'\t')) => 1,
+                (width, _) => width,
+            };
+            (width > 0).then(|| (start..start + width, String::new()))
+        })
+        .collect::<Vec<_>>();
+    apply_edits(text, lines, edits, sel)
+}
+
+pub fn newline(text: &str, sel: Range<usize>, language: Language, tab_width: usize) -> Edit {
+    let start = line_start(text, sel.start);
+    let before = &text[start..sel.start];
+    let indent = &before[..before.len() - before.trim_start().len()];
+    let before = before.trim_end();
+    let after = text[sel.end..].trim_start_matches([' ', '\t']);
+
+    let opener = before
+        .chars()
+        .next_back()
+        .filter(|c| matches!(c, '(' | '[' | '{'));
+    let nested =
+        opener.is_some() || (language == Language::Template && ends_with_block_opener(before));
+    if !nested {
+        return Edit::insert(&sel, format!("\n{indent}"), 1 + indent.len());
+    }
+
+    let closed = opener
+        .and_then(closer)
+        .is_some_and(|c| after.starts_with(c));
+    let mut insert = format!("\n{indent}{:tab_width$}", "");
+    let cursor = insert.len();
+    if closed {
+        insert.push('\n');
+        insert.push_str(indent);
+    }
+    let end = sel.end + (text[sel.end..].len() - after.len());
+    Edit::insert(&(sel.start..end), insert, cursor)
+}
+
+/// Called before `typed` is inserted; `None` lets the browser insert it normally.
+pub fn auto_close(text: &str, sel: Range<usize>, typed: char, language: Language) -> Option<Edit> {
+    let prev = text[..sel.start].chars().next_back();
+    let next = text[sel.end..].chars().next();
+
+    if sel.is_empty() && language == Language::Template && prev == Some('{') && next == Some('}') {
+        let close = match typed {
+            '{' => '}',
+            '%' => '%',
+            '#' => '#',
+            _ => return auto_close_pair(sel, typed, prev, next, language, ""),
+        };
+        return Some(Edit::insert(
+            &(sel.start..sel.end + 1),
+            format!("{typed}  {close}}}"),
+            2,
+        ));
+    }
+    if sel.is_empty() && next == Some(typed) && matches!(typed, ')' | ']' | '}' | '"' | '\'') {
+        return Some(Edit::select(sel.end + 1..sel.end + 1));
+    }
+    auto_close_pair(sel.clone(), typed, prev, next, language, &text[sel])
+}
+
+fn auto_close_pair(
+    sel: Range<usize>,
+    typed: char,
+    prev: Option<char>,
+    next: Option<char>,
+    language: Language,
+    selected: &str,
+) -> Option<Edit> {
+    let close = closer(typed)?;
+    if typed == '\'' && language == Language::Rust {
+        // lifetimes and `char`s are too different to guess
+        return None;
+    }
+
+    if !selected.is_empty() {
+        let start = sel.start + typed.len_utf8();
+        let selection = start..start + selected.len();
+        return Some(Edit {
+            range: sel,
+            text: format!("{typed}{selected}{close}"),
+            selection,
+        });
+    }
+
+    let quote = typed == close;
+    if quote && prev.is_some_and(|c| c.is_alphanumeric() || c == typed) {
+        return None;
+    }
+    if next.is_some_and(|c| !c.is_whitespace() && !matches!(c, ')' | ']' | '}' | ',' | ';')) {
+        return None;
+    }
+    Some(Edit::insert(
+        &sel,
+        format!("{typed}{close}"),
+        typed.len_utf8(),
+    ))
+}
+
+/// Removes an empty auto-closed pair, or indentation up to the previous tab stop.
+pub fn backspace(text: &str, sel: Range<usize>, tab_width: usize) -> Option<Edit> {
+    if !sel.is_empty() || sel.start == 0 {
+        return None;
+    }
+    let pos = sel.start;
+
+    for (open, close) in [("{{ ", " }}"), ("{% ", " %}"), ("{# ", " #}")] {
+        if text[..pos].ends_with(open) && text[pos..].starts_with(close) {
+            return Some(Edit::insert(
+                &(pos - open.len()..pos + close.len()),
+                String::new(),
+                0,
+            ));
+        }
+    }
+    let prev = text[..pos].chars().next_back()?;
+    if closer(prev).is_some_and(|c| text[pos..].starts_with(c)) {
+        return Some(Edit::insert(&(pos - 1..pos + 1), String::new(), 0));
+    }
+
+    let start = line_start(text, pos);
+    let before = &text[start..pos];
+    if before.is_empty() || before.bytes().any(|c| c != b' ') {
+        return None;
+    }
+    let width = match before.len() % tab_width {
+        0 => tab_width,
+        width => width,
+    };
+    Some(Edit::insert(&(pos - width..pos), String::new(), 0))
+}
+
+pub fn toggle_comment(text: &str, sel: Range<usize>, language: Language) -> Edit {
+    match language {
+        Language::Rust => toggle_line_comment(text, sel),
+        Language::Template => toggle_block_comment(text, sel),
+    }
+}
+
+fn toggle_line_comment(text: &str, sel: Range<usize>) -> Edit {
+    let lines = selected_lines(text, &sel);
+    let starts = line_starts(text, lines.clone())
+        .filter(|&start| !is_blank_line(text, start))
+        .collect::<Vec<_>>();
+    let content = |start: usize| &text[start..line_end(text, start)];
+    let indent = |start: usize| {
+        let line = content(start);
+        line.len() - line.trim_start().len()
+    };
+
+    let commented = !starts.is_empty()
+        && starts
+            .iter()
+            .all(|&start| content(start).trim_start().starts_with("//"));
+    let edits = if commented {
+        starts
+            .into_iter()
+            .map(|start| {
+                let start = start + indent(start);
+                let width = match text[start..].starts_with("// ") {
+                    true => 3,
+                    false => 2,
+                };
+                (start..start + width, String::new())
+            })
+            .collect()
+    } else {
+        let column = starts.iter().map(|&start| indent(start)).min().unwrap_or(0);
+        starts
+            .into_iter()
+            .map(|start| (start + column..start + column, "// ".to_owned()))
+            .collect()
+    };
+    apply_edits(text, lines, edits, sel)
+}
+
+fn toggle_block_comment(text: &str, sel: Range<usize>) -> Edit {
+    let target = if sel.is_empty() {
+        let start = line_start(text, sel.start);
+        let line = &text[start..line_end(text, start)];
+        let content = line.trim();
+        let start = start + (line.len() - line.trim_start().len());
+        start..start + content.len()
+    } else {
+        let selected = &text[sel.clone()];
+        let start = sel.start + (selected.len() - selected.trim_start().len());
+        start..start + selected.trim().len()
+    };
+
+    let content = &text[target.clone()];
+    let edits = if content.starts_with("{#") && content.ends_with("#}") && content.len() >= 4 {
+        let open = match content.starts_with("{# ") {
+            true => 3,
+            false => 2,
+        };
+        let close = match content[open..].ends_with(" #}") {
+            true => 3,
+            false => 2,
+        };
+        vec![
+            (target.start..target.start + open, String::new()),
+            (target.end - close..target.end, String::new()),
+        ]
+    } else {
+        vec![
+            (target.start..target.start, "{# ".to_owned()),
+            (target.end..target.end, " #}".to_owned()),
+        ]
+    };
+    apply_edits(text, target, edits, sel)
+}
+
+/// Combine sorted, non-overlapping `edits` inside of `region` into a single [`Edit`].
+fn apply_edits(
+    text: &str,
+    region: Range<usize>,
+    edits: Vec<(Range<usize>, String)>,
+    sel: Range<usize>,
+) -> Edit {
+    let map = |pos: usize, stick_to_start: bool| {
+        let mut new_pos = pos;
+        for (range, insert) in &edits {
+            if range.end < pos || (range.end == pos && !(range.is_empty() && stick_to_start)) {
+                new_pos = new_pos + insert.len() - range.len();
+            } else if range.start < pos {
+                new_pos -= pos - range.start;
+                break;
+            } else {
+                break;
+            }
+        }
+        new_pos
+    };
+    let selection = match sel.is_empty() {
+        true => map(sel.start, false)..map(sel.end, false),
+        false => map(sel.start, true)..map(sel.end, false),
+    };
+
+    let mut replacement = String::with_capacity(region.len());
+    let mut pos = region.start;
+    for (range, insert) in &edits {
+        replacement.push_str(&text[pos..range.start]);
+        replacement.push_str(insert);
+        pos = range.end;
+    }
+    replacement.push_str(&text[pos..region.end]);
+
+    Edit {
+        range: region,
+        text: replacement,
+        selection,
+    }
+}
+
+fn closer(open: char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        _ => None,
+    }
+}
+
+fn ends_with_block_opener(line: &str) -> bool {
+    let Some(tag) = line.strip_suffix("%}") else {
+        return false;
+    };
+    let Some((_, tag)) = tag.rsplit_once("{%") else {
+        return false;
+    };
+    let keyword = tag
+        .trim_start_matches(['-', '+', '~'])
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+    matches!(
+        keyword,
+        "block" | "call" | "elif" | "else" | "filter" | "for" | "if" | "macro" | "match" | "when"
+    )
+}
+
+pub fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |idx| idx + 1)
+}
+
+pub fn line_end(text: &str, pos: usize) -> usize {
+    text[pos..].find('\n').map_or(text.len(), |idx| pos + idx)
+}
+
+fn is_blank_line(text: &str, start: usize) -> bool {
+    text[start..line_end(text, start)].trim().is_empty()
+}
+
+/// Whole lines touched by `sel`. A selection that ends in column 0 does not include that line.
+fn selected_lines(text: &str, sel: &Range<usize>) -> Range<usize> {
+    let start = line_start(text, sel.start);
+    let end = match sel.end > sel.start && text[..sel.end].ends_with('\n') {
+        true => sel.end - 1,
+        false => sel.end,
+    };
+    start..line_end(text, end)
+}
+
+fn line_starts(text: &str, lines: Range<usize>) -> impl Iterator<Item = usize> + '_ {
+    let start = lines.start;
+    std::iter::once(start).chain(
+        text[lines]
+            .match_indices('\n')
+            .map(move |(idx, _)| start + idx + 1),
+    )
+}
+
+/// Convert an offset into the textarea's UTF-16 value into a byte offset.
+pub fn utf16_to_byte(text: &str, pos: u32) -> usize {
+    let mut remaining = pos as usize;
+    for (idx, c) in text.char_indices() {
+        if remaining == 0 {
+            return idx;
+        }
+        remaining = remaining.saturating_sub(c.len_utf16());
+    }
+    text.len()
+}
+
+pub fn byte_to_utf16(text: &str, pos: usize) -> u32 {
+    text[..pos].encode_utf16().count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str, edit: Edit) -> (String, Range<usize>) {
+        let mut text = text.to_owned();
+        text.replace_range(edit.range, &edit.text);
+        (text, edit.selection)
+    }
+
+    #[test]
+    fn indent_and_outdent() {
+        // Up to the next tab stop
+        assert_eq!(
+            apply("ab", indent("ab", 1..1, 4)),
+            ("a   b".to_owned(), 4..4)
+        );
+        assert_eq!(apply("ab", indent("ab", 1..1, 2)), ("a b".to_owned(), 2..2));
+
+        // The line after a selection that ends in column 0 is not indented, nor are blank lines.
+        let text = "a\n\nb\nc";
+        let (indented, selection) = apply(text, indent(text, 0..5, 4));
+        assert_eq!(indented, "    a\n\n    b\nc");
+        assert_eq!(selection, 0..13);
+
+        let (outdented, selection) = apply(&indented, outdent(&indented, selection, 4));
+        assert_eq!(outdented, text);
+        assert_eq!(selection, 0..5);
+
+        let text = "    a\n  b\n\tc\nd";
+        assert_eq!(apply(text, outdent(text, 2..13, 4)).0, "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn utf16_offsets() {
+        let text = "a😀b";
+        assert_eq!(utf16_to_byte(text, 0), 0);
+        assert_eq!(utf16_to_byte(text, 1), 1);
+        assert_eq!(utf16_to_byte(text, 3), 5);
+        assert_eq!(utf16_to_byte(text, 4), 6);
+        assert_eq!(utf16_to_byte(text, 10), 6);
+        // Between the two halves of the surrogate pair
+        assert_eq!(utf16_to_byte(text, 2), 5);
+        assert_eq!(byte_to_utf16(text, 5), 3);
+        assert_eq!(byte_to_utf16(text, 6), 4);
+
+        // The column counts characters, not bytes.
+        let text = "😀x";
+        assert_eq!(
+            apply(text, indent(text, 4..4, 4)),
+            ("😀   x".to_owned(), 7..7)
+        );
+    }
+
+    #[test]
+    fn newlines() {
+        let text = "fn f() {}";
+        assert_eq!(
+            apply(text, newline(text, 8..8, Language::Rust, 4)),
+            ("fn f() {\n    \n}".to_owned(), 13..13),
+        );
+        let text = "    let x = 1;";
+        assert_eq!(
+            apply(text, newline(text, 14..14, Language::Rust, 4)),
+            ("    let x = 1;\n    ".to_owned(), 19..19),
+        );
+        let text = "{% if x %}";
+        assert_eq!(
+            apply(text, newline(text, 10..10, Language::Template, 2)),
+            ("{% if x %}\n  ".to_owned(), 13..13),
+        );
+    }
+
+    #[test]
+    fn auto_closing() {
+        let close = |text: &str, sel: Range<usize>, typed, language| {
+            auto_close(text, sel.clone(), typed, language).map(|edit| apply(text, edit))
+        };
+        assert_eq!(
+            close("", 0..0, '(', Language::Rust),
+            Some(("()".to_owned(), 1..1)),
+        );
+        assert_eq!(
+            close("x;", 0..0, '[', Language::Rust),
+            None,
+            "not in front of a word",
+        );
+        assert_eq!(
+            close("a", 1..1, '"', Language::Template),
+            None,
+            "not after a word",
+        );
+        assert_eq!(close("", 0..0, '\'', Language::Rust), None);
+        assert_eq!(
+            close("", 0..0, '\'', Language::Template),
+            Some(("''".to_owned(), 1..1)),
+        );
+
+        // The selection is wrapped.
+        assert_eq!(
+            close("ab", 0..2, '[', Language::Rust),
+            Some(("[ab]".to_owned(), 1..3)),
+        );
+
+        // Typing the closing character skips over it.
+        assert_eq!(
+            close("()", 1..1, ')', Language::Rust),
+            Some(("()".to_owned(), 2..2)),
+        );
+        assert_eq!(
+            close("\"\"", 1..1, '"', Language::Rust),
+            Some(("\"\"".to_owned(), 2..2)),
+        );
+
+        // Template tags
+        assert_eq!(
+            close("{}", 1..1, '{', Language::Template),
+            Some(("{{  }}".to_owned(), 3..3)),
+        );
+        assert_eq!(
+            close("{}", 1..1, '%', Language::Template),
+            Some(("{%  %}".to_owned(), 3..3)),
+        );
+        assert_eq!(
+            close("{}", 1..1, '#', Language::Template),
+            Some(("{#  #}".to_owned(), 3..3)),
+        );
+    }
+
+    #[test]
+    fn smart_backspace() {
+        let delete =
+            |text: &str, pos: usize| backspace(text, pos..pos, 4).map(|edit| apply(text, edit));
+        assert_eq!(delete("{{  }}", 3), Some((String::new(), 0..0)));
+        assert_eq!(delete("x{%  %}y", 4), Some(("xy".to_owned(), 1..1)));
+        assert_eq!(delete("[]", 1), Some((String::new(), 0..0)));
+
+        // Back to the previous tab stop
+        assert_eq!(delete("      x", 6), Some(("    x".to_owned(), 4..4)));
+        assert_eq!(delete("    x", 4), Some(("x".to_owned(), 0..0)));
+
+        // Otherwise the browser deletes a single character.
+        assert_eq!(delete("a b", 2), None);
+        assert_eq!(delete("  a", 3), None);
+        assert_eq!(delete("", 0), None);
+        assert_eq!(backspace("    ", 0..4, 4), None);
+    }
+
+    #[test]
+    fn comments() {
+        let text = "  a\n\n    b";
+        let (commented, selection) =
+            apply(text, toggle_comment(text, 0..text.len(), Language::Rust));
+        assert_eq!(commented, "  // a\n\n  //   b");
+        let (uncommented, _) = apply(
+            &commented,
+            toggle_comment(&commented, selection, Language::Rust),
+        );
+        assert_eq!(uncommented, text);
+
+        // A mix of commented and uncommented lines is commented again as a whole.
+        let text = "// a\nb";
+        assert_eq!(
+            apply(text, toggle_comment(text, 0..6, Language::Rust)).0,
+            "// // a\n// b",
+        );
+
+        let text = "  {{ x }}\n";
+        let (commented, _) = apply(text, toggle_comment(text, 0..0, Language::Template));
+        assert_eq!(commented, "  {# {{ x }} #}\n");
+        let (uncommented, _) = apply(
+            &commented,
+            toggle_comment(&commented, 0..0, Language::Template),
+        );
+        assert_eq!(uncommented, text);
+        assert_eq!(
+            apply(
+                "a{#b#}c",
+                toggle_comment("a{#b#}c", 1..6, Language::Template)
+            )
+            .0,
+            "abc",
+        );
+    }
+}
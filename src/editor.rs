@@ -1,24 +1,67 @@
-use std::fmt::Write;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::iter::Peekable;
+use std::ops::Range;
 use std::rc::Rc;
+use std::slice;
 
-use syntect::easy::HighlightLines;
-use syntect::highlighting::{Color, FontStyle, Theme};
-use syntect::util::LinesWithEndings;
-use web_sys::HtmlTextAreaElement;
+use syntect::highlighting::Color;
+use wasm_bindgen::prelude::wasm_bindgen;
+use web_sys::js_sys::Date;
 use web_sys::wasm_bindgen::JsCast;
-use yew::{Callback, Html, InputEvent, Properties, function_component, html};
+use web_sys::wasm_bindgen::prelude::Closure;
+use web_sys::{ClipboardEvent, Element, HtmlTextAreaElement, window};
+use yew::{
+    AttrValue, Callback, Event, Html, InputEvent, KeyboardEvent, MouseEvent, NodeRef, Properties,
+    classes, function_component, hook, html, use_effect_with, use_force_update, use_mut_ref,
+    use_node_ref, use_state, use_state_eq,
+};
 
-use crate::{ASSETS, ThrowAt};
+use crate::ThrowAt;
+use crate::app::session_storage;
+use crate::editing::{
+    Edit, Language, TAB_WIDTH, auto_close, backspace, byte_to_utf16, indent, newline, outdent,
+    toggle_comment, utf16_to_byte,
+};
+use crate::folding::{FoldKey, FoldedView, PLACEHOLDER, regions};
+use crate::highlight::{CODE_CLASS, ScopeOp, SharedLineCache};
+use crate::history::History;
+use crate::keymap::{Action, Key, Keymap, KeymapState, SharedRegisters};
+use crate::preferences::Preferences;
+use crate::semantic::Token;
+use crate::themes::ThemeRef;
+
+/// Editors with more lines only highlight the lines near the viewport.
+const VIRTUAL_LINES: usize = 1000;
+/// How many lines to highlight above and below the viewport.
+const VIRTUAL_MARGIN: usize = 50;
 
 #[derive(Properties, PartialEq)]
 pub struct EditorProps {
     pub text: Rc<str>,
-    pub syntax: &'static str,
-    pub theme: &'static Theme,
+    pub syntax: AttrValue,
+    pub theme: ThemeRef,
     #[prop_or_default]
     pub oninput: Option<Callback<String>>,
     #[prop_or_default]
     pub id: Option<&'static str>,
+    #[prop_or_default]
+    pub history_key: Option<&'static str>,
+    /// Identifiers that get classes of their own, on top of the syntax highlighting.
+    #[prop_or_default]
+    pub tokens: Rc<[Token]>,
+    #[prop_or_default]
+    pub preferences: Rc<Preferences>,
+    #[prop_or_default]
+    pub registers: SharedRegisters,
+}
+
+#[derive(Default)]
+struct Session {
+    history: Option<History>,
+    /// Selection in the full text, to restore once the text was rendered.
+    restore_selection: Option<(u32, u32)>,
+    save_timeout: Option<i32>,
 }
 
 #[function_component]
@@ -29,21 +72,240 @@ pub fn Editor(props: &EditorProps) -> Html {
         theme,
         oninput,
         id,
+        history_key,
+        tokens,
+        preferences,
+        registers,
     } = props;
+
+    let textarea = use_node_ref();
+    let session = use_mut_ref(|| Session {
+        history: history_key.map(|key| load_history(key, text)),
+        ..Session::default()
+    });
+    let folded = use_state(BTreeSet::<FoldKey>::new);
+
+    let regions = regions(text, Language::from_syntax(syntax));
+    let view = Rc::new(FoldedView::new(text, &regions, &folded));
+
+    use_effect_with(Rc::clone(&view.text), {
+        let textarea = textarea.clone();
+        let session = Rc::clone(&session);
+        let view = Rc::clone(&view);
+        let text = Rc::clone(text);
+        move |_: &Rc<str>| {
+            let Some(textarea) = textarea.cast::<HtmlTextAreaElement>() else {
+                return;
+            };
+            if let Some((start, end)) = session.borrow_mut().restore_selection.take() {
+                let to_visible = |pos| {
+                    let pos = view.to_visible(utf16_to_byte(&text, pos));
+                    byte_to_utf16(&view.text, pos)
+                };
+                let _ = textarea.set_selection_range(to_visible(start), to_visible(end));
+            }
+        }
+    });
+
+    use_effect_with(Rc::clone(text), {
+        let textarea = textarea.clone();
+        let session = Rc::clone(&session);
+        let view = Rc::clone(&view);
+        let history_key = *history_key;
+        move |text: &Rc<str>| {
+            let Some(textarea) = textarea.cast::<HtmlTextAreaElement>() else {
+                return;
+            };
+            let mut current = session.borrow_mut();
+            if let (Some(history), Some(key)) = (&mut current.history, history_key) {
+                let selection = full_selection(&textarea, &view, text);
+                history.record(Rc::clone(text), selection, Date::now());
+                save_history(key, &session, &mut current.save_timeout);
+            }
+        }
+    });
+
+    let oninput = oninput.clone().map(|oninput| {
+        let view = Rc::clone(&view);
+        let text = Rc::clone(text);
+        Callback::from(move |visible: String| match view.is_folded() {
+            true => oninput.emit(view.apply_edit(&text, &visible)),
+            false => oninput.emit(visible),
+        })
+    });
+
+    let onundo = oninput.as_ref().filter(|_| history_key.is_some()).map(|_| {
+        let session = Rc::clone(&session);
+        let oninput = props.oninput.clone().unwrap_at();
+        Callback::from(move |undo: bool| {
+            let mut session = session.borrow_mut();
+            let Session {
+                history: Some(history),
+                restore_selection,
+                ..
+            } = &mut *session
+            else {
+                return;
+            };
+            let entry = match undo {
+                true => history.undo(),
+                false => history.redo(),
+            };
+            if let Some(entry) = entry {
+                *restore_selection = Some(entry.selection);
+                oninput.emit(entry.text.to_string());
+            }
+        })
+    });
+
+    let (oncopy, oncut) = match view.is_folded() {
+        true => {
+            let copy = |cut: bool| {
+                let view = Rc::clone(&view);
+                let text = Rc::clone(text);
+                Callback::from(move |ev: Event| copy_full_text(&ev, &view, &text, cut))
+            };
+            (Some(copy(false)), oninput.is_some().then(|| copy(true)))
+        }
+        false => (None, None),
+    };
+
+    let ontoggle = {
+        let folded = folded.clone();
+        let textarea = textarea.clone();
+        let session = Rc::clone(&session);
+        let view = Rc::clone(&view);
+        let text = Rc::clone(text);
+        Callback::from(move |key: FoldKey| {
+            if let Some(textarea) = textarea.cast::<HtmlTextAreaElement>() {
+                let selection = full_selection(&textarea, &view, &text);
+                session.borrow_mut().restore_selection = Some(selection);
+            }
+            let mut new_folded = BTreeSet::clone(&folded);
+            if !new_folded.remove(&key) {
+                new_folded.insert(key);
+            }
+            folded.set(new_folded);
+        })
+    };
+
+    let hidden = view.hidden().cloned().collect::<Rc<[_]>>();
+    let markers = regions
+        .into_iter()
+        .filter(|region| {
+            !hidden
+                .iter()
+                .any(|range| range.start < region.line_start && region.line_start < range.end)
+        })
+        .map(|region| FoldMarker {
+            folded: folded.contains(&region.key),
+            pos: region.line_start,
+            key: region.key,
+        })
+        .collect::<Rc<[_]>>();
+
+    // Long lines don't wrap in a virtualized editor or if wrapping is disabled, so the code needs
+    // to scroll along.
+    let code = use_node_ref();
+    let cache = use_state(SharedLineCache::default);
+    let virtualized = cache.borrow_mut().update(text, syntax).len() > VIRTUAL_LINES;
+    let nowrap = virtualized || !preferences.wrap;
+    let onscroll = nowrap.then(|| {
+        let code = code.clone();
+        Callback::from(move |ev: Event| {
+            let (Some(target), Some(code)) = (ev.target(), code.cast::<Element>()) else {
+                return;
+            };
+            code.set_scroll_left(target.unchecked_into::<Element>().scroll_left());
+        })
+    });
+
     html! {
-        <div class="editor">
-            <UnstylizedCode text={Rc::clone(text)} theme={*theme} {oninput} {id} />
-            <StylizedCode text={Rc::clone(text)} syntax={*syntax} theme={*theme} />
+        <div class={classes!("editor", nowrap.then_some("nowrap"))}>
+            <UnstylizedCode
+                text={Rc::clone(&view.text)}
+                syntax={syntax.clone()}
+                theme={theme.clone()}
+                node_ref={textarea}
+                tab_width={preferences.tab_width}
+                keymap={preferences.keymap}
+                registers={registers.clone()}
+                {oninput}
+                {onundo}
+                {oncopy}
+                {oncut}
+                {onscroll}
+                {id}
+            />
+            <StylizedCode
+                text={Rc::clone(text)}
+                cache={cache.current()}
+                node_ref={code}
+                {virtualized}
+                {hidden}
+                {markers}
+                tokens={Rc::clone(tokens)}
+                show_whitespace={preferences.show_whitespace}
+                {ontoggle}
+            />
         </div>
     }
 }
 
+/// The textarea's selection as UTF-16 offsets into the full text.
+fn full_selection(textarea: &HtmlTextAreaElement, view: &FoldedView, full: &str) -> (u32, u32) {
+    let (start, end) = selection(textarea);
+    let to_full = |pos, end| {
+        let pos = view.to_full(utf16_to_byte(&view.text, pos), end);
+        byte_to_utf16(full, pos)
+    };
+    (to_full(start, false), to_full(end, true))
+}
+
+fn copy_full_text(ev: &Event, view: &FoldedView, full: &str, cut: bool) {
+    let Some(target) = ev.target() else { return };
+    let textarea: HtmlTextAreaElement = target.unchecked_into();
+    let Some(data) = ev.unchecked_ref::<ClipboardEvent>().clipboard_data() else {
+        return;
+    };
+
+    let (start, end) = selection(&textarea);
+    let range = utf16_to_byte(&view.text, start)..utf16_to_byte(&view.text, end);
+    let range = view.to_full(range.start, false)..view.to_full(range.end, true);
+    if data.set_data("text/plain", &full[range]).is_ok() {
+        ev.prevent_default();
+        if cut {
+            replace_text(&textarea, start, end, "", start, start);
+        }
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct UnstylizedCodeProps {
     pub text: Rc<str>,
-    pub theme: &'static Theme,
+    pub syntax: AttrValue,
+    pub theme: ThemeRef,
+    #[prop_or_default]
+    pub node_ref: NodeRef,
+    /// How many spaces the Tab key inserts
+    #[prop_or(TAB_WIDTH)]
+    pub tab_width: usize,
+    #[prop_or_default]
+    pub keymap: Keymap,
+    /// Shared by the editors, so that text can be yanked in one and pasted in another.
+    #[prop_or_default]
+    pub registers: SharedRegisters,
     #[prop_or_default]
     pub oninput: Option<Callback<String>>,
+    /// `true` to undo, `false` to redo; the browser's own history is used if unset.
+    #[prop_or_default]
+    pub onundo: Option<Callback<bool>>,
+    #[prop_or_default]
+    pub oncopy: Option<Callback<Event>>,
+    #[prop_or_default]
+    pub oncut: Option<Callback<Event>>,
+    #[prop_or_default]
+    pub onscroll: Option<Callback<Event>>,
     #[prop_or_default]
     pub id: Option<&'static str>,
 }
@@ -64,6 +326,45 @@ pub fn UnstylizedCode(props: &UnstylizedCodeProps) -> Html {
         })
     });
 
+    // After pressing Escape, Tab moves the focus out of the editor like it normally would.
+    let tab_released = use_mut_ref(|| false);
+    // The mode of the keymap starts over if another keymap is chosen.
+    let keymap = use_mut_ref(|| KeymapState::new(props.keymap));
+    if keymap.borrow().keymap() != props.keymap {
+        *keymap.borrow_mut() = KeymapState::new(props.keymap);
+    }
+    let rerender = use_force_update();
+    let onkeydown = props.oninput.is_some().then(|| {
+        let language = Language::from_syntax(&props.syntax);
+        let tab_width = props.tab_width;
+        let onundo = props.onundo.clone();
+        let keymap = Rc::clone(&keymap);
+        let registers = props.registers.clone();
+        Callback::from(move |ev: KeyboardEvent| {
+            let mut keymap = keymap.borrow_mut();
+            let status = keymap.status();
+            handle_keydown(
+                &ev,
+                language,
+                tab_width,
+                &mut keymap,
+                &registers,
+                &mut tab_released.borrow_mut(),
+                onundo.as_ref(),
+            );
+            if keymap.status() != status {
+                rerender.force_update();
+            }
+        })
+    });
+    let (keymap_class, keymap_status) = match props.oninput.is_some() {
+        true => {
+            let keymap = keymap.borrow();
+            (keymap.class(), keymap.status())
+        }
+        false => (None, None),
+    };
+
     let settings = &props.theme.settings;
     let caret = settings
         .caret
@@ -71,7 +372,10 @@ pub fn UnstylizedCode(props: &UnstylizedCodeProps) -> Html {
         .unwrap_or(Color::BLACK);
 
     html! {
+        <>
         <textarea
+            ref={props.node_ref.clone()}
+            class={keymap_class}
             autocapitalize="off"
             spellcheck="false"
             readonly={oninput.is_none()}
@@ -79,96 +383,587 @@ pub fn UnstylizedCode(props: &UnstylizedCodeProps) -> Html {
             style={format!("caret-color:#{:02x}{:02x}{:02x};", caret.r, caret.b, caret.b)}
             id={props.id}
             {oninput}
+            {onkeydown}
+            oncopy={props.oncopy.clone()}
+            oncut={props.oncut.clone()}
+            onscroll={props.onscroll.clone()}
         />
+        if let Some(status) = keymap_status {
+            <span class="keymap-status">{status}</span>
+        }
+        </>
+    }
+}
+
+fn handle_keydown(
+    ev: &KeyboardEvent,
+    language: Language,
+    tab_width: usize,
+    keymap: &mut KeymapState,
+    registers: &SharedRegisters,
+    tab_released: &mut bool,
+    onundo: Option<&Callback<bool>>,
+) {
+    if ev.is_composing() {
+        return;
+    }
+    let key = ev.key();
+    let released = std::mem::replace(tab_released, key == "Escape");
+    if key == "Tab" && released {
+        return;
+    }
+
+    if let (Some(target), Some(key)) = (ev.target(), Key::from_event(ev)) {
+        let target: HtmlTextAreaElement = target.unchecked_into();
+        let text = target.value();
+        let (start, end) = selection(&target);
+        let sel = utf16_to_byte(&text, start)..utf16_to_byte(&text, end);
+        match keymap.handle(&key, &text, sel, &mut registers.borrow_mut(), tab_width) {
+            Action::Pass => {}
+            Action::Consumed => {
+                ev.prevent_default();
+                return;
+            }
+            Action::Edit(edit) => {
+                ev.prevent_default();
+                apply_edit(&target, &text, edit);
+                return;
+            }
+            Action::Undo(undo) => {
+                ev.prevent_default();
+                if let Some(onundo) = onundo {
+                    onundo.emit(undo);
+                }
+                return;
+            }
+        }
+    }
+
+    let modifier = ev.ctrl_key() || ev.meta_key();
+    let undo = match key.to_ascii_lowercase().as_str() {
+        "z" if modifier && !ev.alt_key() => Some(!ev.shift_key()),
+        "y" if ev.ctrl_key() && !ev.alt_key() && !ev.shift_key() => Some(false),
+        _ => None,
+    };
+    if let (Some(undo), Some(onundo)) = (undo, onundo) {
+        ev.prevent_default();
+        onundo.emit(undo);
+        return;
+    }
+
+    let Some(target) = ev.target() else { return };
+    let target: HtmlTextAreaElement = target.unchecked_into();
+    let (Ok(Some(start)), Ok(Some(end))) = (target.selection_start(), target.selection_end())
+    else {
+        return;
+    };
+    let text = target.value();
+    let sel = utf16_to_byte(&text, start)..utf16_to_byte(&text, end);
+
+    if let Some(edit) = smart_edit(ev, &key, &text, sel, language, tab_width) {
+        ev.prevent_default();
+        apply_edit(&target, &text, edit);
+    }
+}
+
+fn selection(textarea: &HtmlTextAreaElement) -> (u32, u32) {
+    let start = textarea
+        .selection_start()
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let end = textarea.selection_end().ok().flatten().unwrap_or(start);
+    (start, end)
+}
+
+/// The history is kept per tab, so that undoing in one tab doesn't replay the edits of another.
+fn load_history(key: &str, text: &Rc<str>) -> History {
+    let history = session_storage()
+        .and_then(|storage| storage.get_item(key).ok().flatten())
+        .and_then(|data| serde_json::from_str::<History>(&data).ok());
+    match history {
+        Some(mut history) => {
+            // The text could have been loaded from a shared URL, so make it undoable.
+            history.record(Rc::clone(text), (0, 0), Date::now());
+            history
+        }
+        None => History::new(Rc::clone(text)),
+    }
+}
+
+/// The history is only serialized once the typing paused, not for every keystroke.
+fn save_history(key: &'static str, session: &Rc<RefCell<Session>>, timeout: &mut Option<i32>) {
+    let handler = Closure::once({
+        let session = Rc::clone(session);
+        move || {
+            let mut session = session.borrow_mut();
+            session.save_timeout = None;
+            let Some(history) = &session.history else {
+                return;
+            };
+            if let (Some(storage), Ok(data)) = (session_storage(), serde_json::to_string(history)) {
+                // Doesn't matter whether or not it succeeded.
+                let _ = storage.set_item(key, &data);
+            }
+        }
+    });
+
+    let window = window().unwrap_at();
+    if let Some(timeout) = timeout.take() {
+        window.clear_timeout_with_handle(timeout);
     }
+    *timeout = window
+        .set_timeout_with_callback_and_timeout_and_arguments_0(
+            handler.into_js_value().unchecked_ref(),
+            500,
+        )
+        .ok();
+}
+
+fn smart_edit(
+    ev: &KeyboardEvent,
+    key: &str,
+    text: &str,
+    sel: Range<usize>,
+    language: Language,
+    tab_width: usize,
+) -> Option<Edit> {
+    let modifier = ev.ctrl_key() || ev.meta_key();
+    let plain = !modifier && !ev.alt_key();
+    match key {
+        "Tab" if plain && ev.shift_key() => Some(outdent(text, sel, tab_width)),
+        "Tab" if plain => Some(indent(text, sel, tab_width)),
+        "Enter" if plain && !ev.shift_key() => Some(newline(text, sel, language, tab_width)),
+        "Backspace" if plain => backspace(text, sel, tab_width),
+        "/" if modifier && !ev.alt_key() => Some(toggle_comment(text, sel, language)),
+        _ => {
+            let mut chars = key.chars();
+            let (Some(typed), None) = (chars.next(), chars.next()) else {
+                return None;
+            };
+            // AltGr is reported as Ctrl+Alt
+            if ev.meta_key() || (ev.ctrl_key() && !ev.alt_key()) {
+                return None;
+            }
+            auto_close(text, sel, typed, language)
+        }
+    }
+}
+
+fn apply_edit(target: &HtmlTextAreaElement, text: &str, edit: Edit) {
+    let Edit {
+        range,
+        text: insert,
+        selection,
+    } = edit;
+    let new_text = [&text[..range.start], &insert, &text[range.end..]].concat();
+    replace_text(
+        target,
+        byte_to_utf16(text, range.start),
+        byte_to_utf16(text, range.end),
+        &insert,
+        byte_to_utf16(&new_text, selection.start),
+        byte_to_utf16(&new_text, selection.end),
+    );
 }
 
 #[derive(Properties, PartialEq)]
 pub struct StylizedCodeProps {
     pub text: Rc<str>,
-    pub syntax: &'static str,
-    pub theme: &'static Theme,
+    /// Updated with `text` and the syntax by the editor
+    pub cache: SharedLineCache,
+    #[prop_or_default]
+    pub node_ref: NodeRef,
+    /// Only highlight the lines near the viewport, the others are plain text.
+    #[prop_or_default]
+    pub virtualized: bool,
+    /// Sorted ranges of `text` that are replaced by a placeholder
+    #[prop_or_default]
+    pub hidden: Rc<[Range<usize>]>,
+    #[prop_or_default]
+    pub markers: Rc<[FoldMarker]>,
+    /// Sorted, non-overlapping identifiers of `text`
+    #[prop_or_default]
+    pub tokens: Rc<[Token]>,
+    /// Render spaces and tabs as visible marks
+    #[prop_or_default]
+    pub show_whitespace: bool,
+    #[prop_or_default]
+    pub ontoggle: Option<Callback<FoldKey>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoldMarker {
+    /// Start of the line that gets the marker
+    pub pos: usize,
+    pub key: FoldKey,
+    pub folded: bool,
 }
 
+/// The colors come from the stylesheet of the selected theme, see
+/// [`theme_css()`](crate::highlight::theme_css).
 #[function_component]
 pub fn StylizedCode(props: &StylizedCodeProps) -> Html {
-    let (syntax_set, _) = *ASSETS;
-    let syntax = syntax_set.find_syntax_by_name(props.syntax).unwrap_at();
-
-    let theme = props.theme;
-    let fg = theme.settings.foreground.unwrap_or(Color::BLACK);
-    let bg = theme.settings.background.unwrap_or(Color::WHITE);
-    let mut highlighter = HighlightLines::new(syntax, theme);
-    let mut output = Vec::new();
-    let mut cur_style = String::new();
-    let mut accu_string = String::new();
-
-    for line in LinesWithEndings::from(&props.text) {
-        let regions = highlighter.highlight_line(line, syntax_set).unwrap_at();
-        for (s, text) in regions {
-            let mut style = String::new();
-            if s.foreground != fg {
-                style.push_str("color:");
-                write_css_color(&mut style, s.foreground);
-                style.push(';');
-            }
-            if s.background != bg {
-                style.push_str("background-color:");
-                write_css_color(&mut style, s.background);
-                style.push(';');
-            }
-            if s.font_style.contains(FontStyle::BOLD) {
-                style.push_str("font-weight:bold;");
-            }
-            if s.font_style.contains(FontStyle::UNDERLINE) {
-                style.push_str("text-decoration:underline;");
-            }
-            if s.font_style.contains(FontStyle::ITALIC) {
-                style.push_str("font-style:italic;");
-            }
-
-            if style == cur_style {
-                accu_string.push_str(text);
-            } else {
-                if !accu_string.is_empty() {
-                    if cur_style.is_empty() {
-                        output.push(html!({ accu_string.to_owned() }));
-                    } else {
-                        output.push(html! {
-                            <span style={cur_style.to_owned()}> {accu_string.to_owned()} </span>
-                        });
+    let cache = props.cache.borrow();
+    let visible_rows = use_visible_rows(props.node_ref.clone(), props.virtualized);
+    let mut decorations = Decorations {
+        hidden: props.hidden.iter().peekable(),
+        markers: props.markers.iter().peekable(),
+        tokens: props.tokens.iter().peekable(),
+        show_whitespace: props.show_whitespace,
+        ontoggle: props.ontoggle.as_ref(),
+    };
+
+    let lines = cache.lines();
+    let mut offsets = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for line in lines {
+        offsets.push(offset);
+        offset += line.text.len();
+    }
+
+    // A line only starts a new row if it is not joined to the previous line by a fold.
+    let rendered = match props.virtualized {
+        true => {
+            let mut hidden = props.hidden.iter().peekable();
+            let rows = offsets
+                .iter()
+                .enumerate()
+                .filter(|&(_, &offset)| {
+                    while hidden.next_if(|range| range.end <= offset).is_some() {}
+                    hidden.peek().is_none_or(|range| range.start >= offset)
+                })
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            let first = visible_rows.start.min(rows.len());
+            let last = visible_rows.end.clamp(first, rows.len());
+            let start = rows.get(first).copied().unwrap_or(lines.len());
+            let end = rows.get(last).copied().unwrap_or(lines.len());
+            start..end
+        }
+        false => 0..lines.len(),
+    };
+    let text_len = props.text.len();
+    let offset_of = |idx: usize| offsets.get(idx).copied().unwrap_or(text_len);
+    let before = plain_text(&props.text, 0..offset_of(rendered.start), &props.hidden);
+    let after = plain_text(
+        &props.text,
+        offset_of(rendered.end)..text_len,
+        &props.hidden,
+    );
+
+    let lines = lines[rendered.clone()]
+        .iter()
+        .zip(rendered)
+        .map(|(line, idx)| {
+            let offset = offsets[idx];
+            decorations.skip_to(offset);
+            let mut output = Output::default();
+            for classes in &line.open {
+                output.open(classes.clone());
+            }
+            let mut pos = 0;
+            for (next, op) in &line.ops {
+                decorations.push_text(&mut output, &line.text[pos..*next], offset + pos);
+                pos = *next;
+                match op {
+                    ScopeOp::Push(classes) => output.open(classes.clone()),
+                    ScopeOp::Pop => output.close(),
+                }
+            }
+            decorations.push_text(&mut output, &line.text[pos..], offset + pos);
+            html! {
+                <span key={line.id} class="line">{output.finish()}</span>
+            }
+        })
+        .collect::<Html>();
+
+    html! {
+        <pre class={CODE_CLASS} ref={props.node_ref.clone()}>
+            {"\u{feff}"} {before} {lines} {after} {"\u{feff}"}
+        </pre>
+    }
+}
+
+/// Takes the place of the lines in `range` that are not highlighted. They are still text, so
+/// that the browser can find them.
+fn plain_text(text: &str, range: Range<usize>, hidden: &[Range<usize>]) -> Html {
+    if range.is_empty() {
+        return html!();
+    }
+    let mut plain = String::with_capacity(range.len());
+    let mut pos = range.start;
+    for hidden in hidden
+        .iter()
+        .filter(|hidden| hidden.start >= range.start && hidden.end <= range.end)
+    {
+        plain.push_str(&text[pos..hidden.start]);
+        plain.push_str(PLACEHOLDER);
+        pos = hidden.end;
+    }
+    plain.push_str(&text[pos..range.end]);
+    html! { <span class="plain">{plain}</span> }
+}
+
+/// The rows of `node` that are in or near the viewport, if `enabled`.
+#[hook]
+fn use_visible_rows(node: NodeRef, enabled: bool) -> Range<usize> {
+    let rows = use_state_eq(|| 0..3 * VIRTUAL_MARGIN);
+    use_effect_with(enabled, {
+        let rows = rows.clone();
+        move |&enabled| {
+            let window = window().unwrap_at();
+            let listener = enabled.then(|| {
+                let update = {
+                    let window = window.clone();
+                    move || {
+                        let Some(element) = node.cast::<Element>() else {
+                            return;
+                        };
+                        let line_height = window
+                            .get_computed_style(&element)
+                            .ok()
+                            .flatten()
+                            .and_then(|style| style.get_property_value("line-height").ok())
+                            .and_then(|value| value.strip_suffix("px")?.parse::<f64>().ok())
+                            .filter(|&height| height > 0.0)
+                            .unwrap_or(20.0);
+                        let top = element.get_bounding_client_rect().top();
+                        let height = window
+                            .inner_height()
+                            .ok()
+                            .and_then(|height| height.as_f64())
+                            .unwrap_or_default();
+                        let first = (-top / line_height).max(0.0) as usize;
+                        let last = ((height - top) / line_height).max(0.0).ceil() as usize;
+                        // only rerender after scrolling for a while
+                        let snap = |row: usize| row / VIRTUAL_MARGIN * VIRTUAL_MARGIN;
+                        rows.set(
+                            snap(first).saturating_sub(VIRTUAL_MARGIN)
+                                ..snap(last) + 2 * VIRTUAL_MARGIN,
+                        );
                     }
-                    accu_string.clear();
+                };
+                update();
+                Closure::<dyn Fn()>::new(update)
+            });
+            if let Some(listener) = &listener {
+                // Capture the scrolling of any element that contains the editor, too.
+                let listener = listener.as_ref().unchecked_ref();
+                let _ = window.add_event_listener_with_callback_and_bool("scroll", listener, true);
+                let _ = window.add_event_listener_with_callback("resize", listener);
+            }
+            move || {
+                if let Some(listener) = listener {
+                    let listener = listener.as_ref().unchecked_ref();
+                    let _ = window
+                        .remove_event_listener_with_callback_and_bool("scroll", listener, true);
+                    let _ = window.remove_event_listener_with_callback("resize", listener);
                 }
-                accu_string.push_str(text);
-                cur_style = style;
             }
         }
+    });
+    Range::clone(&rows)
+}
+
+/// Nested `<span>`s for the scopes of a line.
+#[derive(Default)]
+struct Output {
+    html: Vec<Html>,
+    open: Vec<(AttrValue, Vec<Html>)>,
+    text: String,
+}
+
+impl Output {
+    fn open(&mut self, classes: AttrValue) {
+        self.flush();
+        self.open.push((classes, Vec::new()));
     }
-    if !accu_string.is_empty() {
-        if cur_style.is_empty() {
-            output.push(html!({ accu_string }));
-        } else {
-            output.push(html!(<span style={cur_style}>{accu_string}</span>));
+
+    fn close(&mut self) {
+        self.flush();
+        if let Some((classes, children)) = self.open.pop() {
+            if !children.is_empty() {
+                self.children().push(html! {
+                    <span class={classes}>{children}</span>
+                });
+            }
         }
     }
 
-    let style = format!(
-        "color:#{:02x}{:02x}{:02x};background-color:#{:02x}{:02x}{:02x};",
-        fg.r, fg.g, fg.b, bg.r, bg.g, bg.b,
-    );
+    fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn push_html(&mut self, html: Html) {
+        self.flush();
+        self.children().push(html);
+    }
+
+    fn finish(mut self) -> Vec<Html> {
+        while !self.open.is_empty() {
+            self.close();
+        }
+        self.flush();
+        self.html
+    }
+
+    fn children(&mut self) -> &mut Vec<Html> {
+        match self.open.last_mut() {
+            Some((_, children)) => children,
+            None => &mut self.html,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.text.is_empty() {
+            return;
+        }
+        let text = std::mem::take(&mut self.text);
+        self.children().push(html!({ text }));
+    }
+}
 
+/// What is rendered on top of the highlighted scopes: folds, the classes of identifiers and
+/// visible whitespace.
+struct Decorations<'a> {
+    hidden: Peekable<slice::Iter<'a, Range<usize>>>,
+    markers: Peekable<slice::Iter<'a, FoldMarker>>,
+    tokens: Peekable<slice::Iter<'a, Token>>,
+    show_whitespace: bool,
+    ontoggle: Option<&'a Callback<FoldKey>>,
+}
+
+impl Decorations<'_> {
+    /// Drops the markers, hidden ranges and tokens before `pos`, which were not rendered.
+    fn skip_to(&mut self, pos: usize) {
+        while self.markers.next_if(|marker| marker.pos < pos).is_some() {}
+        while self.hidden.next_if(|range| range.end <= pos).is_some() {}
+        while self
+            .tokens
+            .next_if(|token| token.range.end <= pos)
+            .is_some()
+        {}
+    }
+
+    /// Pushes `text`, which starts at `start` in the full text, leaving out hidden ranges and
+    /// wrapping tokens in a `<span>` of their class.
+    fn push_text(&mut self, output: &mut Output, text: &str, start: usize) {
+        let end = start + text.len();
+        let mut pos = start;
+        while pos < end {
+            if let Some(marker) = self.markers.next_if(|marker| marker.pos <= pos) {
+                output.push_html(fold_marker(marker, self.ontoggle));
+                continue;
+            }
+            if let Some(range) = self
+                .hidden
+                .next_if(|range| range.start <= pos && range.end <= end)
+            {
+                if range.start == pos {
+                    output.push_html(fold_placeholder());
+                }
+                pos = range.end;
+                continue;
+            }
+            if let Some(range) = self.hidden.peek().filter(|range| range.start <= pos) {
+                if range.start == pos {
+                    output.push_html(fold_placeholder());
+                }
+                pos = end;
+                continue;
+            }
+
+            while self
+                .tokens
+                .next_if(|token| token.range.end <= pos)
+                .is_some()
+            {}
+            let token = self.tokens.peek().copied();
+            let token = token.filter(|token| token.range.start <= pos);
+            let next = [
+                Some(end),
+                self.hidden.peek().map(|range| range.start),
+                self.markers.peek().map(|marker| marker.pos),
+                match token {
+                    Some(token) => Some(token.range.end),
+                    None => self.tokens.peek().map(|token| token.range.start),
+                },
+            ]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(end);
+            let text = &text[pos - start..next - start];
+            match (token, self.show_whitespace) {
+                (Some(token), show_whitespace) => {
+                    let text = match show_whitespace {
+                        true => whitespace(text),
+                        false => html!({ text.to_owned() }),
+                    };
+                    output.push_html(html! {
+                        <span class={token.kind.class()}>{text}</span>
+                    });
+                }
+                (None, true) => output.push_html(whitespace(text)),
+                (None, false) => output.push(text),
+            }
+            pos = next;
+        }
+    }
+}
+
+fn fold_marker(marker: &FoldMarker, ontoggle: Option<&Callback<FoldKey>>) -> Html {
+    let onclick = ontoggle.map(|ontoggle| {
+        let key = marker.key.clone();
+        ontoggle.reform(move |_: MouseEvent| key.clone())
+    });
+    let (title, icon) = match marker.folded {
+        true => ("Unfold", "▸"),
+        false => ("Fold", "▾"),
+    };
     html! {
-        <pre {style}>
-            {"\u{feff}"} {output} {"\u{feff}"}
-        </pre>
+        <span class={classes!("fold-marker", marker.folded.then_some("folded"))} {title} {onclick}>
+            {icon}
+        </span>
+    }
+}
+
+/// Wraps every space and tab of `text` in a `<span>` that shows a mark on top of it.
+fn whitespace(text: &str) -> Html {
+    let mut html = Vec::new();
+    let mut rest = text;
+    while let Some(idx) = rest.find([' ', '\t']) {
+        if idx > 0 {
+            html.push(html!({ rest[..idx].to_owned() }));
+        }
+        let (class, ws) = match rest.as_bytes()[idx] {
+            b'\t' => ("ws-tab", "\t"),
+            _ => ("ws-space", " "),
+        };
+        html.push(html!(<span class={class}>{ws}</span>));
+        rest = &rest[idx + 1..];
     }
+    if !rest.is_empty() {
+        html.push(html!({ rest.to_owned() }));
+    }
+    html.into_iter().collect()
 }
 
-fn write_css_color(s: &mut String, c: Color) {
-    if c.a != 0xFF {
-        write!(s, "#{:02x}{:02x}{:02x}{:02x}", c.r, c.g, c.b, c.a).unwrap_at();
-    } else {
-        write!(s, "#{:02x}{:02x}{:02x}", c.r, c.g, c.b).unwrap_at();
+fn fold_placeholder() -> Html {
+    html! {
+        <span class="fold-placeholder">{PLACEHOLDER}</span>
     }
 }
+
+#[wasm_bindgen]
+extern "C" {
+    fn replace_text(
+        element: &HtmlTextAreaElement,
+        start: u32,
+        end: u32,
+        text: &str,
+        selStart: u32,
+        selEnd: u32,
+    );
+}
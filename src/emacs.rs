@@ -0,0 +1,404 @@
+use std::ops::Range;
+
+use crate::editing::{line_end, line_start};
+use crate::keymap::{
+    Action, Key, Registers, at_column, char_at, column, diff, next_char, prev_char,
+};
+
+/// Emacs' movement and editing keys, with a mark and a kill ring.
+///
+/// Browsers keep a few of the keys to themselves, e.g. Chrome never passes on C-n and C-w.
+#[derive(Debug, Default)]
+pub struct Emacs {
+    /// The other end of the region, if it is active
+    mark: Option<usize>,
+    point: usize,
+    /// C-x was pressed.
+    prefix: bool,
+    last: Last,
+}
+
+/// The previous command, which decides what kills and M-y do.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum Last {
+    #[default]
+    Other,
+    Kill,
+    Yank {
+        range: Range<usize>,
+        index: usize,
+    },
+}
+
+impl Emacs {
+    pub fn handle(
+        &mut self,
+        key: &Key,
+        text: &str,
+        sel: Range<usize>,
+        registers: &mut Registers,
+    ) -> Action {
+        self.sync(text, &sel);
+        let last = std::mem::take(&mut self.last);
+        let point = self.point;
+        let mut buf = text.to_owned();
+
+        if std::mem::take(&mut self.prefix) {
+            if key.ctrl("x") {
+                if let Some(mark) = self.mark {
+                    self.mark = Some(point);
+                    self.point = mark;
+                }
+            } else if key.is("h") {
+                self.mark = Some(0);
+                self.point = text.len();
+            } else if key.is("u") {
+                return Action::Undo(true);
+            }
+            // Any other key only cancels the prefix.
+            return diff(text, &sel, &buf, self.selection());
+        }
+
+        if key.ctrl("x") {
+            self.prefix = true;
+            return Action::Consumed;
+        } else if key.ctrl(" ") || key.ctrl("@") {
+            self.mark = Some(point);
+        } else if key.ctrl("g") {
+            self.mark = None;
+        } else if key.ctrl("f") {
+            self.point = next_char(text, point);
+        } else if key.ctrl("b") {
+            self.point = prev_char(text, point);
+        } else if key.ctrl("n") || key.ctrl("p") {
+            self.point = next_line(text, point, key.ctrl("n"));
+        } else if key.ctrl("a") {
+            self.point = line_start(text, point);
+        } else if key.ctrl("e") {
+            self.point = line_end(text, point);
+        } else if key.alt("f") {
+            self.point = forward_word(text, point);
+        } else if key.alt("b") {
+            self.point = backward_word(text, point);
+        } else if key.alt("<") {
+            self.point = 0;
+        } else if key.alt(">") {
+            self.point = text.len();
+        } else if key.ctrl("d") {
+            buf.replace_range(point..next_char(text, point), "");
+            self.mark = None;
+        } else if key.alt("d") {
+            let end = forward_word(text, point);
+            self.kill(&mut buf, point..end, false, &last, registers);
+        } else if key.alt("Backspace") {
+            let start = backward_word(text, point);
+            self.kill(&mut buf, start..point, true, &last, registers);
+        } else if key.ctrl("k") {
+            // Kills the line break, too, if nothing but blanks follow.
+            let end = line_end(text, point);
+            let end = match text[point..end].trim().is_empty() {
+                true => next_char(text, end),
+                false => end,
+            };
+            self.kill(&mut buf, point..end, false, &last, registers);
+        } else if key.ctrl("w") {
+            if let Some(mark) = self.mark {
+                let range = mark.min(point)..mark.max(point);
+                self.kill(&mut buf, range, point < mark, &last, registers);
+            }
+        } else if key.alt("w") {
+            if let Some(mark) = self.mark.take() {
+                registers.kill(
+                    text[mark.min(point)..mark.max(point)].to_owned(),
+                    false,
+                    false,
+                );
+            }
+        } else if key.ctrl("y") {
+            if let Some(kill) = registers.kill_ring.last() {
+                buf.insert_str(point, kill);
+                self.point = point + kill.len();
+                self.mark = None;
+                self.last = Last::Yank {
+                    range: point..self.point,
+                    index: registers.kill_ring.len() - 1,
+                };
+            }
+        } else if key.alt("y") {
+            // Replaces the text that was just yanked with the previous kill.
+            let ring = &registers.kill_ring;
+            if let Last::Yank { range, index } = last {
+                if range.end == point && range.end <= text.len() && !ring.is_empty() {
+                    let index = (index + ring.len() - 1) % ring.len();
+                    buf.replace_range(range.clone(), &ring[index]);
+                    self.point = range.start + ring[index].len();
+                    self.last = Last::Yank {
+                        range: range.start..self.point,
+                        index,
+                    };
+                }
+            }
+        } else if key.ctrl("o") {
+            buf.insert(point, '\n');
+        } else if key.ctrl("t") {
+            if let Some((range, swapped)) = transpose(text, point) {
+                buf.replace_range(range.clone(), &swapped);
+                self.point = range.end;
+            }
+        } else if key.alt("u") || key.alt("l") || key.alt("c") {
+            let end = forward_word(text, point);
+            let word = &text[point..end];
+            let word = match key.name.as_str() {
+                "u" => word.to_uppercase(),
+                "l" => word.to_lowercase(),
+                _ => capitalize(word),
+            };
+            buf.replace_range(point..end, &word);
+            self.point = point + word.len();
+        } else if key.ctrl("/") || key.ctrl("_") {
+            return Action::Undo(true);
+        } else {
+            return Action::Pass;
+        }
+        diff(text, &sel, &buf, self.selection())
+    }
+
+    pub fn status(&self) -> Option<String> {
+        if self.prefix {
+            Some("C-x-".to_owned())
+        } else {
+            self.mark.map(|_| "Mark set".to_owned())
+        }
+    }
+
+    /// Follows changes of the selection made with the mouse or by typing.
+    fn sync(&mut self, text: &str, sel: &Range<usize>) {
+        if let Some(mark) = self.mark {
+            if mark <= text.len() && self.point <= text.len() && region(mark, self.point) == *sel {
+                return;
+            }
+        }
+        match sel.is_empty() {
+            true => self.mark = None,
+            false => self.mark = Some(sel.start),
+        }
+        self.point = sel.end;
+    }
+
+    fn selection(&self) -> Range<usize> {
+        match self.mark {
+            Some(mark) => region(mark, self.point),
+            None => self.point..self.point,
+        }
+    }
+
+    /// Removes `range` and adds it to the kill ring, or to the last kill if it was just killed.
+    fn kill(
+        &mut self,
+        buf: &mut String,
+        range: Range<usize>,
+        backward: bool,
+        last: &Last,
+        registers: &mut Registers,
+    ) {
+        registers.kill(buf[range.clone()].to_owned(), *last == Last::Kill, backward);
+        buf.replace_range(range.clone(), "");
+        self.point = range.start;
+        self.mark = None;
+        self.last = Last::Kill;
+    }
+}
+
+fn region(mark: usize, point: usize) -> Range<usize> {
+    mark.min(point)..mark.max(point)
+}
+
+fn next_line(text: &str, point: usize, down: bool) -> usize {
+    let start = line_start(text, point);
+    let line = match down {
+        true => match line_end(text, point) {
+            end if end == text.len() => return end,
+            end => end + 1,
+        },
+        false if start == 0 => return 0,
+        false => line_start(text, start - 1),
+    };
+    at_column(text, line, column(text, point))
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric()
+}
+
+fn forward_word(text: &str, point: usize) -> usize {
+    let mut pos = point;
+    while let Some(c) = char_at(text, pos).filter(|&c| !is_word(c)) {
+        pos += c.len_utf8();
+    }
+    while let Some(c) = char_at(text, pos).filter(|&c| is_word(c)) {
+        pos += c.len_utf8();
+    }
+    pos
+}
+
+fn backward_word(text: &str, point: usize) -> usize {
+    let mut pos = point;
+    while let Some(c) = text[..pos].chars().next_back().filter(|&c| !is_word(c)) {
+        pos -= c.len_utf8();
+    }
+    while let Some(c) = text[..pos].chars().next_back().filter(|&c| is_word(c)) {
+        pos -= c.len_utf8();
+    }
+    pos
+}
+
+/// Swaps the characters around the point, or the two before it at the end of a line.
+fn transpose(text: &str, point: usize) -> Option<(Range<usize>, String)> {
+    let second = match point == line_end(text, point) {
+        true => prev_char(text, point),
+        false => point,
+    };
+    let first = prev_char(text, second);
+    if first == second || second == line_start(text, second) {
+        return None;
+    }
+    let end = next_char(text, second);
+    let swapped = [&text[second..end], &text[first..second]].concat();
+    Some((first..end, swapped))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut capitalized = String::with_capacity(word.len());
+    let mut first = true;
+    for c in word.chars() {
+        match first && is_word(c) {
+            true => {
+                capitalized.extend(c.to_uppercase());
+                first = false;
+            }
+            false => capitalized.extend(c.to_lowercase()),
+        }
+    }
+    capitalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Editor {
+        emacs: Emacs,
+        text: String,
+        sel: Range<usize>,
+    }
+
+    impl Editor {
+        fn new(text: &str, point: usize) -> Self {
+            Self {
+                emacs: Emacs::default(),
+                text: text.to_owned(),
+                sel: point..point,
+            }
+        }
+
+        /// `keys` like "C-k M-y"
+        fn keys(&mut self, keys: &str, registers: &mut Registers) -> &mut Self {
+            for key in keys.split(' ') {
+                let (ctrl, alt, name) = match key.split_at(2) {
+                    ("C-", name) => (true, false, name),
+                    ("M-", name) => (false, true, name),
+                    _ => panic!("{key:?} needs a modifier"),
+                };
+                let key = Key {
+                    name: name.to_owned(),
+                    ctrl,
+                    alt,
+                };
+                match self
+                    .emacs
+                    .handle(&key, &self.text, self.sel.clone(), registers)
+                {
+                    Action::Edit(edit) => {
+                        self.text.replace_range(edit.range, &edit.text);
+                        self.sel = edit.selection;
+                    }
+                    Action::Consumed => {}
+                    action => panic!("{key:?} led to {action:?}"),
+                }
+            }
+            self
+        }
+
+        fn state(&self) -> (&str, Range<usize>) {
+            (&self.text, self.sel.clone())
+        }
+    }
+
+    #[test]
+    fn kill_and_yank_lines() {
+        let mut registers = Registers::default();
+        let mut editor = Editor::new("one\ntwo\nthree", 0);
+        assert_eq!(
+            editor.keys("C-k", &mut registers).state(),
+            ("\ntwo\nthree", 0..0),
+        );
+        // Consecutive kills are collected into one, with the line breaks of empty lines.
+        editor.keys("C-k C-k", &mut registers);
+        assert_eq!(editor.state(), ("\nthree", 0..0));
+        assert_eq!(registers.kill_ring, ["one\ntwo"]);
+
+        assert_eq!(
+            editor.keys("M-> C-y", &mut registers).state(),
+            ("\nthreeone\ntwo", 13..13),
+        );
+        // The kill ring is shared by the editors.
+        let mut other = Editor::new("", 0);
+        assert_eq!(
+            other.keys("C-y", &mut registers).state(),
+            ("one\ntwo", 7..7)
+        );
+    }
+
+    #[test]
+    fn kill_ring() {
+        let mut registers = Registers::default();
+        let mut editor = Editor::new("a b c", 0);
+        // Moving in between starts a new kill.
+        editor.keys("M-d C-f M-d", &mut registers);
+        assert_eq!(editor.state(), ("  c", 1..1));
+        assert_eq!(registers.kill_ring, ["a", "b"]);
+
+        editor.keys("C-a C-y", &mut registers);
+        assert_eq!(editor.state(), ("b  c", 1..1));
+        // M-y replaces the yanked text with the kills before it, in a circle.
+        assert_eq!(editor.keys("M-y", &mut registers).state(), ("a  c", 1..1));
+        assert_eq!(editor.keys("M-y", &mut registers).state(), ("b  c", 1..1));
+        // Only right after a yank
+        assert_eq!(
+            editor.keys("C-f M-y", &mut registers).state(),
+            ("b  c", 2..2),
+        );
+
+        // Backward kills are prepended.
+        let mut editor = Editor::new("x yy zz", 7);
+        editor.keys("M-Backspace M-Backspace", &mut registers);
+        assert_eq!(editor.state(), ("x ", 2..2));
+        assert_eq!(registers.kill_ring.last().unwrap(), "yy zz");
+    }
+
+    #[test]
+    fn region() {
+        let mut registers = Registers::default();
+        let mut editor = Editor::new("abcdef", 1);
+        editor.keys("C-@ C-f C-f", &mut registers);
+        assert_eq!(editor.state(), ("abcdef", 1..3));
+        assert_eq!(editor.emacs.status().as_deref(), Some("Mark set"));
+        assert_eq!(editor.keys("C-w", &mut registers).state(), ("adef", 1..1));
+        assert_eq!(registers.kill_ring, ["bc"]);
+
+        editor.keys("C-x", &mut registers);
+        assert_eq!(editor.emacs.status().as_deref(), Some("C-x-"));
+        assert_eq!(editor.keys("C-g", &mut registers).state(), ("adef", 1..1));
+        assert_eq!(editor.emacs.status(), None);
+    }
+}
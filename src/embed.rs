@@ -0,0 +1,195 @@
+//! The playground inside an `<iframe>`: only the editors, and messages to and from the host page.
+//!
+//! `?embed=1` hides the header, `?embed=readonly` also makes the editors read-only. Messages are
+//! only exchanged with the origin given by `&origin=https://…`, or else with the origin of the
+//! referring page. Without either, the playground neither sends nor answers any messages.
+//!
+//! The host page sends [`HostMessage`]s with `iframe.contentWindow.postMessage(…)`, and receives
+//! [`EmbedMessage`]s as `message` events of its own window.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use web_sys::js_sys::JSON;
+use web_sys::wasm_bindgen::JsValue;
+use web_sys::{MessageEvent, Url, window};
+
+use crate::share::Workspace;
+
+/// How the playground is embedded, if it is.
+pub static EMBED: Lazy<Option<Embed>> = Lazy::new(|| {
+    let url = Url::new(&window()?.location().href().ok()?).ok()?;
+    let params = url.search_params();
+    let origin = params
+        .get("origin")
+        .or_else(|| window()?.document()?.referrer().into());
+    Embed::new(&params.get("embed")?, origin.as_deref().and_then(to_origin))
+});
+
+/// The origin of `url`, or `None` if it has none, like `data:` URLs.
+fn to_origin(url: &str) -> Option<String> {
+    let origin = Url::new(url).ok()?.origin();
+    (origin != "null").then_some(origin)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Embed {
+    pub read_only: bool,
+    /// The origin of the host page; no messages are exchanged if it is unknown.
+    pub origin: Option<String>,
+}
+
+impl Embed {
+    fn new(embed: &str, origin: Option<String>) -> Option<Self> {
+        let read_only = match embed {
+            "" | "0" | "false" | "no" => return None,
+            "readonly" | "read-only" => true,
+            _ => false,
+        };
+        Some(Self { read_only, origin })
+    }
+
+    /// Only messages of the page around the `<iframe>` are answered.
+    pub fn accepts(&self, ev: &MessageEvent) -> bool {
+        let Some(parent) = window().and_then(|window| window.parent().ok().flatten()) else {
+            return false;
+        };
+        let from_parent = ev
+            .source()
+            .is_some_and(|source| JsValue::from(source) == JsValue::from(parent));
+        let origin = self.origin.as_deref() == Some(ev.origin().as_str());
+        from_parent && origin
+    }
+
+    /// The text of the editors is not sent to pages of an unknown origin.
+    pub fn post(&self, message: &EmbedMessage<'_>) {
+        let Some(origin) = &self.origin else {
+            return;
+        };
+        let Some(parent) = window().and_then(|window| window.parent().ok().flatten()) else {
+            return;
+        };
+        let Ok(message) = serde_json::to_string(message) else {
+            return;
+        };
+        if let Ok(message) = JSON::parse(&message) {
+            let _ = parent.post_message(&message, origin);
+        }
+    }
+}
+
+/// What the host page can ask for.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum HostMessage {
+    /// Replaces the parts of the state that are given, like a shared link does, e.g.
+    /// `{type: "open", rust: "…", tmpl: "…"}`.
+    Open(Workspace),
+    /// Opens the `saved` parameter of a shared link.
+    OpenLink { saved: String },
+    /// Asks for a [`EmbedMessage::Compiled`] message with the current state.
+    Get,
+}
+
+impl HostMessage {
+    pub fn parse(data: &JsValue) -> Result<Self, String> {
+        let data = JSON::stringify(data)
+            .ok()
+            .and_then(|data| data.as_string())
+            .ok_or("The message is not a JSON value.")?;
+        serde_json::from_str(&data).map_err(|err| format!("Invalid message: {err}"))
+    }
+}
+
+/// What the playground tells the host page.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum EmbedMessage<'a> {
+    /// The playground listens to messages now.
+    Ready,
+    /// Sent after every compilation, and as the answer to [`HostMessage::Get`].
+    Compiled {
+        rust: &'a str,
+        tmpl: &'a str,
+        config: &'a str,
+        code: &'a str,
+        diagnostics: &'a [Rc<str>],
+        /// How long it took to generate the code, in milliseconds
+        duration: Option<f64>,
+    },
+    /// Something the reader should know about a link, e.g. that it was made with another rinja.
+    Note { message: String },
+    /// A message of the host page could not be handled.
+    Error { message: String },
+}
+
+pub fn duration_ms(duration: Option<Duration>) -> Option<f64> {
+    Some(duration?.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes() {
+        assert_eq!(Embed::new("0", None), None);
+        assert_eq!(
+            Embed::new("1", None),
+            Some(Embed {
+                read_only: false,
+                origin: None,
+            }),
+        );
+        assert_eq!(
+            Embed::new("readonly", Some("https://docs.example.com".to_owned())),
+            Some(Embed {
+                read_only: true,
+                origin: Some("https://docs.example.com".to_owned()),
+            }),
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let message: HostMessage =
+            serde_json::from_str(r#"{"type": "open", "rust": "struct X;", "tmpl": "x"}"#).unwrap();
+        assert_eq!(
+            message,
+            HostMessage::Open(Workspace {
+                rust: Some(Rc::from("struct X;")),
+                tmpl: Some(Rc::from("x")),
+                ..Workspace::default()
+            }),
+        );
+        let message: HostMessage =
+            serde_json::from_str(r#"{"type": "open-link", "saved": "abc"}"#).unwrap();
+        assert_eq!(
+            message,
+            HostMessage::OpenLink {
+                saved: "abc".to_owned()
+            },
+        );
+        assert!(serde_json::from_str::<HostMessage>(r#"{"type": "compile"}"#).is_err());
+
+        let diagnostics = [Rc::from("oops")];
+        let message = EmbedMessage::Compiled {
+            rust: "",
+            tmpl: "",
+            config: "",
+            code: "",
+            diagnostics: &diagnostics,
+            duration: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"type":"compiled","rust":"","tmpl":"","config":"","code":"","diagnostics":["oops"],"duration":null}"#,
+        );
+        assert_eq!(
+            serde_json::to_string(&EmbedMessage::Ready).unwrap(),
+            r#"{"type":"ready"}"#
+        );
+    }
+}
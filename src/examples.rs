@@ -0,0 +1,93 @@
+use crate::app::{STRUCT_SOURCE, TMPL_SOURCE};
+
+pub struct Example {
+    pub name: &'static str,
+    pub rust: &'static str,
+    pub tmpl: &'static str,
+}
+
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        name: "Hello, world",
+        rust: STRUCT_SOURCE,
+        tmpl: TMPL_SOURCE,
+    },
+    Example {
+        name: "Loops",
+        rust: r##"#[derive(Template)]
+#[template(ext = "html")]
+struct Shopping<'a> {
+    items: &'a [(&'a str, u32)],
+}"##,
+        tmpl: r##"<ul>
+    {%- for (name, amount) in items %}
+    <li class="{{ loop.cycle(["odd", "even"]) }}">
+        {{ loop.index }}. {{ name }}: {{ amount }}
+    </li>
+    {%- else %}
+    <li>Nothing to buy.</li>
+    {%- endfor %}
+</ul>"##,
+    },
+    Example {
+        name: "Match",
+        rust: r##"#[derive(Template)]
+#[template(ext = "txt")]
+struct Greeting<'a> {
+    name: Option<&'a str>,
+    mood: Mood,
+}
+
+enum Mood {
+    Happy,
+    Grumpy { reason: String },
+}"##,
+        tmpl: r##"{% match name -%}
+    {% when Some with (name) -%}
+        Hello, {{ name }}!
+    {% when None -%}
+        Hello, stranger!
+{% endmatch -%}
+{% match mood -%}
+    {% when Mood::Happy -%}
+        What a nice day.
+    {% when Mood::Grumpy with { reason } -%}
+        Leave me alone, {{ reason }}.
+{% endmatch %}"##,
+    },
+    Example {
+        name: "Macros",
+        rust: r##"#[derive(Template)]
+#[template(ext = "html")]
+struct Form<'a> {
+    user: &'a str,
+    email: &'a str,
+}"##,
+        tmpl: r##"{% macro input(label, name, value) -%}
+    <label>
+        {{ label }}
+        <input name="{{ name }}" value="{{ value }}" />
+    </label>
+{%- endmacro %}
+
+<form>
+    {% call input("User", "user", user) %}
+    {% call input("E-mail", "email", email) %}
+</form>"##,
+    },
+    Example {
+        name: "Filters",
+        rust: r##"#[derive(Template)]
+#[template(ext = "html")]
+struct Article<'a> {
+    title: &'a str,
+    body: &'a str,
+    tags: Vec<&'a str>,
+}"##,
+        tmpl: r##"<h1>{{ title|upper }}</h1>
+<p>{{ body|truncate(100)|linebreaks|safe }}</p>
+{%- filter lower %}
+    <p>Tags: {{ tags|join(", ") }}</p>
+{%- endfilter %}"##,
+    },
+];
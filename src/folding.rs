@@ -0,0 +1,446 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::editing::Language;
+
+/// Shown instead of the hidden text, in the textarea as well as in the highlighted code.
+pub const PLACEHOLDER: &str = "⋯";
+
+/// Identifies a foldable region by the content of its first line, so folds survive edits
+/// elsewhere in the text and the regeneration of the generated code.
+pub type FoldKey = (Rc<str>, usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub key: FoldKey,
+    /// Start of the first line, which stays visible.
+    pub line_start: usize,
+    /// From the end of the first line to the indentation of the last line.
+    pub hidden: Range<usize>,
+}
+
+pub fn regions(text: &str, language: Language) -> Vec<Region> {
+    let lines = Lines::new(text);
+    let mut spans = match language {
+        Language::Rust => bracket_spans(text),
+        Language::Template => block_spans(text),
+    };
+    spans.sort_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+
+    let mut regions = Vec::<Region>::new();
+    let mut occurrences = Vec::<Rc<str>>::new();
+    for span in spans {
+        let (first, last) = (lines.line_of(span.start), lines.line_of(span.end));
+        if last <= first + 1
+            || regions
+                .last()
+                .is_some_and(|r| r.line_start == lines.starts[first])
+        {
+            continue;
+        }
+
+        let line_start = lines.starts[first];
+        let line_end = lines.end_of(first);
+        let last_start = lines.starts[last];
+        let indent =
+            text[last_start..].len() - text[last_start..].trim_start_matches([' ', '\t']).len();
+
+        let content = Rc::<str>::from(text[line_start..line_end].trim());
+        let occurrence = occurrences.iter().filter(|&c| *c == content).count();
+        occurrences.push(Rc::clone(&content));
+
+        regions.push(Region {
+            key: (content, occurrence),
+            line_start,
+            hidden: line_end..last_start + indent,
+        });
+    }
+    regions
+}
+
+/// The text as shown with some regions folded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoldedView {
+    pub text: Rc<str>,
+    /// Position of the placeholder in `text`, and the range it replaces in the full text.
+    hidden: Vec<(usize, Range<usize>)>,
+}
+
+impl FoldedView {
+    pub fn new(text: &Rc<str>, regions: &[Region], folded: &BTreeSet<FoldKey>) -> Self {
+        let mut hidden = Vec::new();
+        let mut visible = String::new();
+        let mut pos = 0;
+        for region in regions {
+            if region.hidden.start < pos || !folded.contains(&region.key) {
+                continue;
+            }
+            visible.push_str(&text[pos..region.hidden.start]);
+            hidden.push((visible.len(), region.hidden.clone()));
+            visible.push_str(PLACEHOLDER);
+            pos = region.hidden.end;
+        }
+
+        if hidden.is_empty() {
+            return Self {
+                text: Rc::clone(text),
+                hidden,
+            };
+        }
+        visible.push_str(&text[pos..]);
+        Self {
+            text: Rc::from(visible),
+            hidden,
+        }
+    }
+
+    pub fn is_folded(&self) -> bool {
+        !self.hidden.is_empty()
+    }
+
+    pub fn hidden(&self) -> impl Iterator<Item = &Range<usize>> {
+        self.hidden.iter().map(|(_, range)| range)
+    }
+
+    /// A position inside of a placeholder maps to the start or `end` of the hidden text.
+    pub fn to_full(&self, pos: usize, end: bool) -> usize {
+        let mut delta = 0;
+        for (placeholder, range) in &self.hidden {
+            if pos <= *placeholder {
+                break;
+            } else if pos >= placeholder + PLACEHOLDER.len() {
+                delta += range.len();
+                delta -= PLACEHOLDER.len();
+            } else {
+                return if end { range.end } else { range.start };
+            }
+        }
+        pos + delta
+    }
+
+    /// A position inside of the hidden text maps to the start of its placeholder.
+    pub fn to_visible(&self, pos: usize) -> usize {
+        let mut delta = 0;
+        for (placeholder, range) in &self.hidden {
+            if pos <= range.start {
+                break;
+            } else if pos >= range.end {
+                delta += range.len();
+                delta -= PLACEHOLDER.len();
+            } else {
+                return *placeholder;
+            }
+        }
+        pos - delta
+    }
+
+    /// Applies the change between `self.text` and `new_visible` to the `full` text.
+    ///
+    /// Placeholders that were removed or overwritten take their hidden text with them.
+    pub fn apply_edit(&self, full: &str, new_visible: &str) -> String {
+        let old_visible = &*self.text;
+        let prefix = old_visible
+            .char_indices()
+            .zip(new_visible.chars())
+            .find(|&((_, l), r)| l != r)
+            .map_or(old_visible.len().min(new_visible.len()), |((idx, _), _)| {
+                idx
+            });
+        let suffix = old_visible[prefix..]
+            .chars()
+            .rev()
+            .zip(new_visible[prefix..].chars().rev())
+            .take_while(|(l, r)| l == r)
+            .map(|(c, _)| c.len_utf8())
+            .sum::<usize>();
+
+        let start = self.to_full(prefix, false);
+        let end = self.to_full(old_visible.len() - suffix, true);
+        let inserted = &new_visible[prefix..new_visible.len() - suffix];
+        [&full[..start], inserted, &full[end..]].concat()
+    }
+}
+
+struct Lines {
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl Lines {
+    fn new(text: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        Self {
+            starts,
+            len: text.len(),
+        }
+    }
+
+    fn line_of(&self, pos: usize) -> usize {
+        self.starts.partition_point(|&start| start <= pos) - 1
+    }
+
+    fn end_of(&self, line: usize) -> usize {
+        self.starts.get(line + 1).map_or(self.len, |&next| next - 1)
+    }
+}
+
+/// Matching `()`, `[]` and `{}` outside of comments, strings and `char`s.
+fn bracket_spans(text: &str) -> Vec<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut spans = Vec::new();
+    let mut stack = Vec::<(u8, usize)>::new();
+    let mut pos = 0;
+    while let Some(&c) = bytes.get(pos) {
+        match c {
+            b'(' | b'[' | b'{' => stack.push((c, pos)),
+            b')' | b']' | b'}' => {
+                let open = match c {
+                    b')' => b'(',
+                    b']' => b'[',
+                    _ => b'{',
+                };
+                if let Some(idx) = stack.iter().rposition(|&(c, _)| c == open) {
+                    spans.push(stack[idx].1..pos);
+                    stack.truncate(idx);
+                }
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'/') => {
+                pos = text[pos..].find('\n').map_or(text.len(), |idx| pos + idx);
+                continue;
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'*') => {
+                let mut depth = 0_usize;
+                while pos < bytes.len() {
+                    if bytes[pos..].starts_with(b"/*") {
+                        depth += 1;
+                        pos += 2;
+                    } else if bytes[pos..].starts_with(b"*/") {
+                        depth -= 1;
+                        pos += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        pos += 1;
+                    }
+                }
+                continue;
+            }
+            b'r' if matches!(bytes.get(pos + 1), Some(b'"' | b'#'))
+                && !bytes[..pos]
+                    .last()
+                    .is_some_and(|c| c.is_ascii_alphanumeric()) =>
+            {
+                let hashes = bytes[pos + 1..].iter().take_while(|&&c| c == b'#').count();
+                if bytes.get(pos + 1 + hashes) == Some(&b'"') {
+                    let close = format!("\"{}", "#".repeat(hashes));
+                    let body = pos + 2 + hashes;
+                    pos = text[body..]
+                        .find(&close)
+                        .map_or(text.len(), |idx| body + idx + close.len());
+                    continue;
+                }
+            }
+            b'"' => {
+                pos += 1;
+                while let Some(&c) = bytes.get(pos) {
+                    pos += match c {
+                        b'\\' => 2,
+                        b'"' => break,
+                        _ => 1,
+                    };
+                }
+            }
+            b'\'' => {
+                // a `char`, not a lifetime
+                if bytes.get(pos + 1) == Some(&b'\\') {
+                    pos = bytes
+                        .get(pos + 3..)
+                        .and_then(|rest| rest.iter().position(|&c| c == b'\''))
+                        .map_or(bytes.len(), |idx| pos + 3 + idx);
+                } else if let Some(c) = text[pos + 1..].chars().next() {
+                    if text[pos + 1 + c.len_utf8()..].starts_with('\'') {
+                        pos += c.len_utf8() + 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+    spans
+}
+
+/// Block tags like `{% if %}…{% endif %}`, and multi-line comments.
+fn block_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut stack = Vec::<(&str, usize)>::new();
+    let mut pos = 0;
+    while let Some(idx) = text[pos..].find('{') {
+        let start = pos + idx;
+        let rest = &text[start..];
+        let (close, is_comment) = match rest.get(..2) {
+            Some("{#") => ("#}", true),
+            Some("{%") => ("%}", false),
+            _ => {
+                pos = start + 1;
+                continue;
+            }
+        };
+        let Some(end) = rest[2..].find(close).map(|idx| start + 2 + idx) else {
+            break;
+        };
+        pos = end + 2;
+
+        if is_comment {
+            spans.push(start..end);
+            continue;
+        }
+        let keyword = text[start + 2..end]
+            .trim_start_matches(['-', '+', '~'])
+            .split_whitespace()
+            .next()
+            .unwrap_or_default();
+        if matches!(
+            keyword,
+            "block" | "filter" | "for" | "if" | "macro" | "match" | "raw"
+        ) {
+            stack.push((keyword, end));
+        } else if let Some(opener) = keyword.strip_prefix("end") {
+            if let Some(idx) = stack.iter().rposition(|&(k, _)| k == opener) {
+                spans.push(stack[idx].1..start);
+                stack.truncate(idx);
+            }
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(regions: &[Region], keys: &[&str]) -> BTreeSet<FoldKey> {
+        regions
+            .iter()
+            .filter(|region| keys.contains(&&*region.key.0))
+            .map(|region| region.key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn nested_brackets() {
+        let text: Rc<str> = Rc::from("fn a() {\n    if x {\n        y();\n    }\n}\n");
+        let regions = regions(&text, Language::Rust);
+        assert_eq!(
+            regions,
+            [
+                Region {
+                    key: (Rc::from("fn a() {"), 0),
+                    line_start: 0,
+                    hidden: 8..39,
+                },
+                Region {
+                    key: (Rc::from("if x {"), 0),
+                    line_start: 9,
+                    hidden: 19..37,
+                },
+            ],
+        );
+
+        let view = FoldedView::new(&text, &regions, &folded(&regions, &["if x {"]));
+        assert_eq!(&*view.text, "fn a() {\n    if x {⋯}\n}\n");
+        // The outer region hides the inner one.
+        let view = FoldedView::new(&text, &regions, &folded(&regions, &["fn a() {", "if x {"]));
+        assert_eq!(&*view.text, "fn a() {⋯}\n");
+        assert_eq!(view.hidden().collect::<Vec<_>>(), [&(8..39)]);
+    }
+
+    #[test]
+    fn unbalanced_brackets() {
+        // A missing `)` doesn't keep the `}` from closing the `{`, and a stray `)` is ignored.
+        let text = "fn a() {\n    x(\n\n    )) \n}";
+        let regions = regions(text, Language::Rust);
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].key, (Rc::from("fn a() {"), 0));
+        assert_eq!(regions[1].key, (Rc::from("x("), 0));
+
+        let text = "fn a() {\n    x(\n\n}";
+        let keys = super::regions(text, Language::Rust)
+            .into_iter()
+            .map(|region| region.key.0)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [Rc::from("fn a() {")]);
+
+        // Unclosed, or in strings and comments
+        assert_eq!(super::regions("fn a() {\n\n\n", Language::Rust), []);
+        let text = "let s = \"{\n\n\";\n// {\n/* {\n\n*/ let c = '{';\n\n";
+        assert_eq!(super::regions(text, Language::Rust), []);
+    }
+
+    #[test]
+    fn blocks() {
+        let text = "{% if a %}\n{% for x in y %}\n{{ x }}\n{% endfor %}\n{% endif %}\n\
+                    {% if a %}\n{# a\ncomment #}\n{% endif %}";
+        let keys = regions(text, Language::Template)
+            .into_iter()
+            .map(|region| region.key)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                (Rc::from("{% if a %}"), 0),
+                (Rc::from("{% for x in y %}"), 0),
+                (Rc::from("{% if a %}"), 1),
+            ],
+        );
+
+        // The `endif` closes the unclosed `for`, the stray `endfor` is ignored.
+        let text = "{% if a %}\n{% for x in y %}\n\n{% endif %}\n{% endfor %}\n\n";
+        let keys = regions(text, Language::Template)
+            .into_iter()
+            .map(|region| region.key.0)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [Rc::from("{% if a %}")]);
+
+        assert_eq!(regions("{% if a %}\n\n\n{{ x }}", Language::Template), []);
+    }
+
+    #[test]
+    fn offsets() {
+        let text: Rc<str> = Rc::from("f {\n    x\n}\n");
+        let regions = regions(&text, Language::Rust);
+        assert_eq!(regions[0].hidden, 3..10);
+        let view = FoldedView::new(&text, &regions, &folded(&regions, &["f {"]));
+        assert_eq!(&*view.text, "f {⋯}\n");
+        assert!(view.is_folded());
+
+        assert_eq!(view.to_full(0, false), 0);
+        assert_eq!(view.to_full(3, true), 3);
+        // Inside of the placeholder
+        assert_eq!(view.to_full(4, false), 3);
+        assert_eq!(view.to_full(4, true), 10);
+        assert_eq!(view.to_full(6, false), 10);
+        assert_eq!(view.to_full(8, false), 12);
+
+        assert_eq!(view.to_visible(3), 3);
+        assert_eq!(view.to_visible(5), 3);
+        assert_eq!(view.to_visible(10), 6);
+        assert_eq!(view.to_visible(12), 8);
+
+        assert_eq!(view.apply_edit(&text, "f {⋯}\ng"), "f {\n    x\n}\ng");
+        assert_eq!(view.apply_edit(&text, "g {⋯}\n"), "g {\n    x\n}\n");
+        // A removed placeholder takes the hidden text with it.
+        assert_eq!(view.apply_edit(&text, "f {}\n"), "f {}\n");
+        assert_eq!(view.apply_edit(&text, "f {\n"), "f {\n");
+
+        let view = FoldedView::new(&text, &regions, &BTreeSet::new());
+        assert!(!view.is_folded());
+        assert!(Rc::ptr_eq(&view.text, &text));
+        assert_eq!(view.to_full(5, true), 5);
+        assert_eq!(view.to_visible(5), 5);
+    }
+}
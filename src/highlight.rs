@@ -0,0 +1,281 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use syntect::highlighting::{HighlightIterator, HighlightState, Highlighter, Style, Theme};
+use syntect::html::{ClassStyle, css_for_theme_with_class_style};
+use syntect::parsing::{
+    BasicScopeStackOp, ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet,
+};
+use syntect::util::LinesWithEndings;
+use yew::AttrValue;
+
+use crate::themes::is_plain_name;
+use crate::{ASSETS, ThrowAt, custom_syntax_set};
+
+/// Prefixed to the scope atoms, e.g. `keyword.control` gets the classes `s-keyword s-control`.
+const CLASS_PREFIX: &str = "s-";
+
+/// Class of the element that contains the highlighted code.
+pub const CODE_CLASS: &str = "s-code";
+
+type State = (ParseState, ScopeStack);
+
+/// The parsed lines of a text, so that only the changed lines need to be parsed again.
+#[derive(Default)]
+pub struct LineCache {
+    syntax: Option<AttrValue>,
+    lines: Vec<Line>,
+    classes: HashMap<Scope, AttrValue>,
+    next_id: u64,
+    /// Changes whenever the lines change, also if only the syntax did
+    revision: u64,
+}
+
+pub struct Line {
+    /// Unique among the lines, and kept while the line is reused, so it can key the rendered line
+    pub id: u64,
+    pub text: String,
+    /// Classes of the scopes that are open at the start of the line
+    pub open: Vec<AttrValue>,
+    /// Scopes that get opened and closed, at byte offsets into `text`
+    pub ops: Vec<(usize, ScopeOp)>,
+    /// The state at the end of the line
+    state: State,
+}
+
+/// The [`LineCache`] of an editor, compared by identity and by the revision of its lines.
+#[derive(Default, Clone)]
+pub struct SharedLineCache {
+    cache: Rc<RefCell<LineCache>>,
+    revision: u64,
+}
+
+impl SharedLineCache {
+    pub fn borrow(&self) -> Ref<'_, LineCache> {
+        self.cache.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, LineCache> {
+        self.cache.borrow_mut()
+    }
+
+    /// The same cache, unequal to the handles from before the last change of its lines.
+    pub fn current(&self) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            revision: self.cache.borrow().revision,
+        }
+    }
+}
+
+impl PartialEq for SharedLineCache {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.cache, &other.cache) && self.revision == other.revision
+    }
+}
+
+pub enum ScopeOp {
+    Push(AttrValue),
+    Pop,
+}
+
+impl LineCache {
+    pub fn update(&mut self, text: &str, syntax: &AttrValue) -> &[Line] {
+        let custom_syntax_set = custom_syntax_set(syntax);
+        let (syntax_set, syntax_ref) = find_syntax(custom_syntax_set.as_deref(), syntax);
+        let initial = (ParseState::new(syntax_ref), ScopeStack::new());
+
+        if self.syntax.as_ref() != Some(syntax) {
+            self.syntax = Some(syntax.clone());
+            self.lines.clear();
+            self.revision += 1;
+        }
+
+        let new_lines = LinesWithEndings::from(text).collect::<Vec<_>>();
+        let mut old_lines = std::mem::take(&mut self.lines);
+        let prefix = old_lines
+            .iter()
+            .zip(&new_lines)
+            .take_while(|(old, new)| old.text == **new)
+            .count();
+        if prefix == old_lines.len() && prefix == new_lines.len() {
+            self.lines = old_lines;
+            return &self.lines;
+        }
+        self.revision += 1;
+        let suffix = old_lines[prefix..]
+            .iter()
+            .rev()
+            .zip(new_lines[prefix..].iter().rev())
+            .take_while(|(old, new)| old.text == **new)
+            .count();
+
+        // The unchanged lines at the end can be reused once the state is the same as before them.
+        let tail = old_lines.split_off(old_lines.len() - suffix);
+        let mut tail_state = match old_lines.last() {
+            Some(line) => line.state.clone(),
+            None => initial.clone(),
+        };
+        old_lines.truncate(prefix);
+        let mut lines = old_lines;
+
+        let mut state = match lines.last() {
+            Some(line) => line.state.clone(),
+            None => initial,
+        };
+        for &text in &new_lines[prefix..new_lines.len() - suffix] {
+            lines.push(self.parse_line(text, &mut state, syntax_set));
+        }
+        let mut tail = tail.into_iter();
+        while let Some(old) = tail.next() {
+            if state == tail_state {
+                lines.push(old);
+                lines.extend(tail);
+                break;
+            }
+            tail_state = old.state;
+            lines.push(self.parse_line(&old.text, &mut state, syntax_set));
+        }
+
+        self.lines = lines;
+        &self.lines
+    }
+
+    /// The lines of the text of the last [`LineCache::update()`].
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    fn parse_line(&mut self, text: &str, state: &mut State, syntax_set: &SyntaxSet) -> Line {
+        let (parse_state, stack) = state;
+        let open = stack
+            .as_slice()
+            .iter()
+            .map(|&scope| self.classes(scope))
+            .collect();
+
+        let mut ops = Vec::new();
+        for (pos, op) in parse_state.parse_line(text, syntax_set).unwrap_at() {
+            let result = stack.apply_with_hook(&op, |op, _| {
+                ops.push((
+                    pos,
+                    match op {
+                        BasicScopeStackOp::Push(scope) => ScopeOp::Push(self.classes(scope)),
+                        BasicScopeStackOp::Pop => ScopeOp::Pop,
+                    },
+                ));
+            });
+            result.unwrap_at();
+        }
+
+        self.next_id += 1;
+        Line {
+            id: self.next_id,
+            text: text.to_owned(),
+            open,
+            ops,
+            state: state.clone(),
+        }
+    }
+
+    fn classes(&mut self, scope: Scope) -> AttrValue {
+        self.classes
+            .entry(scope)
+            .or_insert_with(|| {
+                let classes = scope
+                    .build_string()
+                    .split('.')
+                    .map(|atom| format!("{CLASS_PREFIX}{atom}"))
+                    .collect::<Vec<_>>();
+                AttrValue::from(classes.join(" "))
+            })
+            .clone()
+    }
+}
+
+/// The syntax `name`, from the syntax set of the template if it is `custom`, or plain text.
+fn find_syntax<'a>(
+    custom: Option<&'a SyntaxSet>,
+    name: &str,
+) -> (&'a SyntaxSet, &'a SyntaxReference) {
+    let syntax_set = custom.unwrap_or(ASSETS.0);
+    let syntax_ref = syntax_set
+        .find_syntax_by_name(name)
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+    (syntax_set, syntax_ref)
+}
+
+/// The lines of `text` split into pieces of the same style, with the colors resolved by `theme`
+/// instead of the stylesheet, e.g. to export the code.
+pub fn styled_lines(text: &str, syntax: &str, theme: &Theme) -> Vec<Vec<(Style, String)>> {
+    let custom_syntax_set = custom_syntax_set(syntax);
+    let (syntax_set, syntax_ref) = find_syntax(custom_syntax_set.as_deref(), syntax);
+    let highlighter = Highlighter::new(theme);
+    let mut parse_state = ParseState::new(syntax_ref);
+    let mut highlight_state = HighlightState::new(&highlighter, ScopeStack::new());
+    LinesWithEndings::from(text)
+        .map(|line| {
+            let ops = parse_state.parse_line(line, syntax_set).unwrap_at();
+            HighlightIterator::new(&mut highlight_state, &ops, line, &highlighter)
+                .map(|(style, text)| (style, text.to_owned()))
+                .collect()
+        })
+        .collect()
+}
+
+/// The stylesheet for the classes of the highlighted code.
+pub fn theme_css(theme: &Theme) -> AttrValue {
+    // syntect escapes the scopes, but writes the name into a comment as it is.
+    let unnamed;
+    let theme = match theme.name.as_deref() {
+        Some(name) if !is_plain_name(name) => {
+            unnamed = Theme {
+                name: None,
+                ..theme.clone()
+            };
+            &unnamed
+        }
+        _ => theme,
+    };
+    AttrValue::from(
+        css_for_theme_with_class_style(
+            theme,
+            ClassStyle::SpacedPrefixed {
+                prefix: CLASS_PREFIX,
+            },
+        )
+        .unwrap_at(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syntax_change() {
+        let text = "fn main() {}\n";
+        let cache = SharedLineCache::default();
+        cache
+            .borrow_mut()
+            .update(text, &AttrValue::from("Plain Text"));
+        let before = cache.current();
+        cache
+            .borrow_mut()
+            .update(text, &AttrValue::from("Plain Text"));
+        assert!(cache.current() == before);
+
+        // Only the syntax changes, which the editor's overlay needs to show.
+        cache.borrow_mut().update(text, &AttrValue::from("Rust"));
+        assert!(cache.current() != before);
+        assert_eq!(cache.borrow().lines().len(), 1);
+
+        let before = cache.current();
+        cache
+            .borrow_mut()
+            .update("fn main() {}\n\n", &AttrValue::from("Rust"));
+        assert!(cache.current() != before);
+        assert!(SharedLineCache::default().current() != cache.current());
+    }
+}
@@ -0,0 +1,252 @@
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+const MAX_ENTRIES: usize = 200;
+const MAX_BYTES: usize = 1 << 20;
+const COALESCE_PAUSE_MS: f64 = 1000.0;
+const COALESCE_MAX_MS: f64 = 5000.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub text: Rc<str>,
+    /// UTF-16 offsets, as used by the textarea
+    pub selection: (u32, u32),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    undo: Vec<Entry>,
+    current: Entry,
+    redo: Vec<Entry>,
+    #[serde(skip)]
+    typing: Option<Typing>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Typing {
+    kind: TypingKind,
+    started: f64,
+    last: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TypingKind {
+    Insert,
+    Delete,
+}
+
+impl History {
+    pub fn new(text: Rc<str>) -> Self {
+        Self {
+            undo: Vec::new(),
+            current: Entry {
+                text,
+                selection: (0, 0),
+            },
+            redo: Vec::new(),
+            typing: None,
+        }
+    }
+
+    /// Record a change that did not come from [`History::undo()`] or [`History::redo()`].
+    ///
+    /// Consecutive single character insertions (or deletions) are merged into one entry.
+    pub fn record(&mut self, text: Rc<str>, selection: (u32, u32), now: f64) {
+        if text == self.current.text {
+            self.current.selection = selection;
+            return;
+        }
+
+        let kind = typing_kind(&self.current.text, &text);
+        let typing = match (kind, self.typing) {
+            (Some(kind), Some(typing))
+                if kind == typing.kind
+                    && now - typing.last < COALESCE_PAUSE_MS
+                    && now - typing.started < COALESCE_MAX_MS =>
+            {
+                Some(Typing {
+                    last: now,
+                    ..typing
+                })
+            }
+            _ => None,
+        };
+
+        let entry = Entry { text, selection };
+        if typing.is_some() {
+            self.current = entry;
+        } else {
+            self.undo.push(std::mem::replace(&mut self.current, entry));
+            self.prune();
+        }
+        self.redo.clear();
+        self.typing = typing.or_else(|| {
+            kind.map(|kind| Typing {
+                kind,
+                started: now,
+                last: now,
+            })
+        });
+    }
+
+    pub fn undo(&mut self) -> Option<&Entry> {
+        let entry = self.undo.pop()?;
+        self.redo.push(std::mem::replace(&mut self.current, entry));
+        self.typing = None;
+        Some(&self.current)
+    }
+
+    pub fn redo(&mut self) -> Option<&Entry> {
+        let entry = self.redo.pop()?;
+        self.undo.push(std::mem::replace(&mut self.current, entry));
+        self.typing = None;
+        Some(&self.current)
+    }
+
+    fn prune(&mut self) {
+        let mut bytes = self
+            .undo
+            .iter()
+            .chain(&self.redo)
+            .map(|entry| entry.text.len())
+            .sum::<usize>();
+        // the most recent entry is always kept
+        let mut count = 0;
+        while count + 1 < self.undo.len()
+            && (self.undo.len() - count > MAX_ENTRIES || bytes > MAX_BYTES)
+        {
+            bytes -= self.undo[count].text.len();
+            count += 1;
+        }
+        self.undo.drain(..count);
+    }
+}
+
+fn typing_kind(old: &str, new: &str) -> Option<TypingKind> {
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(l, r)| l == r)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(l, r)| l == r)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+    let inserted = &new[prefix..new.len() - suffix];
+    let deleted = &old[prefix..old.len() - suffix];
+
+    let mut inserted = inserted.chars();
+    let mut deleted = deleted.chars();
+    match (
+        inserted.next(),
+        inserted.next(),
+        deleted.next(),
+        deleted.next(),
+    ) {
+        (Some(c), None, None, None) if c != '\n' => Some(TypingKind::Insert),
+        (None, None, Some(_), None) => Some(TypingKind::Delete),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(history: &History) -> Vec<&str> {
+        history.undo.iter().map(|entry| &*entry.text).collect()
+    }
+
+    #[test]
+    fn coalescing() {
+        let mut history = History::new(Rc::from(""));
+        history.record(Rc::from("a"), (1, 1), 0.0);
+        history.record(Rc::from("ab"), (2, 2), 100.0);
+        history.record(Rc::from("abc"), (3, 3), 200.0);
+        assert_eq!(texts(&history), [""]);
+
+        // A pause, a deletion or a new line starts a new entry.
+        history.record(Rc::from("abcd"), (4, 4), 2000.0);
+        history.record(Rc::from("abc"), (3, 3), 2100.0);
+        history.record(Rc::from("ab"), (2, 2), 2200.0);
+        history.record(Rc::from("ab\n"), (3, 3), 2300.0);
+        history.record(Rc::from("ab\nx"), (4, 4), 2400.0);
+        assert_eq!(texts(&history), ["", "abc", "abcd", "ab", "ab\n"]);
+
+        // Long typing is split, too.
+        let mut text = String::from("ab\nx");
+        for i in 0..60 {
+            text.push('y');
+            history.record(
+                Rc::from(text.as_str()),
+                (0, 0),
+                2500.0 + 100.0 * f64::from(i),
+            );
+        }
+        assert_eq!(history.undo.len(), 6);
+        assert_eq!(&*history.undo[5].text, format!("ab\nx{}", "y".repeat(49)));
+
+        // Only the selection changed.
+        let before = texts(&history).len();
+        history.record(Rc::from(text.as_str()), (1, 2), 9000.0);
+        assert_eq!(texts(&history).len(), before);
+        assert_eq!(history.current.selection, (1, 2));
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::new(Rc::from("a"));
+        history.record(Rc::from("a\nb"), (3, 3), 0.0);
+        history.record(Rc::from("a\nb\nc"), (5, 5), 10.0);
+        assert_eq!(&*history.undo().unwrap().text, "a\nb");
+        assert_eq!(&*history.undo().unwrap().text, "a");
+        assert_eq!(history.undo(), None);
+        assert_eq!(&*history.redo().unwrap().text, "a\nb");
+
+        // A new edit drops what could be redone.
+        history.record(Rc::from("a\nb!"), (4, 4), 20.0);
+        assert_eq!(history.redo(), None);
+        assert_eq!(&*history.undo().unwrap().text, "a\nb");
+
+        // Typing right after an undo is not merged into the entry that was undone to.
+        history.record(Rc::from("a\nbx"), (4, 4), 30.0);
+        history.record(Rc::from("a\nbxy"), (5, 5), 40.0);
+        assert_eq!(&*history.undo().unwrap().text, "a\nb");
+        assert_eq!(&*history.redo().unwrap().text, "a\nbxy");
+    }
+
+    #[test]
+    fn pruning() {
+        let mut history = History::new(Rc::from("0"));
+        for i in 1..=MAX_ENTRIES + 50 {
+            history.record(Rc::from(format!("{i}\n")), (0, 0), 0.0);
+        }
+        assert_eq!(history.undo.len(), MAX_ENTRIES);
+        assert_eq!(&*history.undo[0].text, "50\n");
+
+        let big = "x".repeat(MAX_BYTES / 3);
+        let mut history = History::new(Rc::from(""));
+        for i in 0..5 {
+            history.record(Rc::from(format!("{big}{i}\n")), (0, 0), 0.0);
+        }
+        let bytes = history
+            .undo
+            .iter()
+            .map(|entry| entry.text.len())
+            .sum::<usize>();
+        assert!(bytes <= MAX_BYTES);
+        assert_eq!(history.undo.len(), 2);
+        assert_eq!(&*history.undo.last().unwrap().text, format!("{big}3\n"));
+
+        // The latest entry is kept, even if it alone is too big.
+        let huge = Rc::<str>::from("x".repeat(MAX_BYTES + 1));
+        let mut history = History::new(Rc::clone(&huge));
+        history.record(Rc::from("\n"), (0, 0), 0.0);
+        assert_eq!(texts(&history), [&*huge]);
+    }
+}
@@ -0,0 +1,438 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use web_sys::KeyboardEvent;
+
+use crate::editing::{Edit, line_end, line_start};
+use crate::emacs::Emacs;
+use crate::vim::Vim;
+
+/// How keys are interpreted in the editors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Keymap {
+    #[default]
+    Default,
+    Vim,
+    Emacs,
+}
+
+impl Keymap {
+    pub const ALL: &[Self] = &[Self::Default, Self::Vim, Self::Emacs];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Vim => "vim",
+            Self::Emacs => "emacs",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Default => "Default",
+            Self::Vim => "Vim",
+            Self::Emacs => "Emacs",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|keymap| keymap.key() == key)
+    }
+}
+
+/// A key press as the keymaps see it. Presses with Cmd are left to the browser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    /// The character that was typed, or the name of a special key like "Escape"
+    pub name: String,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl Key {
+    pub fn from_event(ev: &KeyboardEvent) -> Option<Self> {
+        let name = ev.key();
+        if ev.meta_key()
+            || matches!(
+                name.as_str(),
+                "" | "Alt" | "AltGraph" | "Control" | "Dead" | "Meta" | "Shift" | "Unidentified"
+            )
+        {
+            return None;
+        }
+
+        // With Ctrl or Alt, the layout may report another character, so use the physical key.
+        let code = ev.code();
+        let letter = code.strip_prefix("Key").map(|letter| match ev.shift_key() {
+            true => letter.to_owned(),
+            false => letter.to_ascii_lowercase(),
+        });
+        let (mut ctrl, mut alt) = (ev.ctrl_key(), ev.alt_key());
+        // AltGr is reported as Ctrl+Alt, but it only types a character.
+        if ctrl && alt && name.chars().count() == 1 && letter.as_ref() != Some(&name) {
+            (ctrl, alt) = (false, false);
+        }
+        let name = match letter {
+            Some(letter) if ctrl || alt => letter,
+            _ => name,
+        };
+        Some(Self { name, ctrl, alt })
+    }
+
+    /// The typed character, if no modifier is held.
+    pub fn char(&self) -> Option<char> {
+        if self.ctrl || self.alt {
+            return None;
+        }
+        let mut chars = self.name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    pub fn is(&self, name: &str) -> bool {
+        !self.ctrl && !self.alt && self.name == name
+    }
+
+    pub fn ctrl(&self, name: &str) -> bool {
+        self.ctrl && !self.alt && self.name == name
+    }
+
+    pub fn alt(&self, name: &str) -> bool {
+        !self.ctrl && self.alt && self.name == name
+    }
+}
+
+/// What the editor should do with a key press.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// The key is handled like without a keymap.
+    Pass,
+    /// The key was handled, but the text and the selection stay the same.
+    Consumed,
+    Edit(Edit),
+    /// `true` to undo, `false` to redo
+    Undo(bool),
+}
+
+/// Text that was yanked or deleted in Vim mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    /// The text consists of whole lines, and is pasted as such.
+    pub linewise: bool,
+}
+
+/// Vim's registers and Emacs' kill ring, shared between the editors.
+#[derive(Debug, Default)]
+pub struct Registers {
+    registers: HashMap<char, Register>,
+    /// The newest kill is last.
+    pub kill_ring: Vec<String>,
+}
+
+const KILL_RING_SIZE: usize = 60;
+
+impl Registers {
+    pub fn get(&self, name: Option<char>) -> Option<&Register> {
+        self.registers
+            .get(&name.unwrap_or('"').to_ascii_lowercase())
+    }
+
+    /// Stores yanked or deleted text like Vim: in the named register, or in the unnamed register
+    /// and the numbered or small delete registers.
+    pub fn store(&mut self, name: Option<char>, text: String, linewise: bool, yank: bool) {
+        let mut register = Register { text, linewise };
+        match name.filter(|&name| name != '"') {
+            Some('_') => return,
+            // Uppercase names append to the register.
+            Some(name) if name.is_ascii_uppercase() => {
+                let name = name.to_ascii_lowercase();
+                if let Some(mut old) = self.registers.remove(&name) {
+                    if register.linewise && !old.text.is_empty() && !old.text.ends_with('\n') {
+                        old.text.push('\n');
+                    }
+                    old.text.push_str(&register.text);
+                    old.linewise |= register.linewise;
+                    register = old;
+                }
+                self.registers.insert(name, register.clone());
+            }
+            Some(name) => {
+                self.registers.insert(name, register.clone());
+            }
+            None if yank => {
+                self.registers.insert('0', register.clone());
+            }
+            None if register.linewise || register.text.contains('\n') => {
+                for idx in (b'1'..b'9').rev() {
+                    if let Some(old) = self.registers.remove(&char::from(idx)) {
+                        self.registers.insert(char::from(idx + 1), old);
+                    }
+                }
+                self.registers.insert('1', register.clone());
+            }
+            None => {
+                self.registers.insert('-', register.clone());
+            }
+        }
+        self.registers.insert('"', register);
+    }
+
+    /// Adds a kill to the kill ring, or extends the last one if the previous command killed too.
+    pub fn kill(&mut self, text: String, append: bool, backward: bool) {
+        match self.kill_ring.last_mut() {
+            Some(last) if append && backward => last.insert_str(0, &text),
+            Some(last) if append => last.push_str(&text),
+            _ => {
+                self.kill_ring.push(text);
+                if self.kill_ring.len() > KILL_RING_SIZE {
+                    self.kill_ring.remove(0);
+                }
+            }
+        }
+    }
+}
+
+/// The [`Registers`] of all editors, compared by identity.
+#[derive(Debug, Clone, Default)]
+pub struct SharedRegisters(Rc<RefCell<Registers>>);
+
+impl SharedRegisters {
+    pub fn borrow_mut(&self) -> std::cell::RefMut<'_, Registers> {
+        self.0.borrow_mut()
+    }
+}
+
+impl PartialEq for SharedRegisters {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The mode and pending keys of the editor's keymap.
+#[derive(Debug)]
+pub enum KeymapState {
+    Default,
+    Vim(Vim),
+    Emacs(Emacs),
+}
+
+impl KeymapState {
+    pub fn new(keymap: Keymap) -> Self {
+        match keymap {
+            Keymap::Default => Self::Default,
+            Keymap::Vim => Self::Vim(Vim::default()),
+            Keymap::Emacs => Self::Emacs(Emacs::default()),
+        }
+    }
+
+    pub fn keymap(&self) -> Keymap {
+        match self {
+            Self::Default => Keymap::Default,
+            Self::Vim(_) => Keymap::Vim,
+            Self::Emacs(_) => Keymap::Emacs,
+        }
+    }
+
+    /// `sel` is the textarea's selection, in bytes.
+    pub fn handle(
+        &mut self,
+        key: &Key,
+        text: &str,
+        sel: Range<usize>,
+        registers: &mut Registers,
+        tab_width: usize,
+    ) -> Action {
+        match self {
+            Self::Default => Action::Pass,
+            Self::Vim(vim) => vim.handle(key, text, sel, registers, tab_width),
+            Self::Emacs(emacs) => emacs.handle(key, text, sel, registers),
+        }
+    }
+
+    /// The mode and the keys typed so far, if any.
+    pub fn status(&self) -> Option<String> {
+        match self {
+            Self::Default => None,
+            Self::Vim(vim) => Some(vim.status()),
+            Self::Emacs(emacs) => emacs.status(),
+        }
+    }
+
+    pub fn class(&self) -> Option<&'static str> {
+        match self {
+            Self::Vim(vim) => Some(vim.class()),
+            Self::Default | Self::Emacs(_) => None,
+        }
+    }
+}
+
+/// Turns the new text and selection into an [`Edit`] of the old text.
+pub fn diff(old: &str, old_sel: &Range<usize>, new: &str, new_sel: Range<usize>) -> Action {
+    if old == new {
+        return match *old_sel == new_sel {
+            true => Action::Consumed,
+            false => Action::Edit(Edit {
+                range: new_sel.start..new_sel.start,
+                text: String::new(),
+                selection: new_sel,
+            }),
+        };
+    }
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|&((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((idx, _), _)| idx);
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    Action::Edit(Edit {
+        range: prefix..old.len() - suffix,
+        text: new[prefix..new.len() - suffix].to_owned(),
+        selection: new_sel,
+    })
+}
+
+pub fn next_char(text: &str, pos: usize) -> usize {
+    text[pos..]
+        .chars()
+        .next()
+        .map_or(pos, |c| pos + c.len_utf8())
+}
+
+pub fn prev_char(text: &str, pos: usize) -> usize {
+    text[..pos]
+        .chars()
+        .next_back()
+        .map_or(pos, |c| pos - c.len_utf8())
+}
+
+pub fn char_at(text: &str, pos: usize) -> Option<char> {
+    text.get(pos..)?.chars().next()
+}
+
+/// Whitespace, punctuation and word characters, which make up separate words.
+/// Every non-blank is a word character in a `big` word.
+pub fn char_class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        2
+    } else {
+        1
+    }
+}
+
+/// The column of `pos` in its line, in characters.
+pub fn column(text: &str, pos: usize) -> usize {
+    text[line_start(text, pos)..pos].chars().count()
+}
+
+/// The position in the line starting at `start` that is closest to `column`.
+pub fn at_column(text: &str, start: usize, column: usize) -> usize {
+    let end = line_end(text, start);
+    text[start..end]
+        .char_indices()
+        .nth(column)
+        .map_or(end, |(idx, _)| start + idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(registers: &Registers, name: char) -> Option<&str> {
+        registers.get(Some(name)).map(|register| &*register.text)
+    }
+
+    #[test]
+    fn vim_registers() {
+        let mut registers = Registers::default();
+        registers.store(None, "yanked".to_owned(), false, true);
+        registers.store(None, "word".to_owned(), false, false);
+        registers.store(None, "line 1\n".to_owned(), true, false);
+        registers.store(None, "line 2\n".to_owned(), true, false);
+        assert_eq!(text(&registers, '0'), Some("yanked"));
+        assert_eq!(text(&registers, '-'), Some("word"));
+        assert_eq!(text(&registers, '1'), Some("line 2\n"));
+        assert_eq!(text(&registers, '2'), Some("line 1\n"));
+        assert_eq!(registers.get(None).map(|r| r.linewise), Some(true));
+
+        // Named registers, uppercase names append.
+        registers.store(Some('a'), "x".to_owned(), false, true);
+        registers.store(Some('A'), "y".to_owned(), false, true);
+        assert_eq!(text(&registers, 'a'), Some("xy"));
+        registers.store(Some('A'), "z\n".to_owned(), true, true);
+        assert_eq!(text(&registers, 'a'), Some("xy\nz\n"));
+        assert_eq!(text(&registers, 'A'), Some("xy\nz\n"));
+        assert_eq!(text(&registers, '"'), Some("xy\nz\n"));
+
+        // The black hole register keeps nothing.
+        registers.store(Some('_'), "gone".to_owned(), false, false);
+        assert_eq!(text(&registers, '"'), Some("xy\nz\n"));
+    }
+
+    #[test]
+    fn kill_ring() {
+        let mut registers = Registers::default();
+        registers.kill("b".to_owned(), false, false);
+        registers.kill("c".to_owned(), true, false);
+        registers.kill("a".to_owned(), true, true);
+        assert_eq!(registers.kill_ring, ["abc"]);
+        registers.kill("d".to_owned(), false, false);
+        assert_eq!(registers.kill_ring, ["abc", "d"]);
+
+        for idx in 0..KILL_RING_SIZE {
+            registers.kill(idx.to_string(), false, false);
+        }
+        assert_eq!(registers.kill_ring.len(), KILL_RING_SIZE);
+        assert_eq!(registers.kill_ring[0], "0");
+    }
+
+    #[test]
+    fn diffs() {
+        assert_eq!(diff("abc", &(1..1), "abc", 1..1), Action::Consumed);
+        assert_eq!(
+            diff("abc", &(1..1), "abc", 2..3),
+            Action::Edit(Edit {
+                range: 2..2,
+                text: String::new(),
+                selection: 2..3,
+            }),
+        );
+        assert_eq!(
+            diff("aäc", &(0..0), "axc", 1..1),
+            Action::Edit(Edit {
+                range: 1..3,
+                text: "x".to_owned(),
+                selection: 1..1,
+            }),
+        );
+    }
+
+    #[test]
+    fn columns() {
+        let text = "äb\nc";
+        assert_eq!(column(text, 3), 2);
+        assert_eq!(at_column(text, 0, 1), 2);
+        assert_eq!(at_column(text, 0, 5), 3);
+        assert_eq!(at_column(text, 4, 5), 5);
+        assert_eq!(next_char(text, 0), 2);
+        assert_eq!(prev_char(text, 2), 0);
+        assert_eq!(char_at(text, 2), Some('b'));
+        assert_eq!(char_class('_', false), char_class('x', false));
+        assert_ne!(char_class('.', false), char_class('x', false));
+        assert_eq!(char_class('.', true), char_class('x', true));
+    }
+}
@@ -1,19 +1,45 @@
 mod app;
+mod commands;
+mod config;
+mod editing;
 mod editor;
+mod emacs;
+mod embed;
+mod examples;
+mod folding;
+mod highlight;
+mod history;
+mod keymap;
+mod preferences;
+mod project;
+mod report;
+mod semantic;
+mod share;
+mod snippet;
+mod theme_editor;
+mod themes;
+mod vim;
+mod workspaces;
+mod zip;
 
+use std::cell::RefCell;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::panic::{Location, PanicHookInfo};
+use std::rc::Rc;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::{Relaxed, SeqCst};
 
 use once_cell::sync::Lazy;
 use syntect::highlighting::Theme;
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
 use syntect_assets::assets::HighlightingAssets;
 use wasm_bindgen::prelude::wasm_bindgen;
 use web_sys::js_sys::Error;
 use web_sys::wasm_bindgen::throw_val;
+use yew::AttrValue;
 
 use crate::app::App;
+use crate::config::Delimiters;
 
 fn main() {
     yew::set_custom_panic_hook({
@@ -87,9 +113,131 @@ static ASSETS: Lazy<(&SyntaxSet, &[(&str, &Theme)])> = Lazy::new(|| {
     let themes = Box::leak(themes);
 
     let syntax_set = assets.get_syntax_set().unwrap_at();
+    let mut builder = syntax_set.clone().into_builder();
+    for &(_, name, id) in TEMPLATE_LANGUAGES {
+        let syntax = format!("Rinja ({name})");
+        if let Some(syntax) = rinja_syntax(syntax_set, &syntax, name, id, &Delimiters::default()) {
+            builder.add(syntax);
+        }
+    }
+    let syntax_set = Box::leak(Box::new(builder.build()));
     (syntax_set, themes)
 });
 
+const RINJA_SYNTAX: &str = include_str!("rinja.sublime-syntax");
+
+/// The languages a template can be written in: file extensions, syntax name, and scope id
+const TEMPLATE_LANGUAGES: &[(&[&str], &str, &str)] = &[
+    (&["html", "htm"], "HTML", "html"),
+    (&["xml", "svg"], "XML", "xml"),
+    (&["md", "markdown"], "Markdown", "markdown"),
+    (&["css"], "CSS", "css"),
+    (&["js", "mjs"], "JavaScript", "js"),
+    (&["json"], "JSON", "json"),
+    (&["yml", "yaml"], "YAML", "yaml"),
+    (&["toml"], "TOML", "toml"),
+    (&["sql"], "SQL", "sql"),
+    (&["tex"], "LaTeX", "latex"),
+    (&["txt"], "Plain Text", "plain"),
+];
+
+struct CustomSyntaxes {
+    syntax: AttrValue,
+    syntax_set: Rc<SyntaxSet>,
+}
+
+thread_local! {
+    /// Only the syntaxes for the most recently used custom delimiters are kept.
+    static CUSTOM_SYNTAXES: RefCell<Option<CustomSyntaxes>> = const { RefCell::new(None) };
+}
+
+/// The syntax to highlight a template with the file extension `ext` and the given `delimiters`.
+fn template_syntax(ext: Option<&str>, delimiters: &Delimiters) -> AttrValue {
+    let (syntax_set, _) = *ASSETS;
+    let ext = ext.unwrap_or("txt").to_ascii_lowercase();
+    let language = [ext.as_str(), "txt"].into_iter().find_map(|ext| {
+        TEMPLATE_LANGUAGES.iter().find_map(|&(exts, name, id)| {
+            let syntax = syntax_set.find_syntax_by_name(&format!("Rinja ({name})"))?;
+            exts.contains(&ext).then_some((syntax, name, id))
+        })
+    });
+    let Some((default_syntax, name, id)) = language else {
+        return AttrValue::Static("Plain Text");
+    };
+    let default_syntax = AttrValue::Static(default_syntax.name.as_str());
+    if *delimiters == Delimiters::default() {
+        return default_syntax;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    delimiters.hash(&mut hasher);
+    let syntax = format!("Rinja ({name}) {:016x}", hasher.finish());
+    CUSTOM_SYNTAXES.with_borrow_mut(|custom| {
+        if let Some(custom) = custom.as_ref().filter(|custom| custom.syntax == syntax) {
+            return custom.syntax.clone();
+        }
+        let Some(definition) = rinja_syntax(syntax_set, &syntax, name, id, delimiters) else {
+            return default_syntax;
+        };
+        let mut builder = syntax_set.clone().into_builder();
+        builder.add(definition);
+        let syntax = AttrValue::from(syntax);
+        *custom = Some(CustomSyntaxes {
+            syntax: syntax.clone(),
+            syntax_set: Rc::new(builder.build()),
+        });
+        syntax
+    })
+}
+
+/// The syntax set that contains the syntax `name` if it was made for custom delimiters.
+fn custom_syntax_set(name: &str) -> Option<Rc<SyntaxSet>> {
+    CUSTOM_SYNTAXES.with_borrow(|custom| {
+        let custom = custom.as_ref().filter(|custom| custom.syntax == name)?;
+        Some(Rc::clone(&custom.syntax_set))
+    })
+}
+
+fn rinja_syntax(
+    syntax_set: &SyntaxSet,
+    name: &str,
+    outer_name: &str,
+    outer_id: &str,
+    delimiters: &Delimiters,
+) -> Option<SyntaxDefinition> {
+    let outer = syntax_set.find_syntax_by_name(outer_name)?;
+    let source = RINJA_SYNTAX
+        .replace("%NAME%", name)
+        .replace("%OUTER_ID%", outer_id)
+        .replace("%OUTER_SCOPE%", &outer.scope.build_string())
+        .replace("%BLOCK_START%", &escape_pattern(&delimiters.block_start))
+        .replace("%BLOCK_END%", &escape_pattern(&delimiters.block_end))
+        .replace("%EXPR_START%", &escape_pattern(&delimiters.expr_start))
+        .replace("%EXPR_END%", &escape_pattern(&delimiters.expr_end))
+        .replace(
+            "%COMMENT_START%",
+            &escape_pattern(&delimiters.comment_start),
+        )
+        .replace("%COMMENT_END%", &escape_pattern(&delimiters.comment_end));
+    SyntaxDefinition::load_from_str(&source, true, None).ok()
+}
+
+/// Escapes `s` to be used in a single quoted regular expression of a sublime-syntax file.
+fn escape_pattern(s: &str) -> String {
+    let mut escaped = String::with_capacity(2 * s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\'' => escaped.push_str("''"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 #[wasm_bindgen]
 extern "C" {
     fn panic_reload();
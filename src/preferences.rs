@@ -0,0 +1,118 @@
+use std::fmt::Write;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use yew::AttrValue;
+
+use crate::app::{EDITOR_FONT_KEY, PREFERENCES_KEY, local_storage};
+use crate::editing::TAB_WIDTH;
+use crate::keymap::Keymap;
+
+pub const FONT_SIZES: std::ops::RangeInclusive<u32> = 8..=40;
+pub const TAB_WIDTHS: std::ops::RangeInclusive<usize> = 1..=8;
+
+/// How the editors show and edit text, set in the settings menu.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// In CSS pixels
+    pub font_size: u32,
+    /// Width of a tab character, and how many spaces the Tab key inserts
+    pub tab_width: usize,
+    pub wrap: bool,
+    pub show_whitespace: bool,
+    pub keymap: Keymap,
+    /// Stored separately, because it is much larger than the rest.
+    #[serde(skip)]
+    pub font: Option<Rc<EditorFont>>,
+}
+
+/// A font file the user uploaded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditorFont {
+    pub name: String,
+    /// The font file as `data:` URL
+    pub data: String,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            font_size: 16,
+            tab_width: TAB_WIDTH,
+            wrap: true,
+            show_whitespace: false,
+            keymap: Keymap::Default,
+            font: None,
+        }
+    }
+}
+
+impl Preferences {
+    pub fn load() -> Self {
+        let Some(storage) = local_storage() else {
+            return Self::default();
+        };
+        let mut preferences = storage
+            .get_item(PREFERENCES_KEY)
+            .ok()
+            .flatten()
+            .and_then(|data| serde_json::from_str::<Self>(&data).ok())
+            .unwrap_or_default();
+        preferences.font = storage
+            .get_item(EDITOR_FONT_KEY)
+            .ok()
+            .flatten()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .map(Rc::new);
+        preferences.clamp()
+    }
+
+    /// Saves everything but the font, which only needs to be saved if it changed.
+    pub fn save(&self) {
+        if let (Some(storage), Ok(data)) = (local_storage(), serde_json::to_string(self)) {
+            // Doesn't matter whether or not it succeeded.
+            let _ = storage.set_item(PREFERENCES_KEY, &data);
+        }
+    }
+
+    pub fn save_font(&self) {
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        // Doesn't matter whether or not it succeeded, the font may be too large to be stored.
+        let _ = match self.font.as_deref().map(serde_json::to_string) {
+            Some(Ok(data)) => storage.set_item(EDITOR_FONT_KEY, &data),
+            _ => storage.remove_item(EDITOR_FONT_KEY),
+        };
+    }
+
+    pub fn clamp(mut self) -> Self {
+        self.font_size = self.font_size.clamp(*FONT_SIZES.start(), *FONT_SIZES.end());
+        self.tab_width = self.tab_width.clamp(*TAB_WIDTHS.start(), *TAB_WIDTHS.end());
+        self
+    }
+
+    /// Custom properties for the editors, and the `@font-face` of the uploaded font.
+    pub fn css(&self) -> AttrValue {
+        let mut css = String::new();
+        let font_family = match &self.font {
+            Some(font) => {
+                let _ = writeln!(
+                    css,
+                    "@font-face {{ font-family: \"Editor Font\"; src: url(\"{}\"); }}",
+                    font.data,
+                );
+                "\"Editor Font\", Hack"
+            }
+            None => "Hack",
+        };
+        let _ = writeln!(
+            css,
+            ":root {{ --editor-font-size: {}px; --editor-tab-size: {}; \
+             --editor-font-family: {font_family}; }}",
+            self.font_size, self.tab_width,
+        );
+        AttrValue::from(css)
+    }
+}
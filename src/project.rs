@@ -0,0 +1,573 @@
+//! Turns the playground's struct, template and configuration into a Cargo project.
+
+use std::fmt::Write;
+use std::rc::Rc;
+
+use prettyplease::unparse;
+use proc_macro2::Span;
+use quote::{ToTokens, quote};
+use syn::ext::IdentExt;
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Expr, ExprLit, Fields, File, Ident, Item, Lit, LitStr, Meta, Path, Token, Type,
+    parse_file, parse_quote,
+};
+use web_sys::js_sys::Date;
+
+use crate::share::Workspace;
+use crate::zip::{DosDateTime, ZipWriter, read_zip};
+
+/// A Cargo project, named after the template's type.
+#[derive(Debug)]
+pub struct Project {
+    pub name: String,
+    /// The paths are relative to the project's root.
+    pub files: Vec<(String, String)>,
+}
+
+impl Project {
+    pub fn new(rust: &str, tmpl: &str, config: &str) -> Result<Self, String> {
+        let mut file = parse_file(rust).map_err(|err| format!("The struct has errors: {err}"))?;
+
+        // Like in the preview, the template belongs to the first type that derives `Template`.
+        let (attrs, ident) = file
+            .items
+            .iter_mut()
+            .find_map(|item| match item {
+                Item::Struct(item) if has_template(&item.attrs) => {
+                    Some((&mut item.attrs, &item.ident))
+                }
+                Item::Enum(item) if has_template(&item.attrs) => {
+                    Some((&mut item.attrs, &item.ident))
+                }
+                _ => None,
+            })
+            .ok_or("There is no struct or enum that derives `Template`.")?;
+        let file_name = snake_case(&ident.to_string());
+        let ext = use_template_path(attrs, &file_name)?;
+        let template_path = format!("templates/{file_name}.{ext}");
+
+        add_template_import(&mut file);
+        if !file
+            .items
+            .iter()
+            .any(|item| matches!(item, Item::Fn(item) if item.sig.ident == "main"))
+        {
+            let main = sample_main(&file).ok_or("The type of the template cannot be found.")?;
+            file.items.push(main);
+        }
+
+        let mut files = vec![
+            ("Cargo.toml".to_owned(), cargo_toml(&file_name)),
+            ("src/main.rs".to_owned(), format_file(file)),
+            (template_path, ensure_newline(tmpl)),
+        ];
+        // Even the default config defines the `custom` syntax, which the struct may use.
+        if !config.trim().is_empty() {
+            files.push(("rinja.toml".to_owned(), ensure_newline(config)));
+        }
+        Ok(Self {
+            name: file_name.replace('_', "-"),
+            files,
+        })
+    }
+
+    /// A zip file with the project in a folder of its name.
+    pub fn to_zip(&self) -> Vec<u8> {
+        let now = Date::new_0();
+        let mut zip = ZipWriter::new(DosDateTime::new(
+            now.get_full_year(),
+            now.get_month() + 1,
+            now.get_date(),
+            now.get_hours(),
+            now.get_minutes(),
+            now.get_seconds(),
+        ));
+        for (path, content) in &self.files {
+            zip.add(&format!("{}/{path}", self.name), content.as_bytes());
+        }
+        zip.finish()
+    }
+}
+
+/// Reads the struct, a template and `rinja.toml` from files of a crate, or from a zip of them.
+///
+/// Only the parts that are found are returned, together with notes about files that were left out.
+pub fn import_files(files: Vec<(String, Vec<u8>)>) -> Result<(Workspace, Vec<String>), String> {
+    let mut texts = Vec::new();
+    for (name, data) in files {
+        if name.to_ascii_lowercase().ends_with(".zip") || data.starts_with(b"PK\x03\x04") {
+            let entries = read_zip(&data).map_err(|err| format!("{name}: {err}"))?;
+            texts.extend(
+                entries
+                    .into_iter()
+                    .filter_map(|(path, data)| Some((path, String::from_utf8(data).ok()?))),
+            );
+        } else if let Ok(text) = String::from_utf8(data) {
+            texts.push((name, text));
+        }
+    }
+    // Leaves out `.git/`, the `__MACOSX/` folder of zips made on a Mac, etc.
+    texts.retain(|(path, _)| {
+        !path
+            .split('/')
+            .any(|part| part.starts_with('.') || part == "__MACOSX")
+    });
+    texts.sort();
+
+    let mut workspace = Workspace::default();
+    let mut notes = Vec::new();
+    let (mut template_path, mut inline_source) = (None, None);
+    for (path, text) in texts.iter().filter(|(path, _)| path.ends_with(".rs")) {
+        let Ok(mut file) = parse_file(text) else {
+            notes.push(format!("{path} has errors, it was left out."));
+            continue;
+        };
+        let Some(idx) = file.items.iter().position(|item| match item {
+            Item::Struct(item) => has_template(&item.attrs),
+            Item::Enum(item) => has_template(&item.attrs),
+            _ => false,
+        }) else {
+            continue;
+        };
+        let mut item = file.items.swap_remove(idx);
+        let attrs = match &mut item {
+            Item::Struct(item) => &mut item.attrs,
+            Item::Enum(item) => &mut item.attrs,
+            _ => continue,
+        };
+        (template_path, inline_source) = use_template_source(attrs)?;
+        let rust = format_file(File {
+            shebang: None,
+            attrs: Vec::new(),
+            items: vec![item],
+        });
+        workspace.rust = Some(Rc::from(rust.trim_end()));
+        break;
+    }
+
+    let templates = texts
+        .iter()
+        .filter(|(path, _)| {
+            !path.ends_with(".rs")
+                && !path.ends_with(".toml")
+                && (!path.contains('/') || path.split('/').any(|part| part == "templates"))
+        })
+        .collect::<Vec<_>>();
+    let chosen = match (&template_path, &inline_source) {
+        (_, Some(_)) => None,
+        (Some(wanted), None) => {
+            let found = templates
+                .iter()
+                .position(|(path, _)| path == wanted || path.ends_with(&format!("/{wanted}")));
+            if found.is_none() && !templates.is_empty() {
+                notes.push(format!(
+                    "The struct's template {wanted:?} is not among the files."
+                ));
+            }
+            found.or((!templates.is_empty()).then_some(0))
+        }
+        (None, None) => (!templates.is_empty()).then_some(0),
+    };
+    workspace.tmpl = match chosen {
+        Some(idx) => Some(Rc::from(templates[idx].1.as_str())),
+        None => inline_source.map(Rc::from),
+    };
+    let skipped = templates
+        .iter()
+        .enumerate()
+        .filter(|&(idx, _)| Some(idx) != chosen)
+        .map(|(_, (path, _))| path.as_str())
+        .collect::<Vec<_>>();
+    if !skipped.is_empty() {
+        notes.push(format!(
+            "The playground has one template, these were left out: {}",
+            skipped.join(", "),
+        ));
+    }
+
+    workspace.config = texts
+        .iter()
+        .find(|(path, _)| path.rsplit('/').next() == Some("rinja.toml"))
+        .map(|(_, text)| Rc::from(text.as_str()));
+
+    if workspace.rust.is_none() && workspace.tmpl.is_none() && workspace.config.is_none() {
+        return Err(
+            "The files contain no struct that derives `Template`, no template and no `rinja.toml`."
+                .to_owned(),
+        );
+    }
+    Ok((workspace, notes))
+}
+
+/// Like [`unparse`], but with an empty line between the items.
+fn format_file(file: File) -> String {
+    let mut items = file.items.into_iter();
+    let mut text = unparse(&File {
+        items: items.next().into_iter().collect(),
+        ..file
+    });
+    for item in items {
+        text.push('\n');
+        text.push_str(&unparse(&File {
+            shebang: None,
+            attrs: Vec::new(),
+            items: vec![item],
+        }));
+    }
+    text
+}
+
+fn has_template(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("template"))
+}
+
+/// Replaces the source of the template with `templates/{name}.{ext}`, and returns `ext`.
+///
+/// The extension selects the escaper, so the `ext` argument is dropped, too.
+fn use_template_path(attrs: &mut Vec<Attribute>, name: &str) -> Result<String, String> {
+    let (idx, mut args) = take_template_args(attrs)?;
+    let ext = take_arg(&mut args, "ext").unwrap_or_else(|| "txt".to_owned());
+    take_arg(&mut args, "source");
+    take_arg(&mut args, "path");
+    take_arg(&mut args, "in_doc");
+
+    let path = LitStr::new(&format!("{name}.{ext}"), Span::call_site());
+    attrs.insert(idx, parse_quote!(#[template(path = #path #(, #args)*)]));
+    Ok(ext)
+}
+
+/// Removes the `path` or `source` of the template, which the playground provides, and returns
+/// them. The escaper is kept with an `ext` argument.
+fn use_template_source(
+    attrs: &mut Vec<Attribute>,
+) -> Result<(Option<String>, Option<String>), String> {
+    let (idx, mut args) = take_template_args(attrs)?;
+    let path = take_arg(&mut args, "path");
+    let source = take_arg(&mut args, "source");
+    take_arg(&mut args, "in_doc");
+
+    let ext = path
+        .as_deref()
+        .and_then(|path| Some(path.rsplit_once('.')?.1))
+        .filter(|_| !args.iter().any(|arg| arg.path().is_ident("ext")));
+    let ext = ext
+        .map(|ext| LitStr::new(ext, Span::call_site()))
+        .into_iter();
+    attrs.insert(idx, parse_quote!(#[template(#(ext = #ext,)* #(#args),*)]));
+    Ok((path, source))
+}
+
+/// Removes the `#[template]` attributes, and returns their position and arguments.
+fn take_template_args(attrs: &mut Vec<Attribute>) -> Result<(usize, Vec<Meta>), String> {
+    let mut args = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("template")) {
+        let metas = attr
+            .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .map_err(|err| format!("The `#[template]` attribute has errors: {err}"))?;
+        args.extend(metas);
+    }
+    let idx = attrs
+        .iter()
+        .position(|attr| attr.path().is_ident("template"))
+        .unwrap_or(attrs.len());
+    attrs.retain(|attr| !attr.path().is_ident("template"));
+    Ok((idx, args))
+}
+
+/// Removes the argument `name`, and returns its value if it is a string.
+fn take_arg(args: &mut Vec<Meta>, name: &str) -> Option<String> {
+    let idx = args.iter().position(|arg| arg.path().is_ident(name))?;
+    match args.remove(idx) {
+        Meta::NameValue(arg) => match arg.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }) => Some(lit.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn add_template_import(file: &mut File) {
+    let imported = file.items.iter().any(|item| match item {
+        Item::Use(item) => item.to_token_stream().to_string().contains("Template"),
+        _ => false,
+    });
+    if !imported {
+        file.items.insert(
+            0,
+            parse_quote!(
+                use rinja::Template;
+            ),
+        );
+    }
+}
+
+/// A `main` that renders the first template with made-up values.
+fn sample_main(file: &File) -> Option<Item> {
+    let value = file.items.iter().find_map(|item| match item {
+        Item::Struct(item) if has_template(&item.attrs) => {
+            let ident = &item.ident;
+            Some(sample_construction(parse_quote!(#ident), &item.fields))
+        }
+        Item::Enum(item) if has_template(&item.attrs) => {
+            let ident = &item.ident;
+            let variant = item.variants.first()?;
+            let variant_ident = &variant.ident;
+            Some(sample_construction(
+                parse_quote!(#ident::#variant_ident),
+                &variant.fields,
+            ))
+        }
+        _ => None,
+    })?;
+    Some(parse_quote! {
+        fn main() {
+            let template = #value;
+            println!("{}", template.render().unwrap());
+        }
+    })
+}
+
+fn sample_construction(path: Path, fields: &Fields) -> Expr {
+    match fields {
+        Fields::Named(fields) => {
+            let fields = fields.named.iter().filter_map(|field| {
+                let ident = field.ident.as_ref()?;
+                let value = sample_value(&field.ty, &ident.unraw().to_string());
+                Some(quote!(#ident: #value))
+            });
+            parse_quote!(#path { #(#fields),* })
+        }
+        Fields::Unnamed(fields) => {
+            let values = fields
+                .unnamed
+                .iter()
+                .enumerate()
+                .map(|(idx, field)| sample_value(&field.ty, &format!("field {idx}")));
+            parse_quote!(#path(#(#values),*))
+        }
+        Fields::Unit => parse_quote!(#path),
+    }
+}
+
+/// Strings get the name of their field, other types a value that makes conditions true.
+fn sample_value(ty: &Type, name: &str) -> Expr {
+    match ty {
+        Type::Reference(reference) => match &*reference.elem {
+            Type::Path(elem) if elem.path.is_ident("str") => parse_quote!(#name),
+            Type::Slice(_) => parse_quote!(&[]),
+            elem => {
+                let value = sample_value(elem, name);
+                parse_quote!(&#value)
+            }
+        },
+        Type::Path(path) => {
+            let ident = path.path.segments.last().map(|segment| &segment.ident);
+            match ident.map(Ident::to_string).as_deref() {
+                Some("String" | "Cow") => parse_quote!(#name.into()),
+                Some("bool") => parse_quote!(true),
+                Some("char") => parse_quote!('x'),
+                Some("f32" | "f64") => parse_quote!(1.0),
+                Some(
+                    "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64"
+                    | "i128" | "isize",
+                ) => parse_quote!(1),
+                _ => parse_quote!(Default::default()),
+            }
+        }
+        _ => parse_quote!(Default::default()),
+    }
+}
+
+/// The playground is built from a checkout of rinja. A released version is used if it is one.
+fn cargo_toml(name: &str) -> String {
+    let features = r#"features = ["serde_json", "urlencode"]"#;
+    let mut parts = env!("RINJA_DESCR").rsplitn(3, '-');
+    let (_, distance, tag) = (parts.next(), parts.next(), parts.next());
+    let dependency = match (distance, tag) {
+        (Some("0"), Some(tag)) => {
+            format!(
+                r#"{{ version = "{}", {features} }}"#,
+                tag.trim_start_matches('v')
+            )
+        }
+        _ => format!(
+            r#"{{ git = "{}", rev = "{}", {features} }}"#,
+            env!("RINJA_URL"),
+            env!("RINJA_REV"),
+        ),
+    };
+
+    let mut toml = String::new();
+    let _ = writeln!(toml, "[package]");
+    let _ = writeln!(toml, "name = {:?}", name.replace('_', "-"));
+    let _ = writeln!(toml, "version = \"0.1.0\"");
+    let _ = writeln!(toml, "edition = \"2021\"");
+    let _ = writeln!(toml, "publish = false");
+    let _ = writeln!(toml);
+    let _ = writeln!(toml, "[dependencies]");
+    let _ = writeln!(toml, "rinja = {dependency}");
+    toml
+}
+
+/// `HelloWorld` becomes `hello_world`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (idx, c) in name.trim_start_matches("r#").char_indices() {
+        if c.is_uppercase() && idx > 0 && !snake.ends_with('_') {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+fn ensure_newline(text: &str) -> String {
+    match text.ends_with('\n') || text.is_empty() {
+        true => text.to_owned(),
+        false => format!("{text}\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{CONFIG_SOURCE, STRUCT_SOURCE, TMPL_SOURCE};
+
+    #[test]
+    fn default_sources() {
+        let project = Project::new(STRUCT_SOURCE, TMPL_SOURCE, CONFIG_SOURCE).unwrap();
+        assert_eq!(project.name, "hello-world");
+        let paths = project.files.iter().map(|(path, _)| path.as_str());
+        assert_eq!(
+            paths.collect::<Vec<_>>(),
+            [
+                "Cargo.toml",
+                "src/main.rs",
+                "templates/hello_world.html",
+                "rinja.toml"
+            ],
+        );
+        assert!(
+            project.files[1]
+                .1
+                .contains("#[template(path = \"hello_world.html\")]")
+        );
+        assert!(
+            project.files[1]
+                .1
+                .contains("user: \"user\",\n        first_visit: true,")
+        );
+        assert_eq!(project.files[2].1, format!("{TMPL_SOURCE}\n"));
+        assert_eq!(project.files[3].1, format!("{CONFIG_SOURCE}\n"));
+
+        let project = Project::new(STRUCT_SOURCE, TMPL_SOURCE, " \n").unwrap();
+        assert_eq!(project.files.len(), 3);
+    }
+
+    #[test]
+    fn default_config_with_custom_syntax() {
+        let rust = r#"
+            #[derive(Template)]
+            #[template(ext = "html", syntax = "custom")]
+            struct Custom;
+        "#;
+        let project = Project::new(rust, "<< 1 >>", CONFIG_SOURCE).unwrap();
+        assert!(
+            project.files[1]
+                .1
+                .contains(r#"#[template(path = "custom.html", syntax = "custom")]"#)
+        );
+        assert_eq!(
+            project.files[3],
+            ("rinja.toml".to_owned(), format!("{CONFIG_SOURCE}\n"))
+        );
+    }
+
+    #[test]
+    fn arguments() {
+        let rust = r#"
+            use rinja::Template;
+            #[derive(Template)]
+            #[template(ext = "txt", print = "code")]
+            #[template(source = "{{ 0 }}", syntax = "custom")]
+            enum r#Type<'a> {
+                Message(&'a str, u8),
+                Other,
+            }
+        "#;
+        let config = "[[syntax]]\nname = \"custom\"";
+        let project = Project::new(rust, "{{ 0 }}", config).unwrap();
+        assert_eq!(project.name, "type");
+        let main = &project.files[1].1;
+        assert!(main.starts_with("use rinja::Template;\n\n#[derive(Template)]\n"));
+        assert!(
+            main.contains(r#"#[template(path = "type.txt", print = "code", syntax = "custom")]"#)
+        );
+        assert!(main.contains(r#"let template = r#Type::Message("field 0", 1);"#));
+        assert_eq!(project.files[2].0, "templates/type.txt");
+        assert_eq!(
+            project.files[3],
+            ("rinja.toml".to_owned(), format!("{config}\n"))
+        );
+
+        assert!(Project::new("struct Plain;", "", "").is_err());
+        assert!(Project::new("struct Broken {", "", "").is_err());
+    }
+
+    #[test]
+    fn import() {
+        let project = Project::new(STRUCT_SOURCE, TMPL_SOURCE, "[general]").unwrap();
+        let mut zip = ZipWriter::new(DosDateTime::new(2024, 5, 17, 13, 45, 30));
+        for (path, content) in &project.files {
+            zip.add(&format!("hello-world/{path}"), content.as_bytes());
+        }
+        zip.add("__MACOSX/hello-world/._Cargo.toml", b"\0\x05\x16\x07");
+        let files = vec![("hello-world.zip".to_owned(), zip.finish())];
+
+        let (workspace, notes) = import_files(files).unwrap();
+        assert_eq!(notes, Vec::<String>::new());
+        let rust = workspace.rust.unwrap();
+        assert!(rust.starts_with("#[derive(Template)]\n#[template(ext = \"html\")]\nstruct"));
+        assert_eq!(
+            workspace.tmpl.as_deref(),
+            Some(format!("{TMPL_SOURCE}\n").as_str())
+        );
+        assert_eq!(workspace.config.as_deref(), Some("[general]\n"));
+    }
+
+    #[test]
+    fn import_loose_files() {
+        let rust = r#"
+            #[derive(Template)]
+            #[template(path = "b.txt", escape = "none")]
+            struct B;
+        "#;
+        let file = |name: &str, text: &str| (name.to_owned(), text.as_bytes().to_vec());
+        let files = vec![
+            file("a.html", "a"),
+            file("b.txt", "b"),
+            file("lib.rs", rust),
+            file("broken.rs", "struct {"),
+        ];
+        let (workspace, notes) = import_files(files).unwrap();
+        assert!(
+            workspace
+                .rust
+                .unwrap()
+                .contains(r#"#[template(ext = "txt", escape = "none")]"#)
+        );
+        assert_eq!(workspace.tmpl.as_deref(), Some("b"));
+        assert_eq!(workspace.config, None);
+        assert_eq!(notes.len(), 2);
+
+        let inline = r#"#[derive(Template)] #[template(source = "inline", ext = "txt")] struct C;"#;
+        let (workspace, _) = import_files(vec![file("c.rs", inline)]).unwrap();
+        assert_eq!(workspace.tmpl.as_deref(), Some("inline"));
+
+        assert!(import_files(vec![file("Cargo.toml", "[package]")]).is_err());
+        assert!(import_files(vec![file("damaged.zip", "PK\x03\x04")]).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! A Markdown description of the playground's state, to paste into an issue of rinja.
+
+use std::fmt::Write;
+
+use crate::app::{CONFIG_SOURCE, TREE_URL};
+
+/// Where new issues of the rinja checkout are filed
+pub const NEW_ISSUE_URL: &str = concat!(env!("RINJA_URL"), "/issues/new");
+
+#[derive(Debug)]
+pub struct Report<'a> {
+    pub rust: &'a str,
+    pub tmpl: &'a str,
+    /// The language of the template, i.e. its `ext`
+    pub tmpl_lang: &'a str,
+    pub config: &'a str,
+    pub code: &'a str,
+    pub diagnostics: &'a [&'a str],
+    pub share_url: Option<&'a str>,
+    /// The user agent and other details of the browser
+    pub browser: Option<&'a str>,
+}
+
+impl Report<'_> {
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "### Description\n");
+        let _ = writeln!(
+            md,
+            "<!-- What did you expect, and what happened instead? -->\n"
+        );
+
+        let _ = writeln!(md, "### Struct\n");
+        code_block(&mut md, "rust", self.rust);
+        let _ = writeln!(md, "### Template\n");
+        code_block(&mut md, self.tmpl_lang, self.tmpl);
+        if !self.config.trim().is_empty() && self.config.trim() != CONFIG_SOURCE.trim() {
+            let _ = writeln!(md, "### `rinja.toml`\n");
+            code_block(&mut md, "toml", self.config);
+        }
+
+        match self.diagnostics {
+            [] => {
+                let _ = writeln!(md, "<details>\n<summary>Generated code</summary>\n");
+                code_block(&mut md, "rust", self.code);
+                let _ = writeln!(md, "</details>\n");
+            }
+            diagnostics => {
+                let _ = writeln!(md, "### Diagnostics\n");
+                for msg in diagnostics {
+                    code_block(&mut md, "text", msg);
+                }
+            }
+        }
+
+        let _ = writeln!(md, "### Environment\n");
+        let _ = writeln!(md, "* rinja: [`{}`]({TREE_URL})", env!("RINJA_DESCR"),);
+        if let Some(share_url) = self.share_url {
+            let _ = writeln!(md, "* playground: [open this example]({share_url})");
+        }
+        if let Some(browser) = self.browser {
+            let _ = writeln!(md, "* browser: {browser}");
+        }
+        md
+    }
+}
+
+/// The fence is longer than any run of backticks in `text`, so the text cannot end the block.
+fn code_block(md: &mut String, lang: &str, text: &str) {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest.max(2) + 1);
+    let text = text.trim_end();
+    let _ = writeln!(md, "{fence}{lang}\n{text}\n{fence}\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown() {
+        let report = Report {
+            rust: "#[derive(Template)]\nstruct Hello;\n",
+            tmpl: "```{{ 1 }}````",
+            tmpl_lang: "html",
+            config: CONFIG_SOURCE,
+            code: "impl Template for Hello {}",
+            diagnostics: &[],
+            share_url: Some("https://example.com/?saved=abc"),
+            browser: Some("Firefox"),
+        };
+        let md = report.to_markdown();
+        assert!(md.contains("```rust\n#[derive(Template)]\nstruct Hello;\n```\n"));
+        assert!(md.contains("`````html\n```{{ 1 }}````\n`````\n"));
+        assert!(!md.contains("rinja.toml"));
+        assert!(md.contains("<summary>Generated code</summary>"));
+        assert!(md.contains("(https://example.com/?saved=abc)"));
+        assert!(md.contains("* browser: Firefox\n"));
+
+        let report = Report {
+            config: "[general]\n",
+            diagnostics: &["unknown filter"],
+            share_url: None,
+            ..report
+        };
+        let md = report.to_markdown();
+        assert!(md.contains("```toml\n[general]\n```\n"));
+        assert!(md.contains("### Diagnostics\n\n```text\nunknown filter\n```\n"));
+        assert!(!md.contains("Generated code"));
+        assert!(!md.contains("playground:"));
+    }
+}
@@ -0,0 +1,262 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use rinja_parser::{Ast, Expr, Filter, Node, SyntaxBuilder, Target, WithSpan};
+
+use crate::config::Delimiters;
+
+/// What an identifier in a template refers to, as far as the parser can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Field,
+    Local,
+    Macro,
+    Filter,
+    /// Neither a field of the struct nor a local variable
+    Undefined,
+}
+
+impl TokenKind {
+    pub fn class(self) -> &'static str {
+        match self {
+            TokenKind::Field => "sem-field",
+            TokenKind::Local => "sem-local",
+            TokenKind::Macro => "sem-macro",
+            TokenKind::Filter => "sem-filter",
+            TokenKind::Undefined => "sem-undefined",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub range: Range<usize>,
+    pub kind: TokenKind,
+}
+
+/// The identifiers in `source`, ordered by their position.
+///
+/// If the `fields` of the struct are unknown, unbound variables are assumed to be fields.
+/// Nothing is returned if the template does not parse.
+pub fn tokens(source: &str, delimiters: &Delimiters, fields: Option<&[String]>) -> Rc<[Token]> {
+    let syntax = SyntaxBuilder {
+        name: "",
+        block_start: Some(&delimiters.block_start),
+        block_end: Some(&delimiters.block_end),
+        expr_start: Some(&delimiters.expr_start),
+        expr_end: Some(&delimiters.expr_end),
+        comment_start: Some(&delimiters.comment_start),
+        comment_end: Some(&delimiters.comment_end),
+    };
+    let Ok(syntax) = syntax.to_syntax() else {
+        return Rc::new([]);
+    };
+    let Ok(ast) = Ast::from_str(source, None, &syntax) else {
+        return Rc::new([]);
+    };
+
+    let mut walker = Walker {
+        source,
+        fields,
+        scopes: vec![Vec::new()],
+        tokens: Vec::new(),
+    };
+    walker.nodes(ast.nodes());
+
+    let mut tokens = walker.tokens;
+    tokens.sort_by_key(|token| token.range.start);
+    tokens.dedup_by(|next, prev| next.range.start < prev.range.end);
+    tokens.into()
+}
+
+struct Walker<'a, 'f> {
+    source: &'a str,
+    fields: Option<&'f [String]>,
+    /// The local variables of the enclosing blocks
+    scopes: Vec<Vec<&'a str>>,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Walker<'a, '_> {
+    fn nodes(&mut self, nodes: &[Node<'a>]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn node(&mut self, node: &Node<'a>) {
+        match node {
+            Node::Expr(_, expr) => self.expr(expr),
+            Node::Let(l) => {
+                if let Some(val) = &l.val {
+                    self.expr(val);
+                }
+                self.target(&l.var);
+            }
+            Node::If(i) => {
+                for branch in &i.branches {
+                    self.scoped(|this| {
+                        if let Some(cond) = &branch.cond {
+                            this.expr(&cond.expr);
+                            if let Some(target) = &cond.target {
+                                this.target(target);
+                            }
+                        }
+                        this.nodes(&branch.nodes);
+                    });
+                }
+            }
+            Node::Match(m) => {
+                self.expr(&m.expr);
+                for arm in &m.arms {
+                    self.scoped(|this| {
+                        this.target(&arm.target);
+                        this.nodes(&arm.nodes);
+                    });
+                }
+            }
+            Node::Loop(l) => {
+                self.expr(&l.iter);
+                self.scoped(|this| {
+                    this.bind("loop");
+                    this.target(&l.var);
+                    if let Some(cond) = &l.cond {
+                        this.expr(cond);
+                    }
+                    this.nodes(&l.body);
+                });
+                self.scoped(|this| this.nodes(&l.else_nodes));
+            }
+            Node::Macro(m) => {
+                self.push(m.name, TokenKind::Macro);
+                self.scoped(|this| {
+                    for (name, default) in &m.args {
+                        if let Some(default) = default {
+                            this.expr(default);
+                        }
+                        this.bind(name);
+                        this.push(name, TokenKind::Local);
+                    }
+                    this.nodes(&m.nodes);
+                });
+            }
+            Node::Call(c) => {
+                self.push(c.name, TokenKind::Macro);
+                for arg in &c.args {
+                    self.expr(arg);
+                }
+            }
+            Node::BlockDef(b) => self.scoped(|this| this.nodes(&b.nodes)),
+            Node::FilterBlock(f) => {
+                self.filter(&f.filters);
+                self.scoped(|this| this.nodes(&f.nodes));
+            }
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &WithSpan<'a, Expr<'a>>) {
+        match &**expr {
+            Expr::Var(name) => {
+                let kind = if self.is_local(name) {
+                    TokenKind::Local
+                } else if self
+                    .fields
+                    .is_none_or(|fields| fields.iter().any(|field| field == name))
+                {
+                    TokenKind::Field
+                } else {
+                    TokenKind::Undefined
+                };
+                self.push(name, kind);
+            }
+            Expr::Filter(filter) => self.filter(filter),
+            Expr::Attr(expr, _)
+            | Expr::As(expr, _)
+            | Expr::NamedArgument(_, expr)
+            | Expr::Unary(_, expr)
+            | Expr::Group(expr)
+            | Expr::Try(expr) => self.expr(expr),
+            Expr::Index(lhs, rhs) | Expr::BinOp(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::Range(_, lhs, rhs) => {
+                for expr in [lhs, rhs].into_iter().flatten() {
+                    self.expr(expr);
+                }
+            }
+            Expr::Array(exprs) | Expr::Tuple(exprs) => {
+                for expr in exprs {
+                    self.expr(expr);
+                }
+            }
+            Expr::Call(callee, args) => {
+                self.expr(callee);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn filter(&mut self, filter: &Filter<'a>) {
+        self.push(filter.name, TokenKind::Filter);
+        for arg in &filter.arguments {
+            self.expr(arg);
+        }
+    }
+
+    /// Binds the names of a `let`, `for`, `if let` or `when` pattern.
+    fn target(&mut self, target: &Target<'a>) {
+        match target {
+            Target::Name(name) => {
+                self.bind(name);
+                self.push(name, TokenKind::Local);
+            }
+            Target::Tuple(_, targets) | Target::Array(_, targets) | Target::OrChain(targets) => {
+                for target in targets {
+                    self.target(target);
+                }
+            }
+            Target::Struct(_, targets) => {
+                for (_, target) in targets {
+                    self.target(target);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn bind(&mut self, name: &'a str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(name);
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        name == "self" || self.scopes.iter().any(|scope| scope.contains(&name))
+    }
+
+    /// `name` is a slice of the source, so its position is known.
+    fn push(&mut self, name: &str, kind: TokenKind) {
+        let Some(start) = (name.as_ptr() as usize).checked_sub(self.source.as_ptr() as usize)
+        else {
+            return;
+        };
+        let end = start + name.len();
+        if end <= self.source.len() && !name.is_empty() {
+            self.tokens.push(Token {
+                range: start..end,
+                kind,
+            });
+        }
+    }
+}
@@ -0,0 +1,609 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use brotli::{CompressorWriter, Decompressor};
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::deflate::core::{
+    CompressorOxide, TDEFLFlush, TDEFLStatus, compress_to_output, create_comp_flags_from_zip_params,
+};
+use miniz_oxide::inflate::{
+    DecompressError, TINFLStatus, decompress_to_vec_with_limit, decompress_to_vec_zlib_with_limit,
+};
+use serde::{Deserialize, Serialize};
+use syntect::highlighting::Theme;
+
+/// The newest format of shared links, the one [`Workspace::to_url_param()`] writes.
+///
+/// * `v1\0{rust}\0{tmpl}`: only the sources.
+/// * `v2\0{json}`: a [`Workspace`] as JSON.
+///
+/// The text is compressed, see [`Compression`], and encoded as base64 with `-_.` in place of `+/=`.
+pub const VERSION: u32 = 2;
+
+/// Upper bound of a decompressed link, so that a crafted link cannot exhaust the memory.
+const MAX_SIZE: usize = 16 << 20;
+
+/// Lengths of links above which some tools are known to cause trouble, and what happens then.
+pub const URL_LIMITS: &[(usize, &str)] = &[
+    (
+        2_000,
+        "Some chat tools, e-mail clients and link shorteners cut off longer links.",
+    ),
+    (8_000, "Many web servers reject links that are this long."),
+    (
+        64_000,
+        "Browsers may refuse to open links that are this long.",
+    ),
+];
+
+/// What goes wrong if a link of `len` characters is shared, if anything.
+pub fn url_warning(len: usize) -> Option<&'static str> {
+    URL_LIMITS
+        .iter()
+        .rev()
+        .find(|&&(limit, _)| len > limit)
+        .map(|&(_, warning)| warning)
+}
+
+/// Everything a shared link restores.
+///
+/// Whatever a link does not contain stays as it is, so fields can be added to the JSON object
+/// without needing a new version.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Workspace {
+    pub rust: Option<Rc<str>>,
+    pub tmpl: Option<Rc<str>>,
+    /// The content of `rinja.toml`
+    pub config: Option<Rc<str>>,
+    pub theme: Option<Rc<str>>,
+    /// The theme itself, if it was imported or edited, because the recipient won't have it.
+    pub custom_theme: Option<Rc<Theme>>,
+    /// The revision of rinja the link was made with
+    pub rinja: Option<Rc<str>>,
+}
+
+/// Why a shared link could not be opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShareError {
+    /// The link contains characters that are not part of the base64 alphabet.
+    Base64,
+    /// The decoded data cannot be decompressed.
+    Deflate,
+    /// The link ends too early, probably because it was cut off when it was copied.
+    Truncated,
+    /// The link was made by a newer version of the playground.
+    Newer(u32),
+    UnknownVersion(String),
+    /// The text of the link is no valid workspace.
+    Invalid(String),
+}
+
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base64 => f.write_str("The link could not be read: it is not valid base64."),
+            Self::Deflate => f.write_str("The link could not be read: it is not compressed data."),
+            Self::Truncated => f.write_str(
+                "The link could not be read: it is incomplete. \
+                 Maybe it was cut off when it was copied?",
+            ),
+            Self::Newer(version) => write!(
+                f,
+                "The link was made by a newer version of the playground (format v{version}). \
+                 Reload the page to update the playground, then open the link again.",
+            ),
+            Self::UnknownVersion(version) => {
+                write!(f, "The link could not be read: unknown format {version:?}.")
+            }
+            Self::Invalid(err) => write!(f, "The link could not be read: {err}"),
+        }
+    }
+}
+
+impl Workspace {
+    /// The value of the `saved` query parameter of a shared link, compressed as well as possible.
+    pub fn to_url_param(&self) -> String {
+        let text = self.to_text();
+        let data = Compression::ALL
+            .iter()
+            .map(|compression| compression.compress(text.as_bytes()))
+            .min_by_key(Vec::len)
+            .unwrap_or_default();
+        to_base64(&data)
+    }
+
+    /// Reads a link of the current or an older format.
+    pub fn from_url_param(param: &str) -> Result<Self, ShareError> {
+        let text = Compression::decompress(&from_base64(param)?)?;
+        let text = String::from_utf8(text)
+            .map_err(|_| ShareError::Invalid("the text is not UTF-8".to_owned()))?;
+        Self::from_text(&text)
+    }
+
+    /// Why the generated code may differ from what the author of the link saw, if it may.
+    pub fn revision_note(&self) -> Option<String> {
+        let rinja = self.rinja.as_deref()?;
+        let current = env!("RINJA_DESCR");
+        (rinja != current).then(|| {
+            format!(
+                "The link was made with rinja {rinja}, but the playground uses rinja {current}. \
+                 The generated code may differ.",
+            )
+        })
+    }
+
+    fn to_text(&self) -> String {
+        // Serializing strings, options and a theme cannot fail.
+        let json = serde_json::to_string(self).unwrap_or_default();
+        format!("v{VERSION}\0{json}")
+    }
+
+    fn from_text(text: &str) -> Result<Self, ShareError> {
+        let (version, payload) = text.split_once('\0').ok_or(ShareError::Truncated)?;
+        let number = version
+            .strip_prefix('v')
+            .and_then(|number| number.parse::<u32>().ok())
+            .ok_or_else(|| ShareError::UnknownVersion(version.to_owned()))?;
+        match number {
+            1 => {
+                // The template ends at the next NUL, if there is any.
+                let (rust, tmpl) = payload.split_once('\0').ok_or(ShareError::Truncated)?;
+                let tmpl = tmpl.split('\0').next().unwrap_or_default();
+                Ok(Self {
+                    rust: Some(Rc::from(rust)),
+                    tmpl: Some(Rc::from(tmpl)),
+                    ..Self::default()
+                })
+            }
+            VERSION => serde_json::from_str(payload).map_err(|err| match err.is_eof() {
+                true => ShareError::Truncated,
+                false => ShareError::Invalid(err.to_string()),
+            }),
+            number if number > VERSION => Err(ShareError::Newer(number)),
+            _ => Err(ShareError::UnknownVersion(version.to_owned())),
+        }
+    }
+}
+
+/// How the text of a link is compressed, stored in the first byte of its data.
+///
+/// Links that were made before there was a choice are zlib streams, whose first byte is never
+/// one of these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Stored = 0,
+    /// Raw deflate
+    Deflate = 1,
+    /// Raw deflate, preceded by [`DICTIONARY`]
+    Dictionary = 2,
+    /// Brotli, which often wins for long texts because its window is larger than deflate's
+    Brotli = 3,
+}
+
+impl Compression {
+    const ALL: &[Self] = &[Self::Stored, Self::Deflate, Self::Dictionary, Self::Brotli];
+
+    fn compress(self, text: &[u8]) -> Vec<u8> {
+        let mut data = vec![self as u8];
+        match self {
+            Self::Stored => data.extend_from_slice(text),
+            Self::Deflate => data.extend(compress_to_vec(text, 10)),
+            Self::Dictionary => {
+                // The dictionary is compressed first, and its output dropped. It is flushed to a
+                // byte boundary, so the rest of the stream only needs the dictionary's text.
+                let flags = create_comp_flags_from_zip_params(10, -15, 0);
+                let mut compressor = CompressorOxide::new(flags);
+                let (status, _) = compress_to_output(
+                    &mut compressor,
+                    DICTIONARY.as_bytes(),
+                    TDEFLFlush::Sync,
+                    |_| true,
+                );
+                debug_assert_eq!(status, TDEFLStatus::Okay);
+                let (status, _) =
+                    compress_to_output(&mut compressor, text, TDEFLFlush::Finish, |out| {
+                        data.extend_from_slice(out);
+                        true
+                    });
+                debug_assert_eq!(status, TDEFLStatus::Done);
+            }
+            Self::Brotli => {
+                let mut writer = CompressorWriter::new(&mut data, 4096, 11, 22);
+                // Writing into a `Vec` cannot fail.
+                let _ = writer.write_all(text);
+            }
+        }
+        data
+    }
+
+    fn decompress(data: &[u8]) -> Result<Vec<u8>, ShareError> {
+        let error = |err: DecompressError| match err.status {
+            TINFLStatus::FailedCannotMakeProgress => ShareError::Truncated,
+            _ => ShareError::Deflate,
+        };
+        let Some((&first, rest)) = data.split_first() else {
+            return Err(ShareError::Truncated);
+        };
+        match first {
+            _ if first & 0x0f == 8 => {
+                decompress_to_vec_zlib_with_limit(data, MAX_SIZE).map_err(error)
+            }
+            0 => Ok(rest.to_vec()),
+            1 => decompress_to_vec_with_limit(rest, MAX_SIZE).map_err(error),
+            2 => {
+                // Any stream that leaves the dictionary in the window at a block boundary will do,
+                // so it is prepended as an uncompressed block.
+                let dictionary = DICTIONARY.as_bytes();
+                let len = dictionary.len() as u16;
+                let mut stream = Vec::with_capacity(5 + dictionary.len() + rest.len());
+                stream.push(0);
+                stream.extend_from_slice(&len.to_le_bytes());
+                stream.extend_from_slice(&(!len).to_le_bytes());
+                stream.extend_from_slice(dictionary);
+                stream.extend_from_slice(rest);
+                let mut text = decompress_to_vec_with_limit(&stream, dictionary.len() + MAX_SIZE)
+                    .map_err(error)?;
+                text.drain(..dictionary.len());
+                Ok(text)
+            }
+            3 => {
+                let mut text = Vec::new();
+                Decompressor::new(rest, 4096)
+                    .take(MAX_SIZE as u64 + 1)
+                    .read_to_end(&mut text)
+                    .map_err(|err| match err.kind() {
+                        io::ErrorKind::UnexpectedEof => ShareError::Truncated,
+                        _ => ShareError::Deflate,
+                    })?;
+                match text.len() > MAX_SIZE {
+                    true => Err(ShareError::Deflate),
+                    false => Ok(text),
+                }
+            }
+            _ => Err(ShareError::Deflate),
+        }
+    }
+}
+
+/// Snippets that are likely to occur in a link, the most likely ones last.
+///
+/// Links compressed with [`Compression::Dictionary`] refer to it, so it must never be changed.
+/// It's the JSON text of a [`Workspace`], so special characters are escaped.
+const DICTIONARY: &str = concat!(
+    r##"{% extends \"base.html\" %}{% block content %}{% endblock %}{% include "##,
+    r##"\"{% import \"{% macro {% endmacro %}{% call {% filter {% endfilter %}"##,
+    r##"{% raw %}{% endraw %}{% let {% set {% match {% when Some with ({% when "##,
+    r##"None %}{% endmatch %}{% for item in items %}{% endfor %}{% if let Some({% "##,
+    r##"elif {% else %}{% endif %}{%-  -%}{%+ {{ loop.index }}loop.firstloop.last{# "##,
+    r##" #}|safe|escape|e|lower|upper|trim|join(\", \")|length|default(|fmt(\"|format(|inden"##,
+    r##"t(|json|urlencode|linebreaks|truncate(<!DOCTYPE html>\n<html>\n<head>\n<title>"##,
+    r##"</title>\n</head>\n<body>\n</body>\n</html><ul>\n</ul>\n<li></li>\n<p>"##,
+    r##"</p>\n<a href=\"\"></a><span class=\"</span><div class=\"</div>\n<table>"##,
+    r##"<tr><td></td></tr></table>use rinja::Template;\n\n#[derive(Template)]\n#[template(so"##,
+    r##"urce = \"\", ext = \"html\")]\n#[template(path = \"\", escape = \"none\")]\next "##,
+    r##"= \"txt\"syntax = \"struct enum impl pub fn fn &selfself.-> &'a strStringVec<Option<"##,
+    r##"Result<boolusizeu32i64f64&'a [pub mod filters {\npub fn (s: impl std::fmt::Display_:"##,
+    r##" &dyn rinja::Values) -> rinja::Result<String> {\nOk(Some(Nonematch  =>"##,
+    r##"             {"rust":"#[derive(Template)]\n#[template(ext = \"html\")]\n// "##,
+    r##"in the preview, the `source=\"…\"` or `path=\"…\"` argument is provided "##,
+    r##"for you\nstruct HelloWorld<'a> {\n    user: &'a str,\n    first_visit: "##,
+    r##"bool,\n}","tmpl":"<div class=\"example\">\n    Hello, <strong>{{user}}"##,
+    r##"</strong>!\n    {%~ if first_visit -%}\n        <br />\n        Nice to "##,
+    r##"meet you.\n    {%~ endif -%}\n</div>","config":"# The playground can't "##,
+    r##"read a config file when compiling, but it uses\n# the `[[syntax]]` delimiters "##,
+    r##"to highlight your template.\n\n[general]\ndefault_syntax = \"default\"\n\n[[syntax]]"##,
+    r##"\nname = \"custom\"\nblock_start = \"<%\"\nblock_end = \"%>\"\nexpr_start "##,
+    r##"= \"<<\"\nexpr_end = \">>\"\ncomment_start = \"<#\"\ncomment_end = \"#>"##,
+    r##"\"","theme":"","custom_theme":null,"rinja":"v0.3.5-"}"##,
+);
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const PADDING: u8 = b'.';
+
+fn to_base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for idx in 0..4 {
+            let c = match idx <= chunk.len() {
+                true => BASE64[(bits >> (18 - 6 * idx)) as usize & 63],
+                false => PADDING,
+            };
+            encoded.push(char::from(c));
+        }
+    }
+    encoded
+}
+
+/// The padding is optional, it is often lost when a link is copied.
+fn from_base64(text: &str) -> Result<Vec<u8>, ShareError> {
+    let text = text.trim_end_matches(char::from(PADDING)).as_bytes();
+    if text.len() % 4 == 1 {
+        return Err(ShareError::Truncated);
+    }
+    let mut data = Vec::with_capacity(text.len() / 4 * 3 + 2);
+    for chunk in text.chunks(4) {
+        let mut bits = 0;
+        for (idx, &c) in chunk.iter().enumerate() {
+            let value = BASE64
+                .iter()
+                .position(|&b| b == c)
+                .ok_or(ShareError::Base64)?;
+            bits |= (value as u32) << (18 - 6 * idx);
+        }
+        data.extend_from_slice(&bits.to_be_bytes()[1..chunk.len()]);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+
+    use super::*;
+
+    fn workspace() -> Workspace {
+        Workspace {
+            rust: Some(Rc::from(
+                "#[derive(Template)]\nstruct Hello<'a> { name: &'a str }",
+            )),
+            tmpl: Some(Rc::from("Hello, {{ name }}! ✓\0")),
+            config: Some(Rc::from("[general]\ndirs = [\"templates\"]")),
+            theme: Some(Rc::from("My theme")),
+            custom_theme: Some(Rc::new(Theme {
+                name: Some("My theme".to_owned()),
+                ..Theme::default()
+            })),
+            rinja: Some(Rc::from("v0.3.5")),
+        }
+    }
+
+    /// A xorshift generator, so that the "fuzz" tests are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn text(&mut self, alphabet: &[char]) -> String {
+            let len = self.below(200);
+            (0..len)
+                .map(|_| alphabet[self.below(alphabet.len())])
+                .collect()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for workspace in [workspace(), Workspace::default()] {
+            let param = workspace.to_url_param();
+            assert!(param.bytes().all(|c| BASE64.contains(&c) || c == PADDING));
+            assert_eq!(Workspace::from_url_param(&param), Ok(workspace));
+        }
+    }
+
+    #[test]
+    fn compressions() {
+        let texts = [
+            String::new(),
+            "x".to_owned(),
+            workspace().to_text(),
+            "{% for i in 0..100 %}{{ i }}{% endfor %}".repeat(1000),
+        ];
+        for text in texts {
+            for &compression in Compression::ALL {
+                let data = compression.compress(text.as_bytes());
+                assert_eq!(data[0], compression as u8);
+                assert_eq!(
+                    Compression::decompress(&data),
+                    Ok(text.clone().into_bytes())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dictionary() {
+        // It must fit into the window of deflate.
+        assert!(DICTIONARY.len() < 32 * 1024);
+        let defaults = Workspace {
+            rust: Some(Rc::from(
+                "#[derive(Template)]\n#[template(ext = \"html\")]\nstruct HelloWorld<'a> {\n    \
+                 user: &'a str,\n    first_visit: bool,\n}",
+            )),
+            tmpl: Some(Rc::from(
+                "<div class=\"example\">\n    Hello, <strong>{{user}}</strong>!",
+            )),
+            ..Workspace::default()
+        };
+        let text = defaults.to_text();
+        let with = Compression::Dictionary.compress(text.as_bytes()).len();
+        let without = Compression::Deflate.compress(text.as_bytes()).len();
+        assert!(with * 2 < without, "{with} vs. {without}");
+    }
+
+    #[test]
+    fn brotli() {
+        // Repeated after more than the 32 KiB that deflate can look back
+        let mut rng = Rng(7);
+        let alphabet = ('a'..='z').chain('0'..='9').collect::<Vec<_>>();
+        let mut part = String::new();
+        while part.len() < 40_000 {
+            part.push_str(&rng.text(&alphabet));
+        }
+        let workspace = Workspace {
+            tmpl: Some(Rc::from(part.repeat(2))),
+            ..Workspace::default()
+        };
+        let param = workspace.to_url_param();
+        assert_eq!(from_base64(&param).unwrap()[0], Compression::Brotli as u8);
+        assert_eq!(Workspace::from_url_param(&param), Ok(workspace));
+    }
+
+    #[test]
+    fn revisions() {
+        let mut workspace = workspace();
+        workspace.rinja = None;
+        assert_eq!(workspace.revision_note(), None);
+        workspace.rinja = Some(Rc::from(env!("RINJA_DESCR")));
+        assert_eq!(workspace.revision_note(), None);
+        workspace.rinja = Some(Rc::from("v0.1.0-0-g0000000"));
+        assert!(
+            workspace
+                .revision_note()
+                .unwrap()
+                .contains("v0.1.0-0-g0000000")
+        );
+    }
+
+    #[test]
+    fn warnings() {
+        assert_eq!(url_warning(100), None);
+        assert_eq!(url_warning(2_000), None);
+        assert_eq!(url_warning(2_001), Some(URL_LIMITS[0].1));
+        assert_eq!(url_warning(1_000_000), Some(URL_LIMITS[2].1));
+    }
+
+    #[test]
+    fn base64() {
+        for len in 0..50 {
+            let data = (0..len)
+                .map(|idx| (idx * 37 + 11) as u8)
+                .collect::<Vec<_>>();
+            let encoded = to_base64(&data);
+            assert_eq!(encoded.len() % 4, 0);
+            assert_eq!(from_base64(&encoded), Ok(data.clone()));
+            assert_eq!(from_base64(encoded.trim_end_matches('.')), Ok(data));
+        }
+        assert_eq!(to_base64(b"\xfb\xff"), "-_8.");
+        assert_eq!(from_base64("ab+c"), Err(ShareError::Base64));
+    }
+
+    #[test]
+    fn v1() {
+        // Made by the JavaScript implementation that preceded this module.
+        let param = "eJwrM2QoLikqTS5R8EjNycm3ZgBTOgrV1Qp5ibmpCrW1igqP5kwGAAkZDf8.";
+        let workspace = Workspace::from_url_param(param).unwrap();
+        assert_eq!(workspace.rust.as_deref(), Some("struct Hello;"));
+        assert_eq!(workspace.tmpl.as_deref(), Some("Hello, {{ name }}! ✓"));
+        assert_eq!(workspace.config, None);
+        assert_eq!(workspace.theme, None);
+    }
+
+    #[test]
+    fn errors() {
+        let param = |text: &str| to_base64(&compress_to_vec_zlib(text.as_bytes(), 9));
+
+        assert_eq!(Workspace::from_url_param("abc$"), Err(ShareError::Base64));
+        assert_eq!(
+            Workspace::from_url_param(&to_base64(b"no zlib")),
+            Err(ShareError::Deflate),
+        );
+        let full = workspace().to_url_param();
+        assert_eq!(
+            Workspace::from_url_param(&full[..full.len() / 2]),
+            Err(ShareError::Truncated),
+        );
+        assert_eq!(
+            Workspace::from_url_param(&param("v2\0{\"rust\":\"str")),
+            Err(ShareError::Truncated),
+        );
+        assert_eq!(
+            Workspace::from_url_param(&param("v1\0only rust")),
+            Err(ShareError::Truncated),
+        );
+        assert_eq!(
+            Workspace::from_url_param(&param("v3\0{}")),
+            Err(ShareError::Newer(3)),
+        );
+        assert_eq!(
+            Workspace::from_url_param(&param("x1\0")),
+            Err(ShareError::UnknownVersion("x1".to_owned())),
+        );
+        assert!(matches!(
+            Workspace::from_url_param(&param("v2\0\"text\"")),
+            Err(ShareError::Invalid(_)),
+        ));
+        // Fields of later revisions of the format are ignored.
+        assert_eq!(
+            Workspace::from_url_param(&param("v2\0{\"rust\":\"a\",\"files\":[]}")),
+            Ok(Workspace {
+                rust: Some(Rc::from("a")),
+                ..Workspace::default()
+            }),
+        );
+    }
+
+    #[test]
+    fn fuzz_random_params() {
+        let mut alphabet = BASE64.map(char::from).to_vec();
+        alphabet.extend(['.', '%', '+', '/', '=', ' ', 'ä']);
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..10_000 {
+            let _ = Workspace::from_url_param(&rng.text(&alphabet));
+        }
+    }
+
+    #[test]
+    fn fuzz_random_workspaces() {
+        let alphabet = [
+            'a', 'Z', '0', ' ', '\n', '\0', '"', '\\', '{', '%', 'ä', '✓', '🦀',
+        ];
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            let mut text = || match rng.below(3) {
+                0 => None,
+                _ => Some(Rc::from(rng.text(&alphabet))),
+            };
+            let workspace = Workspace {
+                rust: text(),
+                tmpl: text(),
+                config: text(),
+                theme: text(),
+                custom_theme: None,
+                rinja: text(),
+            };
+            assert_eq!(
+                Workspace::from_url_param(&workspace.to_url_param()),
+                Ok(workspace),
+            );
+        }
+    }
+
+    #[test]
+    fn fuzz_damaged_params() {
+        let param = workspace().to_url_param();
+        let mut rng = Rng(0xdead_beef_cafe_f00d);
+        for _ in 0..5_000 {
+            let mut damaged = param.clone().into_bytes();
+            match rng.below(3) {
+                0 => damaged.truncate(rng.below(param.len())),
+                1 => {
+                    let idx = rng.below(damaged.len());
+                    damaged[idx] = BASE64[rng.below(64)];
+                }
+                _ => {
+                    let idx = rng.below(damaged.len());
+                    damaged.remove(idx);
+                }
+            }
+            let damaged = String::from_utf8(damaged).unwrap();
+            let _ = Workspace::from_url_param(&damaged);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Highlighted code as standalone HTML or SVG, with the colors of the theme written inline, so it
+//! can be pasted into documents and bug reports.
+
+use std::fmt::Write;
+
+use syntect::highlighting::{Color, FontStyle, Theme};
+
+use crate::highlight::styled_lines;
+use crate::themes::{color_to_hex, escape_xml, is_dark};
+
+const FONT_FAMILY: &str = "Hack, monospace";
+const FONT_SIZE: f64 = 14.0;
+const LINE_HEIGHT: f64 = 20.0;
+/// The advance of a character of a monospace font, relative to the font size
+const CHAR_WIDTH: f64 = 0.6;
+const PADDING: f64 = 10.0;
+
+pub fn to_html(text: &str, syntax: &str, theme: &Theme, tab_width: usize) -> String {
+    let (foreground, background) = colors(theme);
+    let mut html = format!(
+        "<pre style=\"background-color:{};color:{};padding:0.5em 1em;font-family:{FONT_FAMILY};\
+         line-height:1.4;tab-size:{tab_width};white-space:pre;\">",
+        color_to_hex(background),
+        color_to_hex(foreground),
+    );
+    for (style, piece) in styled_lines(text, syntax, theme).iter().flatten() {
+        let mut css = String::new();
+        if style.foreground != foreground {
+            let _ = write!(css, "color:{};", color_to_hex(style.foreground));
+        }
+        if style.background != background && style.background.a > 0 {
+            let _ = write!(css, "background-color:{};", color_to_hex(style.background));
+        }
+        for (flag, property) in [
+            (FontStyle::BOLD, "font-weight:bold;"),
+            (FontStyle::ITALIC, "font-style:italic;"),
+            (FontStyle::UNDERLINE, "text-decoration:underline;"),
+        ] {
+            if style.font_style.contains(flag) {
+                css.push_str(property);
+            }
+        }
+        match css.is_empty() {
+            true => html.push_str(&escape_xml(piece)),
+            false => {
+                let _ = write!(html, "<span style=\"{css}\">{}</span>", escape_xml(piece));
+            }
+        }
+    }
+    html.push_str("</pre>\n");
+    html
+}
+
+/// The backgrounds of single pieces of the text are left out.
+pub fn to_svg(text: &str, syntax: &str, theme: &Theme, tab_width: usize) -> String {
+    let (foreground, background) = colors(theme);
+    let lines = styled_lines(text, syntax, theme);
+
+    let mut body = String::new();
+    let mut columns = 0;
+    for (idx, line) in lines.iter().enumerate() {
+        let baseline = PADDING + (idx as f64 + 0.75) * LINE_HEIGHT;
+        let _ = write!(body, "<text x=\"{PADDING}\" y=\"{baseline:.1}\">");
+        let mut column = 0;
+        for (style, piece) in line {
+            let piece = expand_tabs(piece.trim_end_matches(['\r', '\n']), &mut column, tab_width);
+            if piece.is_empty() {
+                continue;
+            }
+            body.push_str("<tspan");
+            if style.foreground != foreground {
+                let _ = write!(body, " fill=\"{}\"", color_to_hex(style.foreground));
+            }
+            for (flag, attribute) in [
+                (FontStyle::BOLD, " font-weight=\"bold\""),
+                (FontStyle::ITALIC, " font-style=\"italic\""),
+                (FontStyle::UNDERLINE, " text-decoration=\"underline\""),
+            ] {
+                if style.font_style.contains(flag) {
+                    body.push_str(attribute);
+                }
+            }
+            let _ = write!(body, ">{}</tspan>", escape_xml(&piece));
+        }
+        body.push_str("</text>\n");
+        columns = columns.max(column);
+    }
+
+    let width = 2.0 * PADDING + columns as f64 * CHAR_WIDTH * FONT_SIZE;
+    let height = 2.0 * PADDING + lines.len() as f64 * LINE_HEIGHT;
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width:.0}\" height=\"{height:.0}\" \
+         viewBox=\"0 0 {width:.0} {height:.0}\" font-family=\"{FONT_FAMILY}\" \
+         font-size=\"{FONT_SIZE}\" fill=\"{}\" xml:space=\"preserve\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"{}\" />\n{body}</svg>\n",
+        color_to_hex(foreground),
+        color_to_hex(background),
+    )
+}
+
+/// The default colors of the text, like the page uses them.
+fn colors(theme: &Theme) -> (Color, Color) {
+    let dark = is_dark(theme);
+    let settings = &theme.settings;
+    let foreground = settings.foreground.unwrap_or(match dark {
+        true => Color::WHITE,
+        false => Color::BLACK,
+    });
+    let background = settings.background.unwrap_or(match dark {
+        true => Color::BLACK,
+        false => Color::WHITE,
+    });
+    (foreground, background)
+}
+
+/// Replaces tabs with spaces up to the next tab stop, and counts the columns.
+fn expand_tabs(piece: &str, column: &mut usize, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(piece.len());
+    for c in piece.chars() {
+        match c {
+            '\t' => {
+                let tab_width = tab_width.max(1);
+                let spaces = tab_width - *column % tab_width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                *column += spaces;
+            }
+            c => {
+                expanded.push(c);
+                *column += 1;
+            }
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tab_stops() {
+        let mut column = 0;
+        assert_eq!(expand_tabs("a\tb", &mut column, 4), "a   b");
+        assert_eq!(column, 5);
+        assert_eq!(expand_tabs("\t", &mut column, 4), "   ");
+        assert_eq!(column, 8);
+    }
+}
@@ -0,0 +1,194 @@
+use syntect::highlighting::{Color, FontStyle, Theme};
+use wasm_bindgen::prelude::wasm_bindgen;
+use web_sys::wasm_bindgen::JsCast;
+use web_sys::{HtmlDialogElement, HtmlInputElement};
+use yew::{
+    AttrValue, Callback, Event, Html, InputEvent, MouseEvent, Properties, function_component, html,
+    use_effect_with, use_node_ref,
+};
+
+use crate::themes::{
+    ThemeRef, color_from_input, color_to_hex, scope_to_string, settings_color_mut, settings_colors,
+    to_tmtheme,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct ThemeEditorProps {
+    pub open: bool,
+    pub name: AttrValue,
+    pub theme: ThemeRef,
+    /// Every change is applied immediately, so the editors show it while you are editing.
+    pub onchange: Callback<Theme>,
+    /// Only custom themes can be deleted.
+    #[prop_or_default]
+    pub ondelete: Option<Callback<()>>,
+    pub onclose: Callback<()>,
+}
+
+/// A non-modal dialog to tweak the colors of a theme and to export it as a `.tmTheme` file.
+#[function_component]
+pub fn ThemeEditor(props: &ThemeEditorProps) -> Html {
+    let dialog = use_node_ref();
+    use_effect_with(props.open, {
+        let dialog = dialog.clone();
+        move |&open| {
+            let Some(dialog) = dialog.cast::<HtmlDialogElement>() else {
+                return;
+            };
+            if open {
+                dialog.show();
+            } else if dialog.open() {
+                dialog.close();
+            }
+        }
+    });
+
+    // Applies `edit` to a copy of the current theme.
+    let edit = |edit: Box<dyn Fn(&mut Theme, &HtmlInputElement)>| {
+        let theme = props.theme.clone();
+        let onchange = props.onchange.clone();
+        move |ev: Event| {
+            let Some(target) = ev.target() else { return };
+            let mut theme = Theme::clone(&theme);
+            edit(&mut theme, &target.unchecked_into());
+            onchange.emit(theme);
+        }
+    };
+
+    let settings = settings_colors(&props.theme.settings)
+        .into_iter()
+        .map(|(key, color)| {
+            let oninput = edit(Box::new(move |theme, input| {
+                if let Some(color) = settings_color_mut(&mut theme.settings, key) {
+                    *color = color_from_input(&input.value(), *color).or(*color);
+                }
+            }));
+            let onclear = edit(Box::new(move |theme, _| {
+                if let Some(color) = settings_color_mut(&mut theme.settings, key) {
+                    *color = None;
+                }
+            }));
+            html! {
+                <tr>
+                    <th>{key}</th>
+                    <td>{color_input(color, oninput, onclear)}</td>
+                    <td />
+                </tr>
+            }
+        })
+        .collect::<Html>();
+
+    let scopes = props
+        .theme
+        .scopes
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let oninput = edit(Box::new(move |theme, input| {
+                if let Some(item) = theme.scopes.get_mut(idx) {
+                    let color = &mut item.style.foreground;
+                    *color = color_from_input(&input.value(), *color).or(*color);
+                }
+            }));
+            let onclear = edit(Box::new(move |theme, _| {
+                if let Some(item) = theme.scopes.get_mut(idx) {
+                    item.style.foreground = None;
+                }
+            }));
+            let font_style = item.style.font_style.unwrap_or_else(FontStyle::empty);
+            let flags = [
+                (FontStyle::BOLD, "B", "Bold"),
+                (FontStyle::ITALIC, "I", "Italic"),
+                (FontStyle::UNDERLINE, "U", "Underline"),
+            ]
+            .into_iter()
+            .map(|(flag, label, title)| {
+                let onchange = edit(Box::new(move |theme, input| {
+                    if let Some(item) = theme.scopes.get_mut(idx) {
+                        let font_style = item.style.font_style.get_or_insert_with(FontStyle::empty);
+                        font_style.set(flag, input.checked());
+                    }
+                }));
+                html! {
+                    <label {title}>
+                        <input type="checkbox" checked={font_style.contains(flag)} {onchange} />
+                        {label}
+                    </label>
+                }
+            })
+            .collect::<Html>();
+            html! {
+                <tr>
+                    <th><code>{scope_to_string(item)}</code></th>
+                    <td>{color_input(item.style.foreground, oninput, onclear)}</td>
+                    <td class="font-style">{flags}</td>
+                </tr>
+            }
+        })
+        .collect::<Html>();
+
+    let onexport = {
+        let name = props.name.clone();
+        let theme = props.theme.clone();
+        move |_: MouseEvent| {
+            download_file(
+                &format!("{name}.tmTheme"),
+                "application/x-plist",
+                &to_tmtheme(&theme),
+            );
+        }
+    };
+    let ondelete = props
+        .ondelete
+        .as_ref()
+        .map(|ondelete| ondelete.reform(|_: MouseEvent| ()));
+    let onclose = props.onclose.reform(|_: Event| ());
+    let close = props.onclose.reform(|_: MouseEvent| ());
+
+    html! {
+        <dialog id="theme_editor" ref={dialog} {onclose}>
+            <h3>{"Theme: "}{props.name.clone()}</h3>
+            <div class="theme-colors">
+                <table>
+                    <tbody>{settings}</tbody>
+                    <tbody>{scopes}</tbody>
+                </table>
+            </div>
+            <p class="hint">
+                {"Changes to a bundled theme are saved as a new custom theme."}
+            </p>
+            <div class="dialog-buttons">
+                <button type="button" onclick={onexport}>{"export .tmTheme"}</button>
+                if let Some(ondelete) = ondelete {
+                    <button type="button" onclick={ondelete}>{"delete"}</button>
+                }
+                <button type="button" onclick={close}>{"close"}</button>
+            </div>
+        </dialog>
+    }
+}
+
+fn color_input(
+    color: Option<Color>,
+    oninput: impl Fn(Event) + 'static,
+    onclear: impl Fn(Event) + 'static,
+) -> Html {
+    let value = color.map_or_else(|| "#000000".to_owned(), color_to_hex);
+    // The color input has no alpha channel.
+    let value = value[..7].to_owned();
+    let oninput = Callback::from(oninput).reform(|ev: InputEvent| ev.into());
+    let onclear = Callback::from(onclear).reform(|ev: MouseEvent| ev.into());
+    html! {
+        <span class="color">
+            <input type="color" {value} {oninput} class={color.is_none().then_some("unset")} />
+            if color.is_some() {
+                <button type="button" title="Unset" onclick={onclear}>{"×"}</button>
+            }
+        </span>
+    }
+}
+
+#[wasm_bindgen]
+extern "C" {
+    fn download_file(name: &str, mime: &str, data: &str);
+}
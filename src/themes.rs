@@ -0,0 +1,469 @@
+use std::fmt::Write;
+use std::io::Cursor;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use syntect::highlighting::{Color, FontStyle, Theme, ThemeItem, ThemeSet, ThemeSettings};
+use syntect::parsing::ScopeStack;
+use web_sys::wasm_bindgen::JsCast;
+use web_sys::wasm_bindgen::prelude::Closure;
+use web_sys::{MediaQueryListEvent, window};
+use yew::{AttrValue, hook, use_effect_with, use_state_eq};
+
+use crate::ASSETS;
+use crate::app::{CUSTOM_THEMES_KEY, THEME_PAIR_KEY, local_storage};
+use crate::embed::EMBED;
+
+/// A bundled theme, or one that was imported or edited by the user.
+#[derive(Debug, Clone)]
+pub enum ThemeRef {
+    Bundled(&'static Theme),
+    Custom(Rc<Theme>),
+}
+
+impl Deref for ThemeRef {
+    type Target = Theme;
+
+    fn deref(&self) -> &Theme {
+        match self {
+            ThemeRef::Bundled(theme) => theme,
+            ThemeRef::Custom(theme) => theme,
+        }
+    }
+}
+
+/// Themes are only compared by identity, an edited theme is a new `Rc`.
+impl PartialEq for ThemeRef {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ThemeRef::Bundled(l), ThemeRef::Bundled(r)) => std::ptr::eq(*l, *r),
+            (ThemeRef::Custom(l), ThemeRef::Custom(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+/// The themes the user imported or edited, in the order they were added.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CustomThemes(Vec<(Rc<str>, Rc<Theme>)>);
+
+impl CustomThemes {
+    pub fn load() -> Self {
+        local_storage()
+            .and_then(|storage| storage.get_item(CUSTOM_THEMES_KEY).ok().flatten())
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if EMBED.is_some() {
+            return;
+        }
+        if let (Some(storage), Ok(data)) = (local_storage(), serde_json::to_string(self)) {
+            // Doesn't matter whether or not it succeeded.
+            let _ = storage.set_item(CUSTOM_THEMES_KEY, &data);
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|(key, _)| **key == *name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Rc<Theme>> {
+        self.0
+            .iter()
+            .find(|(key, _)| **key == *name)
+            .map(|(_, theme)| theme)
+    }
+
+    /// Adds the theme `name`, or replaces it if it already exists.
+    pub fn insert(&mut self, name: Rc<str>, theme: Theme) {
+        let theme = Rc::new(theme);
+        match self.0.iter_mut().find(|(key, _)| *key == name) {
+            Some((_, value)) => *value = theme,
+            None => self.0.push((name, theme)),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.0.retain(|(key, _)| **key != *name);
+    }
+
+    /// `name`, or `name (2)`, `name (3)` … if a theme of that name exists already.
+    pub fn unused_name(&self, name: &str) -> Rc<str> {
+        let (_, bundled) = *ASSETS;
+        let exists =
+            |name: &str| self.contains(name) || bundled.iter().any(|&(key, _)| key == name);
+        if !exists(name) {
+            return Rc::from(name);
+        }
+        (2..)
+            .map(|idx| format!("{name} ({idx})"))
+            .find(|name| !exists(name))
+            .unwrap_or_default()
+            .into()
+    }
+}
+
+/// The themes to use in light and dark mode, if the theme follows the system's color scheme.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThemePair {
+    pub light: Rc<str>,
+    pub dark: Rc<str>,
+}
+
+impl ThemePair {
+    pub fn load() -> Option<Self> {
+        let data = local_storage()?.get_item(THEME_PAIR_KEY).ok().flatten()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    pub fn save(pair: Option<&Self>) {
+        if EMBED.is_some() {
+            return;
+        }
+        let Some(storage) = local_storage() else {
+            return;
+        };
+        // Doesn't matter whether or not it succeeded.
+        let _ = match pair.and_then(|pair| serde_json::to_string(pair).ok()) {
+            Some(data) => storage.set_item(THEME_PAIR_KEY, &data),
+            None => storage.remove_item(THEME_PAIR_KEY),
+        };
+    }
+
+    pub fn get(&self, dark: bool) -> &Rc<str> {
+        match dark {
+            true => &self.dark,
+            false => &self.light,
+        }
+    }
+
+    pub fn get_mut(&mut self, dark: bool) -> &mut Rc<str> {
+        match dark {
+            true => &mut self.dark,
+            false => &mut self.light,
+        }
+    }
+}
+
+/// Whether the background of `theme` is dark, by its relative luminance.
+pub fn is_dark(theme: &Theme) -> bool {
+    let Some(Color { r, g, b, .. }) = theme.settings.background else {
+        return false;
+    };
+    let luminance = 0.2126 * f64::from(r) + 0.7152 * f64::from(g) + 0.0722 * f64::from(b);
+    luminance < 128.0
+}
+
+/// Whether the system prefers a dark color scheme, updated when the preference changes.
+#[hook]
+pub fn use_prefers_dark() -> bool {
+    let query = || window()?.match_media("(prefers-color-scheme: dark)").ok()?;
+    let dark = use_state_eq(|| query().is_some_and(|query| query.matches()));
+    use_effect_with((), {
+        let dark = dark.setter();
+        move |_| {
+            let listener = query().map(|query| {
+                let listener =
+                    Closure::<dyn Fn(MediaQueryListEvent)>::new(move |ev: MediaQueryListEvent| {
+                        dark.set(ev.matches())
+                    });
+                let _ = query
+                    .add_event_listener_with_callback("change", listener.as_ref().unchecked_ref());
+                (query, listener)
+            });
+            move || {
+                if let Some((query, listener)) = listener {
+                    let _ = query.remove_event_listener_with_callback(
+                        "change",
+                        listener.as_ref().unchecked_ref(),
+                    );
+                }
+            }
+        }
+    });
+    *dark
+}
+
+/// Custom properties for the page around the editors, derived from the global colors of `theme`.
+///
+/// Colors the theme does not define are mixed from its foreground and background.
+pub fn chrome_css(theme: &Theme) -> AttrValue {
+    let settings = &theme.settings;
+    let dark = is_dark(theme);
+    let background = settings.background.unwrap_or(match dark {
+        true => Color::BLACK,
+        false => Color::WHITE,
+    });
+    let foreground = settings.foreground.unwrap_or(match dark {
+        true => Color::WHITE,
+        false => Color::BLACK,
+    });
+    let mix = |amount| mix_colors(foreground, background, amount);
+
+    let mut css = String::from(":root {\n");
+    for (name, color) in [
+        ("background", background),
+        ("foreground", foreground),
+        ("surface", mix(0.06)),
+        (
+            "accent",
+            settings.accent.or(settings.caret).unwrap_or(foreground),
+        ),
+        ("selection", settings.selection.unwrap_or_else(|| mix(0.25))),
+        ("gutter", settings.gutter.unwrap_or_else(|| mix(0.1))),
+        (
+            "gutter-foreground",
+            settings.gutter_foreground.unwrap_or_else(|| mix(0.5)),
+        ),
+        ("border", mix(0.35)),
+    ] {
+        let Color { r, g, b, a } = color;
+        let a = f64::from(a) / 255.0;
+        let _ = writeln!(css, "  --theme-{name}: rgba({r}, {g}, {b}, {a:.3});");
+    }
+    css.push_str("}\n");
+    AttrValue::from(css)
+}
+
+/// `amount` of the way from `background` to `foreground`.
+fn mix_colors(foreground: Color, background: Color, amount: f64) -> Color {
+    let mix = |fg: u8, bg: u8| (f64::from(bg) + (f64::from(fg) - f64::from(bg)) * amount) as u8;
+    Color {
+        r: mix(foreground.r, background.r),
+        g: mix(foreground.g, background.g),
+        b: mix(foreground.b, background.b),
+        a: 0xff,
+    }
+}
+
+/// The bundled themes followed by the custom themes.
+pub fn all_themes(custom: &CustomThemes) -> Vec<(AttrValue, ThemeRef)> {
+    let (_, bundled) = *ASSETS;
+    let bundled = bundled
+        .iter()
+        .map(|&(name, theme)| (AttrValue::Static(name), ThemeRef::Bundled(theme)));
+    let custom = custom.0.iter().map(|(name, theme)| {
+        (
+            AttrValue::Rc(Rc::clone(name)),
+            ThemeRef::Custom(Rc::clone(theme)),
+        )
+    });
+    bundled.chain(custom).collect()
+}
+
+/// Whether `name` can be shown and written into a stylesheet's comment as it is.
+pub fn is_plain_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.len() <= 100
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || " -_.,+'()".contains(c))
+}
+
+/// Checks a theme that came from someone else, i.e. from a shared link: its names must be plain
+/// and its scopes may only consist of the characters that scopes of real syntaxes use.
+pub fn check_theme(name: &str, theme: &Theme) -> Result<(), String> {
+    if !is_plain_name(name) {
+        return Err(format!(
+            "the theme name {name:?} contains unexpected characters."
+        ));
+    }
+    if let Some(name) = theme.name.as_deref().filter(|name| !is_plain_name(name)) {
+        return Err(format!(
+            "the theme name {name:?} contains unexpected characters."
+        ));
+    }
+    for item in &theme.scopes {
+        let scopes = scope_to_string(item);
+        let plain = |c: char| c.is_ascii_alphanumeric() || " -_.,+".contains(c);
+        if !scopes.chars().all(plain) {
+            return Err(format!(
+                "the scope {scopes:?} contains unexpected characters."
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the content of a `.tmTheme` file.
+pub fn parse_tmtheme(source: &str) -> Result<Theme, String> {
+    ThemeSet::load_from_reader(&mut Cursor::new(source.as_bytes())).map_err(|err| err.to_string())
+}
+
+/// Serializes `theme` as a `.tmTheme` file, i.e. an XML property list.
+pub fn to_tmtheme(theme: &Theme) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+        "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\">\n",
+        "<dict>\n",
+    ));
+    for (key, value) in [("name", &theme.name), ("author", &theme.author)] {
+        if let Some(value) = value {
+            write_entry(&mut out, 1, key, value);
+        }
+    }
+    out.push_str("\t<key>settings</key>\n\t<array>\n\t\t<dict>\n\t\t\t<key>settings</key>\n");
+    out.push_str("\t\t\t<dict>\n");
+    for (key, color) in settings_colors(&theme.settings) {
+        if let Some(color) = color {
+            write_entry(&mut out, 4, key, &color_to_hex(color));
+        }
+    }
+    out.push_str("\t\t\t</dict>\n\t\t</dict>\n");
+    for item in &theme.scopes {
+        out.push_str("\t\t<dict>\n");
+        write_entry(&mut out, 3, "scope", &scope_to_string(item));
+        out.push_str("\t\t\t<key>settings</key>\n\t\t\t<dict>\n");
+        let style = &item.style;
+        for (key, color) in [
+            ("foreground", style.foreground),
+            ("background", style.background),
+        ] {
+            if let Some(color) = color {
+                write_entry(&mut out, 4, key, &color_to_hex(color));
+            }
+        }
+        if let Some(font_style) = style.font_style {
+            write_entry(&mut out, 4, "fontStyle", &font_style_to_string(font_style));
+        }
+        out.push_str("\t\t\t</dict>\n\t\t</dict>\n");
+    }
+    out.push_str("\t</array>\n</dict>\n</plist>\n");
+    out
+}
+
+fn write_entry(out: &mut String, indent: usize, key: &str, value: &str) {
+    let indent = "\t".repeat(indent);
+    let _ = writeln!(out, "{indent}<key>{}</key>", escape_xml(key));
+    let _ = writeln!(out, "{indent}<string>{}</string>", escape_xml(value));
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The global colors of a theme that the theme editor shows, by their key in a `.tmTheme` file.
+pub fn settings_colors(settings: &ThemeSettings) -> [(&'static str, Option<Color>); 9] {
+    [
+        ("foreground", settings.foreground),
+        ("background", settings.background),
+        ("caret", settings.caret),
+        ("selection", settings.selection),
+        ("lineHighlight", settings.line_highlight),
+        ("gutter", settings.gutter),
+        ("gutterForeground", settings.gutter_foreground),
+        ("accent", settings.accent),
+        ("guide", settings.guide),
+    ]
+}
+
+/// The global color of `settings` that has the `key` of [`settings_colors()`].
+pub fn settings_color_mut<'a>(
+    settings: &'a mut ThemeSettings,
+    key: &str,
+) -> Option<&'a mut Option<Color>> {
+    Some(match key {
+        "foreground" => &mut settings.foreground,
+        "background" => &mut settings.background,
+        "caret" => &mut settings.caret,
+        "selection" => &mut settings.selection,
+        "lineHighlight" => &mut settings.line_highlight,
+        "gutter" => &mut settings.gutter,
+        "gutterForeground" => &mut settings.gutter_foreground,
+        "accent" => &mut settings.accent,
+        "guide" => &mut settings.guide,
+        _ => return None,
+    })
+}
+
+/// The scope selector of a theme item, as it would be written in a `.tmTheme` file.
+pub fn scope_to_string(item: &ThemeItem) -> String {
+    let stack = |stack: &ScopeStack| stack.to_string().trim_end().to_owned();
+    item.scope
+        .selectors
+        .iter()
+        .map(|selector| {
+            let mut s = stack(&selector.path);
+            for exclude in &selector.excludes {
+                s.push_str(" - ");
+                s.push_str(&stack(exclude));
+            }
+            s
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn font_style_to_string(font_style: FontStyle) -> String {
+    [
+        (FontStyle::BOLD, "bold"),
+        (FontStyle::ITALIC, "italic"),
+        (FontStyle::UNDERLINE, "underline"),
+    ]
+    .into_iter()
+    .filter(|&(flag, _)| font_style.contains(flag))
+    .map(|(_, name)| name)
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// `#rrggbb`, or `#rrggbbaa` if the color is not opaque.
+pub fn color_to_hex(color: Color) -> String {
+    let Color { r, g, b, a } = color;
+    match a {
+        0xff => format!("#{r:02x}{g:02x}{b:02x}"),
+        a => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+    }
+}
+
+/// Parses the `#rrggbb` value of a color input, keeping the alpha channel of the `old` color.
+pub fn color_from_input(value: &str, old: Option<Color>) -> Option<Color> {
+    let value = value.strip_prefix('#').filter(|value| value.len() == 6)?;
+    let channel = |idx: usize| u8::from_str_radix(value.get(idx..idx + 2)?, 16).ok();
+    Some(Color {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+        a: old.map_or(0xff, |old| old.a),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use syntect::highlighting::{ScopeSelectors, StyleModifier};
+
+    use super::*;
+
+    fn theme(name: &str, scopes: &str) -> Theme {
+        Theme {
+            name: Some(name.to_owned()),
+            scopes: vec![ThemeItem {
+                scope: ScopeSelectors::from_str(scopes).unwrap(),
+                style: StyleModifier::default(),
+            }],
+            ..Theme::default()
+        }
+    }
+
+    #[test]
+    fn untrusted_themes() {
+        let plain = theme("Solarized (dark)", "keyword.control, source.rust - comment");
+        assert_eq!(check_theme("Solarized (dark) (2)", &plain), Ok(()));
+
+        assert!(check_theme("*/ body { display: none } /*", &plain).is_err());
+        assert!(check_theme("", &plain).is_err());
+        let named = theme("x */ * { color: red } /*", "keyword");
+        assert!(check_theme("x", &named).is_err());
+        let scoped = theme("x", "keyword.}*{color:red}");
+        assert!(check_theme("x", &scoped).is_err());
+    }
+}
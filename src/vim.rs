@@ -0,0 +1,1494 @@
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::Chars;
+
+use crate::editing::{line_end, line_start};
+use crate::keymap::{
+    Action, Key, Registers, at_column, char_at, char_class, column, diff, next_char, prev_char,
+};
+
+/// Counts are capped, so that a typo can't make the editor loop for ages.
+const MAX_COUNT: usize = 9999;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual {
+        line: bool,
+    },
+}
+
+/// Vim's modes. In normal and visual mode, the typed keys are parsed into commands, in insert
+/// mode the textarea works like without a keymap.
+#[derive(Debug, Default)]
+pub struct Vim {
+    mode: Mode,
+    /// The keys of the command that is being typed
+    pending: String,
+    /// The other end of the visual selection
+    anchor: usize,
+    /// The cursor after the last command
+    cursor: usize,
+    /// The column that `j` and `k` keep
+    column: Option<usize>,
+    last_find: Option<Find>,
+    /// The change that `.` repeats
+    last_change: Option<Change>,
+    /// The command that started insert mode, where, and how long the text was then
+    insert: Option<(String, usize, usize)>,
+    /// `.` is running, so the changes are not recorded.
+    replaying: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Change {
+    keys: String,
+    /// The text typed in insert mode, if the command started insert mode
+    inserted: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Find {
+    /// `f`, `F`, `t` or `T`
+    kind: char,
+    target: char,
+}
+
+/// The text and cursor while a command runs.
+struct Buffer<'a> {
+    text: String,
+    cursor: usize,
+    registers: &'a mut Registers,
+    tab_width: usize,
+}
+
+struct Command {
+    register: Option<char>,
+    count: Option<usize>,
+    kind: Kind,
+}
+
+enum Kind {
+    Move(Motion),
+    Operate(Operator, Target),
+    /// An operator applied to the visual selection
+    Visual(Operator),
+    /// Selects a text object in visual mode
+    Select(Object),
+    /// `i`, `a`, `I`, `A`, `o` or `O`
+    Insert(char),
+    Paste {
+        before: bool,
+    },
+    Replace(char),
+    Join,
+    ToggleCase,
+    Undo,
+    Repeat,
+    StartVisual {
+        line: bool,
+    },
+    SwapAnchor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+    Lower,
+    Upper,
+    ToggleCase,
+}
+
+enum Target {
+    Motion(Motion, Option<usize>),
+    Object(Object),
+    /// The operator was doubled, like `dd`
+    Lines(Option<usize>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    NextLine,
+    PrevLine,
+    WordStart(bool),
+    WordEnd(bool),
+    WordBack(bool),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    Find(Find),
+    RepeatFind(bool),
+    MatchPair,
+    ParagraphForward,
+    ParagraphBack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Object {
+    /// `w`, `W`, a quote, or an opening bracket
+    kind: char,
+    around: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+enum Parse {
+    Incomplete,
+    Invalid,
+    Done(Command),
+}
+
+impl Vim {
+    pub fn handle(
+        &mut self,
+        key: &Key,
+        text: &str,
+        sel: Range<usize>,
+        registers: &mut Registers,
+        tab_width: usize,
+    ) -> Action {
+        let escape = key.is("Escape") || key.ctrl("[");
+        if self.mode == Mode::Insert && !escape {
+            return Action::Pass;
+        }
+
+        let mut buf = Buffer {
+            text: text.to_owned(),
+            cursor: sel.start,
+            registers,
+            tab_width,
+        };
+        if self.mode == Mode::Insert {
+            self.leave_insert(&mut buf);
+        } else {
+            buf.cursor = self.sync(text, &sel);
+            if escape {
+                self.pending.clear();
+                self.mode = Mode::Normal;
+            } else if key.ctrl("r") && self.pending.is_empty() {
+                return Action::Undo(false);
+            } else {
+                let Some(c) = vim_char(key) else {
+                    // Tab would indent, other special keys may scroll.
+                    return match key.is("Tab") {
+                        true => Action::Consumed,
+                        false => Action::Pass,
+                    };
+                };
+                self.pending.push(c);
+                let visual = matches!(self.mode, Mode::Visual { .. });
+                let command = match parse(&self.pending, visual) {
+                    Parse::Incomplete => return Action::Consumed,
+                    Parse::Invalid => {
+                        self.pending.clear();
+                        return Action::Consumed;
+                    }
+                    Parse::Done(command) => command,
+                };
+                let keys = std::mem::take(&mut self.pending);
+                if let Some(action) = self.run(command, &keys, &mut buf) {
+                    return action;
+                }
+            }
+            if self.mode != Mode::Insert {
+                buf.cursor = clamp(&buf.text, buf.cursor);
+            }
+        }
+        self.cursor = buf.cursor;
+        diff(text, &sel, &buf.text, self.selection(&buf))
+    }
+
+    pub fn status(&self) -> String {
+        let mode = match self.mode {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual { line: false } => "VISUAL",
+            Mode::Visual { line: true } => "VISUAL LINE",
+        };
+        match self.pending.is_empty() {
+            true => mode.to_owned(),
+            false => format!("{mode} {}", self.pending),
+        }
+    }
+
+    pub fn class(&self) -> &'static str {
+        match self.mode {
+            Mode::Insert => "vim-insert",
+            Mode::Normal | Mode::Visual { .. } => "vim-normal",
+        }
+    }
+
+    /// The cursor, after following changes of the selection made with the mouse or by undoing.
+    fn sync(&mut self, text: &str, sel: &Range<usize>) -> usize {
+        let valid = |pos: usize| pos <= text.len() && text.is_char_boundary(pos);
+        if let Mode::Visual { line } = self.mode {
+            if valid(self.anchor)
+                && valid(self.cursor)
+                && visual_range(text, self.anchor, self.cursor, line) == *sel
+            {
+                return self.cursor;
+            }
+        }
+        if sel.is_empty() {
+            self.mode = Mode::Normal;
+            sel.start
+        } else {
+            self.mode = Mode::Visual { line: false };
+            self.anchor = sel.start;
+            prev_char(text, sel.end)
+        }
+    }
+
+    /// The selection that the textarea shows in the current mode.
+    fn selection(&self, buf: &Buffer<'_>) -> Range<usize> {
+        match self.mode {
+            Mode::Normal | Mode::Insert => buf.cursor..buf.cursor,
+            Mode::Visual { line } => visual_range(&buf.text, self.anchor, buf.cursor, line),
+        }
+    }
+
+    fn run(&mut self, command: Command, keys: &str, buf: &mut Buffer<'_>) -> Option<Action> {
+        let Command {
+            register,
+            count,
+            kind,
+        } = command;
+        let visual = match self.mode {
+            Mode::Visual { line } => Some(line),
+            Mode::Normal | Mode::Insert => None,
+        };
+        let vertical = |motion| matches!(motion, Motion::Up | Motion::Down | Motion::LineEnd);
+        match kind {
+            Kind::Move(motion) | Kind::Operate(_, Target::Motion(motion, _))
+                if vertical(motion) => {}
+            _ => self.column = None,
+        }
+
+        match kind {
+            Kind::Move(motion) => {
+                if let Some((pos, _)) = self.motion(motion, count, &buf.text, buf.cursor) {
+                    buf.cursor = pos;
+                }
+            }
+            Kind::Operate(operator, target) => {
+                let (range, linewise) = self.target(operator, target, count, buf)?;
+                self.operate(operator, range, linewise, register, Some(keys), buf);
+            }
+            Kind::Visual(operator) => {
+                let line = visual.unwrap_or_default();
+                let range = visual_range(&buf.text, self.anchor, buf.cursor, line);
+                self.mode = Mode::Normal;
+                self.operate(operator, range, line, register, None, buf);
+            }
+            Kind::Select(object) => {
+                let range = object_range(&buf.text, buf.cursor, object)?;
+                if !range.is_empty() {
+                    self.anchor = range.start;
+                    buf.cursor = prev_char(&buf.text, range.end);
+                }
+            }
+            Kind::Insert(at) => {
+                insert_at(at, buf);
+                self.enter_insert(Some(keys), buf);
+            }
+            Kind::Paste { .. } if visual.is_some() => {
+                self.paste_visual(visual.unwrap_or_default(), register, buf);
+            }
+            Kind::Paste { before } => {
+                paste(before, register, count, buf);
+                self.record(keys);
+            }
+            Kind::Replace(c) => {
+                replace(c, count, buf)?;
+                self.record(keys);
+            }
+            Kind::Join => {
+                let (start, lines) = match visual {
+                    Some(_) => {
+                        let start = self.anchor.min(buf.cursor);
+                        let end = self.anchor.max(buf.cursor);
+                        (start, buf.text[start..end].matches('\n').count() + 1)
+                    }
+                    None => (buf.cursor, count.unwrap_or(2)),
+                };
+                buf.cursor = join(&mut buf.text, start, lines.max(2));
+                match visual {
+                    Some(_) => self.mode = Mode::Normal,
+                    None => self.record(keys),
+                }
+            }
+            Kind::ToggleCase => {
+                let end = line_end(&buf.text, buf.cursor);
+                let end = buf.text[buf.cursor..end]
+                    .char_indices()
+                    .nth(count.unwrap_or(1))
+                    .map_or(end, |(idx, _)| buf.cursor + idx);
+                let toggled = map_case(&buf.text[buf.cursor..end], Operator::ToggleCase);
+                buf.text.replace_range(buf.cursor..end, &toggled);
+                buf.cursor += toggled.len();
+                self.record(keys);
+            }
+            Kind::Undo => return Some(Action::Undo(true)),
+            Kind::Repeat => self.repeat(count, buf),
+            Kind::StartVisual { line } => match visual {
+                Some(current) if current == line => self.mode = Mode::Normal,
+                Some(_) => self.mode = Mode::Visual { line },
+                None => {
+                    self.anchor = buf.cursor;
+                    self.mode = Mode::Visual { line };
+                }
+            },
+            Kind::SwapAnchor => std::mem::swap(&mut self.anchor, &mut buf.cursor),
+        }
+        None
+    }
+
+    fn record(&mut self, keys: &str) {
+        if !self.replaying {
+            self.last_change = Some(Change {
+                keys: keys.to_owned(),
+                inserted: None,
+            });
+        }
+    }
+
+    fn enter_insert(&mut self, keys: Option<&str>, buf: &Buffer<'_>) {
+        self.mode = Mode::Insert;
+        self.insert = keys.map(|keys| (keys.to_owned(), buf.cursor, buf.text.len()));
+    }
+
+    /// Records the text typed since entering insert mode, so that `.` can type it again.
+    fn leave_insert(&mut self, buf: &mut Buffer<'_>) {
+        if let Some((keys, start, len)) = self.insert.take() {
+            if !self.replaying {
+                let inserted = buf
+                    .text
+                    .len()
+                    .checked_sub(len)
+                    .and_then(|added| buf.text.get(start..start + added))
+                    .filter(|_| buf.cursor >= start)
+                    .map(str::to_owned);
+                self.last_change = Some(Change { keys, inserted });
+            }
+        }
+        self.mode = Mode::Normal;
+        if buf.cursor > line_start(&buf.text, buf.cursor) {
+            buf.cursor = prev_char(&buf.text, buf.cursor);
+        }
+    }
+
+    fn repeat(&mut self, count: Option<usize>, buf: &mut Buffer<'_>) {
+        let Some(change) = self.last_change.clone() else {
+            return;
+        };
+        // A new count replaces the count of the change.
+        let keys = match count {
+            Some(count) => {
+                let split = match change.keys.starts_with('"') {
+                    true => change.keys.char_indices().nth(2).map_or(0, |(idx, _)| idx),
+                    false => 0,
+                };
+                let (register, rest) = change.keys.split_at(split);
+                let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+                format!("{register}{count}{rest}")
+            }
+            None => change.keys.clone(),
+        };
+
+        self.replaying = true;
+        self.pending.clear();
+        for c in keys.chars() {
+            self.pending.push(c);
+            match parse(&self.pending, false) {
+                Parse::Incomplete => continue,
+                Parse::Invalid => break,
+                Parse::Done(command) => {
+                    let keys = std::mem::take(&mut self.pending);
+                    self.run(command, &keys, buf);
+                }
+            }
+        }
+        self.pending.clear();
+        if self.mode == Mode::Insert {
+            if let Some(inserted) = &change.inserted {
+                buf.text.insert_str(buf.cursor, inserted);
+                buf.cursor += inserted.len();
+            }
+            self.leave_insert(buf);
+        }
+        self.replaying = false;
+    }
+
+    /// Where `motion` moves the cursor to.
+    fn motion(
+        &mut self,
+        motion: Motion,
+        count: Option<usize>,
+        text: &str,
+        cursor: usize,
+    ) -> Option<(usize, MotionKind)> {
+        let n = count.unwrap_or(1);
+        let repeat = |step: &dyn Fn(usize) -> usize| {
+            let mut pos = cursor;
+            for _ in 0..n {
+                let next = step(pos);
+                if next == pos {
+                    break;
+                }
+                pos = next;
+            }
+            pos
+        };
+        Some(match motion {
+            Motion::Left => {
+                let start = line_start(text, cursor);
+                let pos = repeat(&|pos| match pos > start {
+                    true => prev_char(text, pos),
+                    false => pos,
+                });
+                (pos, MotionKind::Exclusive)
+            }
+            Motion::Right => {
+                let end = line_end(text, cursor);
+                (
+                    repeat(&|pos| next_char(text, pos).min(end)),
+                    MotionKind::Exclusive,
+                )
+            }
+            Motion::Up | Motion::Down | Motion::NextLine | Motion::PrevLine => {
+                let down = matches!(motion, Motion::Down | Motion::NextLine);
+                let line = repeat(&|pos| {
+                    let start = line_start(text, pos);
+                    match down {
+                        true => (line_end(text, pos) + 1).min(text.len()).max(start),
+                        false if start == 0 => pos,
+                        false => line_start(text, start - 1),
+                    }
+                });
+                let line = line_start(text, line);
+                if line == line_start(text, cursor) {
+                    return None;
+                }
+                let pos = match motion {
+                    Motion::Up | Motion::Down => {
+                        let column = *self.column.get_or_insert_with(|| column(text, cursor));
+                        at_column(text, line, column)
+                    }
+                    _ => first_non_blank(text, line),
+                };
+                (pos, MotionKind::Linewise)
+            }
+            Motion::WordStart(big) => (
+                repeat(&|pos| word_start(text, pos, big)),
+                MotionKind::Exclusive,
+            ),
+            Motion::WordEnd(big) => (
+                repeat(&|pos| word_end(text, pos, big, false)),
+                MotionKind::Inclusive,
+            ),
+            Motion::WordBack(big) => (
+                repeat(&|pos| word_back(text, pos, big)),
+                MotionKind::Exclusive,
+            ),
+            Motion::LineStart => (line_start(text, cursor), MotionKind::Exclusive),
+            Motion::FirstNonBlank => (
+                first_non_blank(text, line_start(text, cursor)),
+                MotionKind::Exclusive,
+            ),
+            Motion::LineEnd => {
+                let mut pos = cursor;
+                for _ in 1..n {
+                    match line_end(text, pos) {
+                        end if end == text.len() => break,
+                        end => pos = end + 1,
+                    }
+                }
+                self.column = Some(usize::MAX);
+                (line_end(text, pos), MotionKind::Exclusive)
+            }
+            Motion::FirstLine | Motion::LastLine => {
+                let line = match (count, motion) {
+                    (Some(count), _) => count - 1,
+                    (None, Motion::FirstLine) => 0,
+                    (None, _) => usize::MAX,
+                };
+                let start = match line {
+                    0 => 0,
+                    line => text
+                        .match_indices('\n')
+                        .nth(line - 1)
+                        .map_or_else(|| line_start(text, text.len()), |(idx, _)| idx + 1),
+                };
+                (first_non_blank(text, start), MotionKind::Linewise)
+            }
+            Motion::Find(find) => {
+                self.last_find = Some(find);
+                find_in_line(text, cursor, find, n)?
+            }
+            Motion::RepeatFind(reverse) => {
+                let mut find = self.last_find?;
+                if reverse {
+                    find.kind = match find.kind {
+                        'f' => 'F',
+                        'F' => 'f',
+                        't' => 'T',
+                        _ => 't',
+                    };
+                }
+                find_in_line(text, cursor, find, n)?
+            }
+            Motion::MatchPair => (match_pair(text, cursor)?, MotionKind::Inclusive),
+            Motion::ParagraphForward => (
+                repeat(&|pos| paragraph(text, pos, true)),
+                MotionKind::Exclusive,
+            ),
+            Motion::ParagraphBack => (
+                repeat(&|pos| paragraph(text, pos, false)),
+                MotionKind::Exclusive,
+            ),
+        })
+    }
+
+    /// The range an operator works on, and whether it consists of whole lines.
+    fn target(
+        &mut self,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+        buf: &Buffer<'_>,
+    ) -> Option<(Range<usize>, bool)> {
+        let text = &buf.text;
+        let cursor = buf.cursor;
+        let (motion, count) = match target {
+            Target::Lines(count2) => {
+                let mut end = cursor;
+                for _ in 1..multiply(count, count2).unwrap_or(1) {
+                    match line_end(text, end) {
+                        end if end == text.len() => break,
+                        line_end => end = line_end + 1,
+                    }
+                }
+                return Some((lines(text, cursor, end), true));
+            }
+            Target::Object(object) => return Some((object_range(text, cursor, object)?, false)),
+            Target::Motion(motion, count2) => (motion, multiply(count, count2)),
+        };
+
+        // `cw` on a word changes to the end of the word, like `ce`, but stays on its last letter.
+        let (pos, kind) = match motion {
+            Motion::WordStart(big)
+                if operator == Operator::Change
+                    && char_at(text, cursor).is_some_and(|c| !c.is_whitespace()) =>
+            {
+                let mut pos = word_end(text, cursor, big, true);
+                for _ in 1..count.unwrap_or(1) {
+                    pos = word_end(text, pos, big, false);
+                }
+                (pos, MotionKind::Inclusive)
+            }
+            motion => self.motion(motion, count, text, cursor)?,
+        };
+        let (start, end) = (cursor.min(pos), cursor.max(pos));
+        Some(match kind {
+            MotionKind::Linewise => (lines(text, start, end), true),
+            MotionKind::Inclusive => (start..next_char(text, end), false),
+            // An exclusive motion that ends at the start of a later line stops at the end of the
+            // line before, so `dw` on the last word of a line keeps the line break.
+            MotionKind::Exclusive
+                if end > start
+                    && end == line_start(text, end)
+                    && line_start(text, start) < line_start(text, end) =>
+            {
+                (start..end - 1, false)
+            }
+            MotionKind::Exclusive => (start..end, false),
+        })
+    }
+
+    fn operate(
+        &mut self,
+        operator: Operator,
+        range: Range<usize>,
+        linewise: bool,
+        register: Option<char>,
+        keys: Option<&str>,
+        buf: &mut Buffer<'_>,
+    ) {
+        let mut yanked = buf.text[range.clone()].to_owned();
+        if linewise && !yanked.ends_with('\n') {
+            yanked.push('\n');
+        }
+        match operator {
+            Operator::Yank => {
+                buf.registers.store(register, yanked, linewise, true);
+                if !linewise || buf.cursor >= range.end {
+                    buf.cursor = range.start;
+                }
+                return;
+            }
+            Operator::Delete => {
+                buf.registers.store(register, yanked, linewise, false);
+                // Deleting the last lines also deletes the line break before them.
+                let range = match linewise && !buf.text[range.clone()].ends_with('\n') {
+                    true => range.start.saturating_sub(1)..range.end,
+                    false => range,
+                };
+                buf.text.replace_range(range.clone(), "");
+                buf.cursor = match linewise {
+                    true => first_non_blank(&buf.text, line_start(&buf.text, range.start)),
+                    false => range.start,
+                };
+            }
+            Operator::Change => {
+                buf.registers.store(register, yanked, linewise, false);
+                // Changed lines keep their indentation.
+                let replacement = match linewise {
+                    true => {
+                        let indent = indentation(&buf.text, range.start);
+                        match buf.text[range.clone()].ends_with('\n') {
+                            true => format!("{indent}\n"),
+                            false => indent.to_owned(),
+                        }
+                    }
+                    false => String::new(),
+                };
+                let cursor = range.start + replacement.trim_end_matches('\n').len();
+                buf.text.replace_range(range, &replacement);
+                buf.cursor = cursor;
+                self.enter_insert(keys, buf);
+                return;
+            }
+            Operator::Indent | Operator::Outdent => {
+                let last = match range.is_empty() {
+                    true => range.start,
+                    false => prev_char(&buf.text, range.end),
+                };
+                let lines = line_start(&buf.text, range.start)..line_end(&buf.text, last);
+                let width = buf.tab_width;
+                let shifted = buf.text[lines.clone()]
+                    .split('\n')
+                    .map(|line| match operator {
+                        _ if line.trim().is_empty() => line.to_owned(),
+                        Operator::Indent => format!("{:width$}{line}", ""),
+                        _ if line.starts_with('\t') => line[1..].to_owned(),
+                        _ => {
+                            let spaces = line.len() - line.trim_start_matches(' ').len();
+                            line[spaces.min(width)..].to_owned()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                buf.text.replace_range(lines.clone(), &shifted);
+                buf.cursor = first_non_blank(&buf.text, lines.start);
+            }
+            Operator::Lower | Operator::Upper | Operator::ToggleCase => {
+                let mapped = map_case(&buf.text[range.clone()], operator);
+                buf.text.replace_range(range.clone(), &mapped);
+                buf.cursor = range.start;
+            }
+        }
+        if let Some(keys) = keys {
+            self.record(keys);
+        }
+    }
+
+    /// Replaces the visual selection with a register, and keeps the selection in the register.
+    fn paste_visual(&mut self, line: bool, register: Option<char>, buf: &mut Buffer<'_>) {
+        self.mode = Mode::Normal;
+        let Some(pasted) = buf.registers.get(register).cloned() else {
+            return;
+        };
+        let range = visual_range(&buf.text, self.anchor, buf.cursor, line);
+        let replaced = buf.text[range.clone()].to_owned();
+        let mut insert = pasted.text;
+        match (line, pasted.linewise) {
+            (true, false) if replaced.ends_with('\n') => insert.push('\n'),
+            (false, true) => insert.insert(0, '\n'),
+            _ => {}
+        }
+        buf.text.replace_range(range.clone(), &insert);
+        buf.registers.store(None, replaced, line, false);
+        buf.cursor = range.start;
+    }
+}
+
+/// Parses the keys typed in normal or visual mode:
+/// `["register][count]command`, where an operator is followed by `[count]motion`.
+fn parse(keys: &str, visual: bool) -> Parse {
+    let mut chars = keys.chars().peekable();
+    let mut register = None;
+    if chars.next_if_eq(&'"').is_some() {
+        match chars.next() {
+            Some(name) => register = Some(name),
+            None => return Parse::Incomplete,
+        }
+    }
+    let count = parse_count(&mut chars);
+    let Some(c) = chars.next() else {
+        return Parse::Incomplete;
+    };
+    let kind = match parse_command(c, &mut chars, visual) {
+        Ok(kind) => kind,
+        Err(parse) => return parse,
+    };
+    match chars.next() {
+        Some(_) => Parse::Invalid,
+        None => Parse::Done(Command {
+            register,
+            count,
+            kind,
+        }),
+    }
+}
+
+fn parse_count(chars: &mut Peekable<Chars<'_>>) -> Option<usize> {
+    let mut count = None;
+    // A leading 0 is the motion to the start of the line.
+    while let Some(digit) = chars
+        .peek()
+        .and_then(|c| c.to_digit(10))
+        .filter(|&digit| digit > 0 || count.is_some())
+    {
+        chars.next();
+        let count = count.get_or_insert(0);
+        *count = (*count * 10 + digit as usize).min(MAX_COUNT);
+    }
+    count
+}
+
+fn parse_command(c: char, chars: &mut Peekable<Chars<'_>>, visual: bool) -> Result<Kind, Parse> {
+    let operator = match c {
+        'd' => Some(Operator::Delete),
+        'c' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        '>' => Some(Operator::Indent),
+        '<' => Some(Operator::Outdent),
+        'g' => match chars.next() {
+            Some('u') => Some(Operator::Lower),
+            Some('U') => Some(Operator::Upper),
+            Some('~') => Some(Operator::ToggleCase),
+            Some('g') => return Ok(Kind::Move(Motion::FirstLine)),
+            Some(_) => return Err(Parse::Invalid),
+            None => return Err(Parse::Incomplete),
+        },
+        _ => None,
+    };
+    if let Some(operator) = operator {
+        if visual {
+            return Ok(Kind::Visual(operator));
+        }
+        let count = parse_count(chars);
+        let doubled = match operator {
+            Operator::Lower => 'u',
+            Operator::Upper => 'U',
+            Operator::ToggleCase => '~',
+            _ => c,
+        };
+        return match chars.next() {
+            None => Err(Parse::Incomplete),
+            Some(next) if next == doubled => Ok(Kind::Operate(operator, Target::Lines(count))),
+            Some(next @ ('i' | 'a')) => parse_object(next == 'a', chars)
+                .map(|object| Kind::Operate(operator, Target::Object(object))),
+            Some(next) => parse_motion(next, chars)
+                .map(|motion| Kind::Operate(operator, Target::Motion(motion, count))),
+        };
+    }
+
+    let kind = match (c, visual) {
+        ('i' | 'a', true) => return parse_object(c == 'a', chars).map(Kind::Select),
+        ('x', true) => Kind::Visual(Operator::Delete),
+        ('s', true) => Kind::Visual(Operator::Change),
+        ('u', true) => Kind::Visual(Operator::Lower),
+        ('U', true) => Kind::Visual(Operator::Upper),
+        ('~', true) => Kind::Visual(Operator::ToggleCase),
+        ('o', true) => Kind::SwapAnchor,
+        ('p' | 'P', true) => Kind::Paste { before: false },
+        ('x', false) => Kind::Operate(Operator::Delete, Target::Motion(Motion::Right, None)),
+        ('X', false) => Kind::Operate(Operator::Delete, Target::Motion(Motion::Left, None)),
+        ('D', false) => Kind::Operate(Operator::Delete, Target::Motion(Motion::LineEnd, None)),
+        ('C', false) => Kind::Operate(Operator::Change, Target::Motion(Motion::LineEnd, None)),
+        ('s', false) => Kind::Operate(Operator::Change, Target::Motion(Motion::Right, None)),
+        ('S', false) => Kind::Operate(Operator::Change, Target::Lines(None)),
+        ('Y', false) => Kind::Operate(Operator::Yank, Target::Lines(None)),
+        ('i' | 'a' | 'I' | 'A' | 'o' | 'O', false) => Kind::Insert(c),
+        ('p', false) => Kind::Paste { before: false },
+        ('P', false) => Kind::Paste { before: true },
+        ('r', false) => match chars.next() {
+            Some(c) => Kind::Replace(c),
+            None => return Err(Parse::Incomplete),
+        },
+        ('~', false) => Kind::ToggleCase,
+        ('u', false) => Kind::Undo,
+        ('.', false) => Kind::Repeat,
+        ('J', _) => Kind::Join,
+        ('v', _) => Kind::StartVisual { line: false },
+        ('V', _) => Kind::StartVisual { line: true },
+        _ => return parse_motion(c, chars).map(Kind::Move),
+    };
+    Ok(kind)
+}
+
+fn parse_motion(c: char, chars: &mut Peekable<Chars<'_>>) -> Result<Motion, Parse> {
+    Ok(match c {
+        'h' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'j' => Motion::Down,
+        'k' => Motion::Up,
+        '+' => Motion::NextLine,
+        '-' => Motion::PrevLine,
+        'w' | 'W' => Motion::WordStart(c == 'W'),
+        'e' | 'E' => Motion::WordEnd(c == 'E'),
+        'b' | 'B' => Motion::WordBack(c == 'B'),
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        'G' => Motion::LastLine,
+        'g' => match chars.next() {
+            Some('g') => Motion::FirstLine,
+            Some(_) => return Err(Parse::Invalid),
+            None => return Err(Parse::Incomplete),
+        },
+        'f' | 'F' | 't' | 'T' => match chars.next() {
+            Some(target) => Motion::Find(Find { kind: c, target }),
+            None => return Err(Parse::Incomplete),
+        },
+        ';' | ',' => Motion::RepeatFind(c == ','),
+        '%' => Motion::MatchPair,
+        '}' => Motion::ParagraphForward,
+        '{' => Motion::ParagraphBack,
+        _ => return Err(Parse::Invalid),
+    })
+}
+
+fn parse_object(around: bool, chars: &mut Peekable<Chars<'_>>) -> Result<Object, Parse> {
+    let kind = match chars.next() {
+        Some(kind @ ('w' | 'W' | '"' | '\'' | '`')) => kind,
+        Some('(' | ')' | 'b') => '(',
+        Some('[' | ']') => '[',
+        Some('{' | '}' | 'B') => '{',
+        Some('<' | '>') => '<',
+        Some(_) => return Err(Parse::Invalid),
+        None => return Err(Parse::Incomplete),
+    };
+    Ok(Object { kind, around })
+}
+
+/// The key as it is written in a command. Special keys are aliases of other commands.
+fn vim_char(key: &Key) -> Option<char> {
+    if let Some(c) = key.char() {
+        return Some(c);
+    }
+    if key.ctrl || key.alt {
+        return None;
+    }
+    Some(match key.name.as_str() {
+        "ArrowLeft" | "Backspace" => 'h',
+        "ArrowRight" => 'l',
+        "ArrowUp" => 'k',
+        "ArrowDown" => 'j',
+        "Home" => '0',
+        "End" => '$',
+        "Enter" => '+',
+        "Delete" => 'x',
+        _ => return None,
+    })
+}
+
+fn multiply(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some((a.unwrap_or(1) * b.unwrap_or(1)).min(MAX_COUNT)),
+    }
+}
+
+/// In normal mode, the cursor is on a character, not behind the end of the line.
+fn clamp(text: &str, pos: usize) -> usize {
+    let pos = pos.min(text.len());
+    match pos == line_end(text, pos) && pos > line_start(text, pos) {
+        true => prev_char(text, pos),
+        false => pos,
+    }
+}
+
+fn visual_range(text: &str, anchor: usize, cursor: usize, line: bool) -> Range<usize> {
+    let (start, end) = (anchor.min(cursor), anchor.max(cursor));
+    match line {
+        true => lines(text, start, end),
+        false => start..next_char(text, end),
+    }
+}
+
+/// The whole lines from the one containing `start` to the one containing `end`.
+fn lines(text: &str, start: usize, end: usize) -> Range<usize> {
+    line_start(text, start)..(line_end(text, end) + 1).min(text.len())
+}
+
+fn indentation(text: &str, pos: usize) -> &str {
+    let start = line_start(text, pos);
+    let line = &text[start..line_end(text, pos)];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+fn first_non_blank(text: &str, start: usize) -> usize {
+    start + indentation(text, start).len()
+}
+
+fn word_start(text: &str, pos: usize, big: bool) -> usize {
+    let Some(c) = char_at(text, pos) else {
+        return pos;
+    };
+    let class = char_class(c, big);
+    let mut pos = pos;
+    if class != 0 {
+        while let Some(c) = char_at(text, pos).filter(|&c| char_class(c, big) == class) {
+            pos += c.len_utf8();
+        }
+    }
+    // An empty line counts as a word.
+    while let Some(c) = char_at(text, pos).filter(|c| c.is_whitespace()) {
+        pos += c.len_utf8();
+        if c == '\n' && char_at(text, pos) == Some('\n') {
+            break;
+        }
+    }
+    pos
+}
+
+/// The last character of the next word, or of the current one if `stay`.
+fn word_end(text: &str, pos: usize, big: bool, stay: bool) -> usize {
+    let mut pos = match stay {
+        true => pos,
+        false => next_char(text, pos),
+    };
+    while let Some(c) = char_at(text, pos).filter(|c| c.is_whitespace()) {
+        pos += c.len_utf8();
+    }
+    let Some(c) = char_at(text, pos) else {
+        return prev_char(text, text.len());
+    };
+    let class = char_class(c, big);
+    loop {
+        let next = next_char(text, pos);
+        match char_at(text, next) {
+            Some(c) if char_class(c, big) == class => pos = next,
+            _ => return pos,
+        }
+    }
+}
+
+fn word_back(text: &str, pos: usize, big: bool) -> usize {
+    let mut pos = prev_char(text, pos);
+    while pos > 0 && char_at(text, pos).is_some_and(char::is_whitespace) {
+        pos = prev_char(text, pos);
+    }
+    let Some(class) = char_at(text, pos).map(|c| char_class(c, big)) else {
+        return pos;
+    };
+    while pos > 0 && char_at(text, prev_char(text, pos)).map(|c| char_class(c, big)) == Some(class)
+    {
+        pos = prev_char(text, pos);
+    }
+    pos
+}
+
+fn find_in_line(text: &str, cursor: usize, find: Find, n: usize) -> Option<(usize, MotionKind)> {
+    let Find { kind, target } = find;
+    if matches!(kind, 'f' | 't') {
+        let start = next_char(text, cursor);
+        let end = line_end(text, cursor).max(start);
+        let found = text[start..end]
+            .match_indices(target)
+            .nth(n - 1)
+            .map(|(idx, _)| start + idx)?;
+        let pos = match kind {
+            't' => prev_char(text, found),
+            _ => found,
+        };
+        Some((pos, MotionKind::Inclusive))
+    } else {
+        let start = line_start(text, cursor);
+        let found = text[start..cursor]
+            .rmatch_indices(target)
+            .nth(n - 1)
+            .map(|(idx, _)| start + idx)?;
+        let pos = match kind {
+            'T' => next_char(text, found),
+            _ => found,
+        };
+        Some((pos, MotionKind::Exclusive))
+    }
+}
+
+/// The bracket matching the first bracket at or after the cursor in its line.
+fn match_pair(text: &str, cursor: usize) -> Option<usize> {
+    let (pos, c) = text[cursor..line_end(text, cursor)]
+        .char_indices()
+        .find(|&(_, c)| "()[]{}".contains(c))
+        .map(|(idx, c)| (cursor + idx, c))?;
+    let (open, close) = match c {
+        '(' | ')' => ('(', ')'),
+        '[' | ']' => ('[', ']'),
+        _ => ('{', '}'),
+    };
+    let mut depth = 0usize;
+    if c == open {
+        for (idx, c) in text[pos..].char_indices() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(pos + idx);
+                }
+            }
+        }
+    } else {
+        for (idx, c) in text[..=pos].char_indices().rev() {
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The next or previous blank line after a paragraph.
+fn paragraph(text: &str, pos: usize, forward: bool) -> usize {
+    let blank = |start: usize| text[start..line_end(text, start)].trim().is_empty();
+    let mut pos = line_start(text, pos);
+    let step = |pos: usize| match forward {
+        true => match line_end(text, pos) {
+            end if end == text.len() => None,
+            end => Some(end + 1),
+        },
+        false => pos.checked_sub(1).map(|pos| line_start(text, pos)),
+    };
+    while blank(pos) {
+        match step(pos) {
+            Some(next) => pos = next,
+            None => return pos,
+        }
+    }
+    while !blank(pos) {
+        match step(pos) {
+            Some(next) => pos = next,
+            None if forward => return text.len(),
+            None => return 0,
+        }
+    }
+    pos
+}
+
+fn object_range(text: &str, cursor: usize, object: Object) -> Option<Range<usize>> {
+    let Object { kind, around } = object;
+    match kind {
+        'w' | 'W' => {
+            let big = kind == 'W';
+            let c = char_at(text, cursor).filter(|&c| c != '\n')?;
+            let class = char_class(c, big);
+            let same = |c: char| c != '\n' && char_class(c, big) == class;
+            let mut start = cursor;
+            while let Some(c) = text[..start].chars().next_back().filter(|&c| same(c)) {
+                start -= c.len_utf8();
+            }
+            let mut end = next_char(text, cursor);
+            while let Some(c) = char_at(text, end).filter(|&c| same(c)) {
+                end += c.len_utf8();
+            }
+            if around {
+                let blank = |c: char| c == ' ' || c == '\t';
+                if class == 0 {
+                    // Blanks and the word after them
+                    let next = char_at(text, end).filter(|&c| c != '\n');
+                    if let Some(class) = next.map(|c| char_class(c, big)) {
+                        while let Some(c) =
+                            char_at(text, end).filter(|&c| char_class(c, big) == class)
+                        {
+                            end += c.len_utf8();
+                        }
+                    }
+                } else if char_at(text, end).is_some_and(blank) {
+                    while let Some(c) = char_at(text, end).filter(|&c| blank(c)) {
+                        end += c.len_utf8();
+                    }
+                } else {
+                    while let Some(c) = text[..start].chars().next_back().filter(|&c| blank(c)) {
+                        start -= c.len_utf8();
+                    }
+                }
+            }
+            Some(start..end)
+        }
+        '"' | '\'' | '`' => {
+            let start = line_start(text, cursor);
+            let line = &text[start..line_end(text, cursor)];
+            let mut escaped = false;
+            let quotes = line
+                .char_indices()
+                .filter(|&(_, c)| {
+                    let quote = c == kind && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    quote
+                })
+                .map(|(idx, _)| start + idx)
+                .collect::<Vec<_>>();
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|&(_, close)| close >= cursor)?;
+            Some(match around {
+                true => open..close + 1,
+                false => open + 1..close,
+            })
+        }
+        _ => {
+            let (open, close) = match kind {
+                '(' => ('(', ')'),
+                '[' => ('[', ']'),
+                '{' => ('{', '}'),
+                _ => ('<', '>'),
+            };
+            let mut depth = 0usize;
+            let mut start = None;
+            for (idx, c) in text[..next_char(text, cursor)].char_indices().rev() {
+                if c == close && idx != cursor {
+                    depth += 1;
+                } else if c == open {
+                    if depth == 0 {
+                        start = Some(idx);
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+            let start = start?;
+            let mut depth = 0usize;
+            let end = text[start + 1..].char_indices().find_map(|(idx, c)| {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        return Some(start + 1 + idx);
+                    }
+                    depth -= 1;
+                }
+                None
+            })?;
+            if around {
+                return Some(start..end + 1);
+            }
+            // A block keeps the line breaks after the opening and before the closing bracket.
+            let mut inner = start + 1..end;
+            if text[inner.clone()].starts_with('\n') {
+                inner.start += 1;
+                let last_line = line_start(text, end);
+                if last_line >= inner.start && text[last_line..end].trim().is_empty() {
+                    inner.end = last_line;
+                }
+            }
+            Some(inner)
+        }
+    }
+}
+
+fn insert_at(at: char, buf: &mut Buffer<'_>) {
+    let text = &buf.text;
+    let cursor = buf.cursor;
+    match at {
+        'a' if cursor < line_end(text, cursor) => buf.cursor = next_char(text, cursor),
+        'I' => buf.cursor = first_non_blank(text, line_start(text, cursor)),
+        'A' => buf.cursor = line_end(text, cursor),
+        'o' => {
+            let end = line_end(text, cursor);
+            let insert = format!("\n{}", indentation(text, cursor));
+            buf.text.insert_str(end, &insert);
+            buf.cursor = end + insert.len();
+        }
+        'O' => {
+            let start = line_start(text, cursor);
+            let indent = indentation(text, cursor).to_owned();
+            buf.text.insert_str(start, &format!("{indent}\n"));
+            buf.cursor = start + indent.len();
+        }
+        _ => {}
+    }
+}
+
+fn paste(before: bool, register: Option<char>, count: Option<usize>, buf: &mut Buffer<'_>) {
+    let Some(pasted) = buf.registers.get(register) else {
+        return;
+    };
+    let insert = pasted.text.repeat(count.unwrap_or(1));
+    let text = &buf.text;
+    let cursor = buf.cursor;
+    if pasted.linewise {
+        let end = line_end(text, cursor);
+        let (pos, insert, first_line) = match before {
+            true => (line_start(text, cursor), insert, line_start(text, cursor)),
+            false if end < text.len() => (end + 1, insert, end + 1),
+            // The last line has no line break to paste after.
+            false => {
+                let insert = insert.strip_suffix('\n').unwrap_or(&insert);
+                (end, format!("\n{insert}"), end + 1)
+            }
+        };
+        buf.text.insert_str(pos, &insert);
+        buf.cursor = first_non_blank(&buf.text, first_line);
+    } else if !insert.is_empty() {
+        let pos = match before || cursor == line_end(text, cursor) {
+            true => cursor,
+            false => next_char(text, cursor),
+        };
+        buf.text.insert_str(pos, &insert);
+        buf.cursor = prev_char(&buf.text, pos + insert.len());
+    }
+}
+
+fn replace(c: char, count: Option<usize>, buf: &mut Buffer<'_>) -> Option<()> {
+    let n = count.unwrap_or(1);
+    let cursor = buf.cursor;
+    let line = &buf.text[cursor..line_end(&buf.text, cursor)];
+    let end = match line.char_indices().nth(n) {
+        Some((idx, _)) => cursor + idx,
+        None if line.chars().count() == n => cursor + line.len(),
+        None => return None,
+    };
+    buf.text
+        .replace_range(cursor..end, &c.to_string().repeat(n));
+    buf.cursor = cursor + c.len_utf8() * (n - 1);
+    Some(())
+}
+
+/// Joins `lines` lines, starting with the one containing `pos`. Returns where the last join is.
+fn join(text: &mut String, pos: usize, lines: usize) -> usize {
+    let mut pos = pos;
+    for _ in 1..lines {
+        let end = line_end(text, pos);
+        if end == text.len() {
+            break;
+        }
+        let next = &text[end + 1..line_end(text, end + 1)];
+        let blanks = next.len() - next.trim_start_matches([' ', '\t']).len();
+        let separator = match next[blanks..].is_empty()
+            || next[blanks..].starts_with(')')
+            || text[..end].ends_with([' ', '\t', '\n'])
+            || end == 0
+        {
+            true => "",
+            false => " ",
+        };
+        text.replace_range(end..end + 1 + blanks, separator);
+        pos = end;
+    }
+    pos
+}
+
+fn map_case(text: &str, operator: Operator) -> String {
+    match operator {
+        Operator::Lower => text.to_lowercase(),
+        Operator::Upper => text.to_uppercase(),
+        _ => text
+            .chars()
+            .flat_map(|c| match c.is_uppercase() {
+                true => c.to_lowercase().collect::<Vec<_>>(),
+                false => c.to_uppercase().collect::<Vec<_>>(),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::history::History;
+
+    /// An editor with Vim keys, where the browser's part is played by the test.
+    struct Editor {
+        vim: Vim,
+        text: String,
+        sel: Range<usize>,
+        registers: Registers,
+        history: History,
+        now: f64,
+    }
+
+    impl Editor {
+        fn new(text: &str, cursor: usize) -> Self {
+            Self {
+                vim: Vim::default(),
+                text: text.to_owned(),
+                sel: cursor..cursor,
+                registers: Registers::default(),
+                history: History::new(Rc::from(text)),
+                now: 0.0,
+            }
+        }
+
+        /// Every character of `keys` is a key press; `<Esc>` is Escape.
+        fn keys(&mut self, keys: &str) -> &mut Self {
+            for (idx, part) in keys.split("<Esc>").enumerate() {
+                if idx > 0 {
+                    self.press(Key {
+                        name: "Escape".to_owned(),
+                        ctrl: false,
+                        alt: false,
+                    });
+                }
+                for c in part.chars() {
+                    self.press(Key {
+                        name: c.to_string(),
+                        ctrl: false,
+                        alt: false,
+                    });
+                }
+            }
+            self
+        }
+
+        fn ctrl(&mut self, name: &str) -> &mut Self {
+            self.press(Key {
+                name: name.to_owned(),
+                ctrl: true,
+                alt: false,
+            })
+        }
+
+        fn press(&mut self, key: Key) -> &mut Self {
+            let action =
+                self.vim
+                    .handle(&key, &self.text, self.sel.clone(), &mut self.registers, 4);
+            match action {
+                Action::Pass => {
+                    self.text.replace_range(self.sel.clone(), &key.name);
+                    let pos = self.sel.start + key.name.len();
+                    self.sel = pos..pos;
+                }
+                Action::Consumed => {}
+                Action::Edit(edit) => {
+                    self.text.replace_range(edit.range, &edit.text);
+                    self.sel = edit.selection;
+                }
+                Action::Undo(undo) => {
+                    let entry = match undo {
+                        true => self.history.undo(),
+                        false => self.history.redo(),
+                    };
+                    if let Some(entry) = entry {
+                        self.text = entry.text.to_string();
+                        let (start, end) = entry.selection;
+                        self.sel = start as usize..end as usize;
+                    }
+                }
+            }
+            // Every key press is a separate step in the history.
+            self.now += 10_000.0;
+            let selection = (self.sel.start as u32, self.sel.end as u32);
+            self.history
+                .record(Rc::from(self.text.as_str()), selection, self.now);
+            self
+        }
+
+        fn state(&self) -> (&str, usize) {
+            (&self.text, self.sel.start)
+        }
+    }
+
+    #[test]
+    fn delete_words() {
+        let mut editor = Editor::new("foo bar baz", 0);
+        assert_eq!(editor.keys("dw").state(), ("bar baz", 0));
+        assert_eq!(editor.registers.get(None).unwrap().text, "foo ");
+        assert_eq!(editor.keys("2dw").state(), ("", 0));
+
+        // The last word of a line doesn't take the line break with it.
+        let mut editor = Editor::new("a b\nc", 2);
+        assert_eq!(editor.keys("dw").state(), ("a \nc", 1));
+
+        let mut editor = Editor::new("a.b c", 0);
+        assert_eq!(editor.keys("dW").state(), ("c", 0));
+        assert_eq!(editor.vim.status(), "NORMAL");
+    }
+
+    #[test]
+    fn counts_and_columns() {
+        let text = "abc\nd\nefg\nhij\nklm";
+        let mut editor = Editor::new(text, 2);
+        assert_eq!(editor.keys("3j").state(), (text, 12));
+        // `j` and `k` keep the column across short lines.
+        assert_eq!(editor.keys("k").state(), (text, 8));
+        assert_eq!(editor.keys("k").state(), (text, 4));
+        assert_eq!(editor.keys("k").state(), (text, 2));
+        // A count beyond the last line stops there.
+        assert_eq!(editor.keys("99j").state(), (text, 16));
+
+        editor.keys("3");
+        assert_eq!(editor.vim.status(), "NORMAL 3");
+        editor.keys("<Esc>");
+        assert_eq!(editor.vim.status(), "NORMAL");
+    }
+
+    #[test]
+    fn change_inside_quotes() {
+        let mut editor = Editor::new(r#"let s = "hello";"#, 10);
+        editor.keys("ci\"");
+        assert_eq!(editor.state(), (r#"let s = "";"#, 9));
+        assert_eq!(editor.vim.status(), "INSERT");
+        assert_eq!(editor.keys("bye<Esc>").state(), (r#"let s = "bye";"#, 11));
+
+        // The typed text is repeated, too.
+        let mut editor = Editor::new(r#"("a", "b")"#, 2);
+        editor.keys("ci\"x<Esc>");
+        assert_eq!(editor.keys("fb.").state(), (r#"("x", "x")"#, 7));
+
+        // No quotes, no change
+        let mut editor = Editor::new("abc", 1);
+        assert_eq!(editor.keys("ci\"").state(), ("abc", 1));
+        assert_eq!(editor.vim.status(), "NORMAL");
+    }
+
+    #[test]
+    fn undo_after_repeat() {
+        let mut editor = Editor::new("a b c d", 0);
+        editor.keys("dw");
+        assert_eq!(editor.keys(".").state(), ("c d", 0));
+        assert_eq!(editor.keys("u").state().0, "b c d");
+        assert_eq!(editor.keys("u").state().0, "a b c d");
+        assert_eq!(editor.ctrl("r").state().0, "b c d");
+
+        // A repeated change with inserted text is undone at once.
+        let mut editor = Editor::new("foo bar", 0);
+        editor.keys("ciwx<Esc>w.");
+        assert_eq!(editor.state().0, "x x");
+        assert_eq!(editor.keys("u").state().0, "x bar");
+
+        // A new count replaces the count of the change.
+        let mut editor = Editor::new("a b c d e f", 0);
+        editor.keys("2dw");
+        assert_eq!(editor.keys("3.").state().0, "f");
+        assert_eq!(editor.keys("u").state().0, "c d e f");
+    }
+}
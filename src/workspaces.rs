@@ -0,0 +1,925 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::wasm_bindgen;
+use web_sys::js_sys::{Date, Function};
+use web_sys::wasm_bindgen::prelude::Closure;
+use web_sys::wasm_bindgen::{JsCast, JsValue};
+use web_sys::{BroadcastChannel, MessageEvent, window};
+use yew::{
+    Callback, FocusEvent, Html, MouseEvent, Properties, UseStateHandle, classes,
+    function_component, html, use_effect_with, use_memo, use_mut_ref, use_state,
+};
+
+use crate::app::{
+    CONFIG_SOURCE, STRUCT_SOURCE, TMPL_SOURCE, WORKSPACE_BASE_KEY, WORKSPACE_KEY, local_storage,
+    session_storage,
+};
+use crate::share::Workspace;
+
+const WORKSPACES: &str = "workspaces";
+const SNAPSHOTS: &str = "snapshots";
+const CHANNEL: &str = "play-rinja-workspaces";
+
+/// A named workspace, stored in IndexedDB.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredWorkspace {
+    /// Assigned by the database when the workspace is stored for the first time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub name: String,
+    /// In milliseconds since the epoch
+    pub modified: f64,
+    pub content: Workspace,
+}
+
+/// What a tab tells the other tabs after it changed the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Notice {
+    workspace: u32,
+    change: Change,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Change {
+    Content,
+    Deleted,
+    /// The name, the snapshots, or a new workspace
+    Other,
+}
+
+/// The content of a workspace at some point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub workspace: u32,
+    /// In milliseconds since the epoch
+    pub created: f64,
+    pub content: Workspace,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct WorkspacesMenuProps {
+    /// What the editors show, which is saved into the open workspace.
+    pub content: Rc<Workspace>,
+    pub onopen: Callback<Workspace>,
+    /// The page was opened with a shared link, which replaced the content of the editors.
+    pub from_link: bool,
+}
+
+/// Lists the workspaces and the snapshots of the open one.
+///
+/// The open workspace is still kept in the session storage, so it can be shown before the database
+/// is loaded. The database gets a copy of the changes once the typing paused, and the other tabs
+/// are told about each copy.
+///
+/// Every tab has its own open workspace. A new tab opens the workspace that was open last, unless
+/// the page was opened with a shared link, whose content becomes a new workspace. If two
+/// tabs edit the same workspace, the tab that did not save first stops saving, and offers to
+/// reload the workspace or to keep its own content as a fork. A reloaded tab does the same if the
+/// workspace was changed since the tab loaded or saved it, see [`set_base()`].
+#[function_component]
+pub fn WorkspacesMenu(props: &WorkspacesMenuProps) -> Html {
+    let workspaces = use_state(|| Option::<Rc<[StoredWorkspace]>>::None);
+    let snapshots = use_state(|| Rc::<[Snapshot]>::from([]));
+    let unavailable = use_state(|| false);
+    let active = use_state(|| {
+        [session_storage(), local_storage()]
+            .into_iter()
+            .flatten()
+            .find_map(|storage| storage.get_item(WORKSPACE_KEY).ok().flatten())
+            .and_then(|id| id.parse::<u32>().ok())
+    });
+    let conflict = use_state(|| Option::<(Change, Rc<str>)>::None);
+    let channel = use_memo((), |_| BroadcastChannel::new(CHANNEL).ok());
+    let pending = use_mut_ref(|| Option::<Pending>::None);
+
+    let state = State {
+        workspaces: workspaces.clone(),
+        snapshots: snapshots.clone(),
+        active: active.clone(),
+        conflict: conflict.clone(),
+        channel,
+        pending,
+    };
+
+    // The database answers after the first render, when a shared link may have replaced the
+    // content already.
+    let latest = use_mut_ref(|| (Rc::clone(&props.content), props.from_link));
+    *latest.borrow_mut() = (Rc::clone(&props.content), props.from_link);
+
+    // The state from before there were workspaces becomes the first one.
+    use_effect_with((), {
+        let state = state.clone();
+        let unavailable = unavailable.clone();
+        let onopen = props.onopen.clone();
+        move |_| {
+            let new_tab = session_storage()
+                .is_some_and(|storage| matches!(storage.get_item(WORKSPACE_KEY), Ok(None)));
+            get_all::<StoredWorkspace>(WORKSPACES, move |list| {
+                let Some(list) = list else {
+                    unavailable.set(true);
+                    return;
+                };
+                let (content, from_link) = latest.borrow().clone();
+                let content = Workspace::clone(&content);
+                let active = *state.active;
+                match start(&list, active, &content, new_tab, from_link, base()) {
+                    Start::Create(name) => {
+                        // The content doesn't belong to the workspace that was open last.
+                        state.take_pending();
+                        state.create(&list, name, content);
+                    }
+                    Start::Restore(workspace) => {
+                        set_base(workspace.modified);
+                        state.show(list, active);
+                        onopen.emit(workspace.content);
+                    }
+                    Start::Conflict(name) => {
+                        state.show(list, active);
+                        state.conflict.set(Some((Change::Content, Rc::from(name))));
+                    }
+                    Start::Save => {
+                        state.show(list.clone(), active);
+                        state.save(&list, active, content);
+                    }
+                }
+            });
+        }
+    });
+
+    // The channel listener outlives this render, so it reads the current state from here.
+    let current = use_mut_ref(|| state.clone());
+    *current.borrow_mut() = state.clone();
+    use_effect_with((), {
+        let channel = Rc::clone(&state.channel);
+        let current = Rc::clone(&current);
+        move |_| {
+            let listener = Closure::<dyn Fn(MessageEvent)>::new(move |ev: MessageEvent| {
+                let Some(notice) = ev
+                    .data()
+                    .as_string()
+                    .and_then(|data| serde_json::from_str::<Notice>(&data).ok())
+                else {
+                    return;
+                };
+                let state = current.borrow().clone();
+                if notice.change != Change::Other && Some(notice.workspace) == *state.active {
+                    let name = state
+                        .workspaces
+                        .as_deref()
+                        .and_then(|list| {
+                            list.iter().find(|workspace| workspace.id == *state.active)
+                        })
+                        .map_or("Untitled", |workspace| &workspace.name);
+                    state.conflict.set(Some((notice.change, Rc::from(name))));
+                }
+                state.refresh();
+            });
+            if let Some(channel) = &*channel {
+                channel.set_onmessage(Some(listener.as_ref().unchecked_ref()));
+            }
+            move || {
+                if let Some(channel) = &*channel {
+                    channel.set_onmessage(None);
+                }
+                drop(listener);
+            }
+        }
+    });
+
+    // The open workspace follows every change, once the typing paused. When another workspace is
+    // opened, `active` is updated before its content arrives, so the content is never saved into
+    // the wrong one.
+    use_effect_with(Rc::clone(&props.content), {
+        let state = state.clone();
+        move |content| {
+            let Some(window) = window() else {
+                return;
+            };
+            let handler = Closure::once(move || {
+                let state = current.borrow().clone();
+                if let Some(list) = state.workspaces.as_deref() {
+                    state.flush(list);
+                }
+            });
+            let Ok(timeout) = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                handler.into_js_value().unchecked_ref(),
+                500,
+            ) else {
+                return;
+            };
+            let pending = Pending {
+                workspace: *state.active,
+                content: Workspace::clone(content),
+                timeout,
+            };
+            if let Some(old) = state.pending.borrow_mut().replace(pending) {
+                window.clear_timeout_with_handle(old.timeout);
+            }
+        }
+    });
+
+    let body = match &*workspaces {
+        _ if *unavailable => html! {
+            <p class="error">{"Workspaces are not available, the browser blocks the database."}</p>
+        },
+        None => html! { <p>{"Loading…"}</p> },
+        Some(list) => {
+            let entries = list
+                .iter()
+                .map(|workspace| {
+                    let is_active = workspace.id == *active;
+                    let onclick = {
+                        let state = state.clone();
+                        let onopen = props.onopen.clone();
+                        let workspace = workspace.clone();
+                        move |_: MouseEvent| {
+                            if workspace.id != *state.active {
+                                state.open(&workspace, &onopen);
+                            }
+                        }
+                    };
+                    html! {
+                        <button
+                            type="button"
+                            class={classes!("workspace", is_active.then_some("active"))}
+                            title={format!("Changed {}", format_time(workspace.modified))}
+                            {onclick}
+                            onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                        >
+                            {&workspace.name}
+                        </button>
+                    }
+                })
+                .collect::<Html>();
+
+            let open = list
+                .iter()
+                .find(|workspace| workspace.id == *active)
+                .cloned();
+            let onnew = {
+                let state = state.clone();
+                let onopen = props.onopen.clone();
+                let list = Rc::clone(list);
+                move |_: MouseEvent| {
+                    let content = default_content();
+                    state.create(&list, "Untitled", content.clone());
+                    onopen.emit(content);
+                }
+            };
+            let onduplicate = open.clone().map(|workspace| {
+                let state = state.clone();
+                let list = Rc::clone(list);
+                move |_: MouseEvent| {
+                    let name = format!("{} (copy)", workspace.name);
+                    state.create(&list, &name, workspace.content.clone());
+                }
+            });
+            let onrename = open.clone().map(|workspace| {
+                let state = state.clone();
+                let list = Rc::clone(list);
+                move |_: MouseEvent| {
+                    let name = window().and_then(|window| {
+                        window
+                            .prompt_with_message_and_default(
+                                "Name of the workspace:",
+                                &workspace.name,
+                            )
+                            .ok()
+                            .flatten()
+                    });
+                    let Some(name) = name.map(|name| name.trim().to_owned()) else {
+                        return;
+                    };
+                    if !name.is_empty() {
+                        state.replace(
+                            &list,
+                            StoredWorkspace {
+                                name,
+                                ..workspace.clone()
+                            },
+                            Change::Other,
+                        );
+                    }
+                }
+            });
+            let ondelete = open.clone().map(|workspace| {
+                let state = state.clone();
+                let onopen = props.onopen.clone();
+                let list = Rc::clone(list);
+                move |_: MouseEvent| {
+                    let message = format!(
+                        "Delete the workspace {:?} and all of its snapshots?",
+                        workspace.name,
+                    );
+                    if confirm(&message) {
+                        state.delete(&list, &workspace, &onopen);
+                    }
+                }
+            });
+            let onsnapshot = open.map(|workspace| {
+                let state = state.clone();
+                let content = Workspace::clone(&props.content);
+                move |_: MouseEvent| {
+                    if let Some(id) = workspace.id {
+                        state.snapshot(id, content.clone());
+                    }
+                }
+            });
+
+            let snapshot_entries = snapshots
+                .iter()
+                .map(|snapshot| {
+                    let onrestore = {
+                        let onopen = props.onopen.clone();
+                        let content = snapshot.content.clone();
+                        move |_: MouseEvent| onopen.emit(content.clone())
+                    };
+                    let ondelete = {
+                        let state = state.clone();
+                        let (workspace, id) = (snapshot.workspace, snapshot.id);
+                        move |_: MouseEvent| {
+                            if let Some(id) = id {
+                                state.delete_snapshot(workspace, id);
+                            }
+                        }
+                    };
+                    html! {
+                        <div class="snapshot">
+                            <button
+                                type="button"
+                                title="Restore this snapshot"
+                                onclick={onrestore}
+                                onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                            >
+                                {format_time(snapshot.created)}
+                            </button>
+                            <button
+                                type="button"
+                                title="Delete this snapshot"
+                                onclick={ondelete}
+                                onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                            >
+                                {"×"}
+                            </button>
+                        </div>
+                    }
+                })
+                .collect::<Html>();
+
+            html! {
+                <>
+                    {entries}
+                    <div class="workspace-buttons">
+                        <button
+                            type="button"
+                            onclick={onnew}
+                            onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                        >
+                            {"New"}
+                        </button>
+                        <button
+                            type="button"
+                            onclick={onrename}
+                            onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                        >
+                            {"Rename…"}
+                        </button>
+                        <button
+                            type="button"
+                            onclick={onduplicate}
+                            onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                        >
+                            {"Duplicate"}
+                        </button>
+                        <button
+                            type="button"
+                            onclick={ondelete}
+                            onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                        >
+                            {"Delete…"}
+                        </button>
+                    </div>
+                    <hr />
+                    <button
+                        type="button"
+                        onclick={onsnapshot}
+                        onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                    >
+                        {"Take snapshot"}
+                    </button>
+                    if snapshots.is_empty() {
+                        <p class="hint">{"No snapshots of this workspace yet."}</p>
+                    } else {
+                        {snapshot_entries}
+                    }
+                </>
+            }
+        }
+    };
+
+    let banner = conflict.as_ref().map(|(change, name)| {
+        let onreload = {
+            let state = state.clone();
+            let onopen = props.onopen.clone();
+            move |_: MouseEvent| state.reload_open(&onopen)
+        };
+        let onfork = {
+            let state = state.clone();
+            let name = format!("{name} (fork)");
+            let content = Workspace::clone(&props.content);
+            move |_: MouseEvent| {
+                if let Some(list) = state.workspaces.as_deref() {
+                    state.create(list, &name, content.clone());
+                }
+            }
+        };
+        html! {
+            <div id="workspace-conflict" role="alert">
+                if *change == Change::Deleted {
+                    {format!("The workspace {name:?} was deleted in another tab.")}
+                } else {
+                    {format!("The workspace {name:?} was changed in another tab.")}
+                    <button type="button" onclick={onreload}>{"Reload"}</button>
+                }
+                <button
+                    type="button"
+                    title="Keep the content of this tab as a new workspace"
+                    onclick={onfork}
+                >
+                    {"Fork"}
+                </button>
+            </div>
+        }
+    });
+
+    html! {
+        <>
+            {banner}
+            <button
+                id="workspaces-menu"
+                type="button"
+                class="dropdown-menu"
+                onclick={|event: MouseEvent| toggle_element(event, "workspaces-menu")}
+                onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+            >
+                {"Workspaces"}
+                <div
+                    tabindex="-1"
+                    onblur={|event: FocusEvent| handle_blur(event, "workspaces-menu")}
+                >
+                    {body}
+                </div>
+            </button>
+        </>
+    }
+}
+
+/// The state of [`WorkspacesMenu`], and the operations on the database that change it.
+#[derive(Clone)]
+struct State {
+    /// Sorted by name
+    workspaces: UseStateHandle<Option<Rc<[StoredWorkspace]>>>,
+    /// Of the open workspace, the newest first
+    snapshots: UseStateHandle<Rc<[Snapshot]>>,
+    active: UseStateHandle<Option<u32>>,
+    /// The open workspace, by name, was changed by another tab, so this tab doesn't save into it
+    /// anymore.
+    conflict: UseStateHandle<Option<(Change, Rc<str>)>>,
+    channel: Rc<Option<BroadcastChannel>>,
+    pending: Rc<RefCell<Option<Pending>>>,
+}
+
+/// Content of the editors that is not saved yet.
+struct Pending {
+    workspace: Option<u32>,
+    content: Workspace,
+    timeout: i32,
+}
+
+impl State {
+    fn set_list(&self, mut list: Vec<StoredWorkspace>) {
+        list.sort_by_cached_key(|workspace| workspace.name.to_lowercase());
+        self.workspaces.set(Some(Rc::from(list)));
+    }
+
+    /// Shows `list`, and the snapshots of the workspace `active`.
+    fn show(&self, list: Vec<StoredWorkspace>, active: Option<u32>) {
+        self.set_list(list);
+        if let Some(id) = active {
+            // The local storage tells new tabs which workspace was open last.
+            for storage in [session_storage(), local_storage()].into_iter().flatten() {
+                // Doesn't matter whether or not it succeeded.
+                let _ = storage.set_item(WORKSPACE_KEY, &id.to_string());
+            }
+        }
+        self.active.set(active);
+        self.load_snapshots(active);
+    }
+
+    fn load_snapshots(&self, active: Option<u32>) {
+        let snapshots = self.snapshots.clone();
+        get_all::<Snapshot>(SNAPSHOTS, move |list| {
+            let mut list = list.unwrap_or_default();
+            list.retain(|snapshot| Some(snapshot.workspace) == active);
+            list.sort_by(|a, b| b.created.total_cmp(&a.created));
+            snapshots.set(Rc::from(list));
+        });
+    }
+
+    /// Shows what another tab changed, without changing which workspace is open.
+    fn refresh(&self) {
+        let state = self.clone();
+        get_all::<StoredWorkspace>(WORKSPACES, move |list| {
+            if let Some(list) = list {
+                state.set_list(list);
+            }
+        });
+        if self.active.is_some() {
+            self.load_snapshots(*self.active);
+        }
+    }
+
+    /// Replaces the content of this tab with the stored content of the open workspace.
+    fn reload_open(&self, onopen: &Callback<Workspace>) {
+        let state = self.clone();
+        let onopen = onopen.clone();
+        get_all::<StoredWorkspace>(WORKSPACES, move |list| {
+            let list = list.unwrap_or_default();
+            let active = *state.active;
+            if let Some(workspace) = list.iter().find(|workspace| workspace.id == active) {
+                state.conflict.set(None);
+                set_base(workspace.modified);
+                onopen.emit(workspace.content.clone());
+                state.show(list, active);
+            }
+        });
+    }
+
+    /// Saves the pending content now, if its workspace is still open, and returns `list` with it.
+    fn flush(&self, list: &[StoredWorkspace]) -> Vec<StoredWorkspace> {
+        self.take_pending()
+            .filter(|pending| pending.workspace == *self.active)
+            .and_then(|pending| self.save(list, pending.workspace, pending.content))
+            .unwrap_or_else(|| list.to_vec())
+    }
+
+    fn take_pending(&self) -> Option<Pending> {
+        let pending = self.pending.borrow_mut().take()?;
+        if let Some(window) = window() {
+            window.clear_timeout_with_handle(pending.timeout);
+        }
+        Some(pending)
+    }
+
+    /// Tells the other tabs that `workspace` changed.
+    fn notify(&self, workspace: u32, change: Change) {
+        let Some(channel) = &*self.channel else {
+            return;
+        };
+        if let Ok(notice) = serde_json::to_string(&Notice { workspace, change }) {
+            let _ = channel.post_message(&JsValue::from_str(&notice));
+        }
+    }
+
+    /// Reloads the list of workspaces, and shows `active`.
+    fn reload(&self, active: Option<u32>) {
+        let state = self.clone();
+        get_all::<StoredWorkspace>(WORKSPACES, move |list| {
+            state.show(list.unwrap_or_default(), active);
+        });
+    }
+
+    fn open(&self, workspace: &StoredWorkspace, onopen: &Callback<Workspace>) {
+        let Some(list) = self.workspaces.as_deref() else {
+            return;
+        };
+        let list = self.flush(list);
+        self.conflict.set(None);
+        set_base(workspace.modified);
+        self.show(list, workspace.id);
+        onopen.emit(workspace.content.clone());
+    }
+
+    /// Stores a new workspace, and shows it as the open one.
+    ///
+    /// No workspace is open until it is stored, so its content isn't saved into the previous one.
+    fn create(&self, list: &[StoredWorkspace], name: &str, content: Workspace) {
+        self.flush(list);
+        self.active.set(None);
+        self.conflict.set(None);
+        let workspace = StoredWorkspace {
+            id: None,
+            name: unused_name(list, name),
+            modified: Date::now(),
+            content,
+        };
+        set_base(workspace.modified);
+        let state = self.clone();
+        put(WORKSPACES, &workspace, move |id| {
+            if let Some(id) = id {
+                state.notify(id, Change::Other);
+            }
+            state.reload(id);
+        });
+    }
+
+    /// Stores `content` in the workspace `id` of `list`, if it changed and no other tab changed
+    /// the workspace in the meantime. Returns the changed list.
+    fn save(
+        &self,
+        list: &[StoredWorkspace],
+        id: Option<u32>,
+        content: Workspace,
+    ) -> Option<Vec<StoredWorkspace>> {
+        if self.conflict.is_some() {
+            return None;
+        }
+        let workspace = list.iter().find(|workspace| workspace.id == id)?;
+        if workspace.content == content {
+            return None;
+        }
+        let modified = Date::now();
+        set_base(modified);
+        let workspace = StoredWorkspace {
+            modified,
+            content,
+            ..workspace.clone()
+        };
+        Some(self.replace(list, workspace, Change::Content))
+    }
+
+    /// Stores `workspace`, and shows it in `list` right away. Returns the changed list.
+    fn replace(
+        &self,
+        list: &[StoredWorkspace],
+        workspace: StoredWorkspace,
+        change: Change,
+    ) -> Vec<StoredWorkspace> {
+        let mut list = list.to_vec();
+        if let Some(old) = list.iter_mut().find(|old| old.id == workspace.id) {
+            *old = workspace.clone();
+        }
+        self.set_list(list.clone());
+        let state = self.clone();
+        put(WORKSPACES, &workspace, move |id| {
+            if let Some(id) = id {
+                state.notify(id, change);
+            }
+        });
+        list
+    }
+
+    /// Deletes the open workspace with its snapshots, and opens another one.
+    fn delete(
+        &self,
+        list: &[StoredWorkspace],
+        workspace: &StoredWorkspace,
+        onopen: &Callback<Workspace>,
+    ) {
+        let Some(id) = workspace.id else {
+            return;
+        };
+        // Saving it would store the deleted workspace again.
+        self.take_pending();
+        get_all::<Snapshot>(SNAPSHOTS, move |snapshots| {
+            for snapshot in snapshots.unwrap_or_default() {
+                if let Some(snapshot_id) = snapshot.id.filter(|_| snapshot.workspace == id) {
+                    delete(SNAPSHOTS, snapshot_id, || ());
+                }
+            }
+        });
+
+        let rest = list
+            .iter()
+            .filter(|other| other.id != Some(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.conflict.set(None);
+        let state = self.clone();
+        let onopen = onopen.clone();
+        delete(WORKSPACES, id, move || {
+            state.notify(id, Change::Deleted);
+            match rest.first().cloned() {
+                Some(next) => {
+                    set_base(next.modified);
+                    state.show(rest, next.id);
+                    onopen.emit(next.content);
+                }
+                None => {
+                    let content = default_content();
+                    state.create(&rest, "Untitled", content.clone());
+                    onopen.emit(content);
+                }
+            }
+        });
+    }
+
+    fn snapshot(&self, workspace: u32, content: Workspace) {
+        let snapshot = Snapshot {
+            id: None,
+            workspace,
+            created: Date::now(),
+            content,
+        };
+        let state = self.clone();
+        put(SNAPSHOTS, &snapshot, move |_| {
+            state.notify(workspace, Change::Other);
+            state.reload(Some(workspace));
+        });
+    }
+
+    fn delete_snapshot(&self, workspace: u32, id: u32) {
+        let state = self.clone();
+        delete(SNAPSHOTS, id, move || {
+            state.notify(workspace, Change::Other);
+            state.reload(Some(workspace));
+        });
+    }
+}
+
+/// What a tab does with its content once the workspaces are loaded.
+#[derive(Debug, PartialEq)]
+enum Start {
+    /// Stores the content as a new workspace of this name.
+    Create(&'static str),
+    /// Shows the stored content of the open workspace.
+    Restore(StoredWorkspace),
+    /// Another tab changed the open workspace, of this name, since this tab's state was saved.
+    Conflict(String),
+    /// Saves the content into the open workspace.
+    Save,
+}
+
+/// `base` is when this tab last loaded or saved the open workspace `active`, see [`set_base()`].
+fn start(
+    list: &[StoredWorkspace],
+    active: Option<u32>,
+    content: &Workspace,
+    new_tab: bool,
+    from_link: bool,
+    base: Option<f64>,
+) -> Start {
+    if from_link {
+        return Start::Create("Shared");
+    }
+    match list.iter().find(|workspace| workspace.id == active) {
+        Some(workspace) if new_tab => Start::Restore(workspace.clone()),
+        Some(workspace)
+            if base.is_some_and(|base| workspace.modified > base)
+                && workspace.content != *content =>
+        {
+            Start::Conflict(workspace.name.clone())
+        }
+        Some(_) => Start::Save,
+        None => Start::Create("Untitled"),
+    }
+}
+
+/// Remembers when the open workspace was last changed as far as this tab knows, i.e. when it was
+/// loaded or saved by this tab.
+fn set_base(modified: f64) {
+    if let Some(storage) = session_storage() {
+        // Doesn't matter whether or not it succeeded.
+        let _ = storage.set_item(WORKSPACE_BASE_KEY, &modified.to_string());
+    }
+}
+
+fn base() -> Option<f64> {
+    let base = session_storage()?.get_item(WORKSPACE_BASE_KEY).ok()??;
+    base.parse().ok()
+}
+
+/// What a new workspace contains. The theme stays as it is.
+fn default_content() -> Workspace {
+    Workspace {
+        rust: Some(Rc::from(STRUCT_SOURCE)),
+        tmpl: Some(Rc::from(TMPL_SOURCE)),
+        config: Some(Rc::from(CONFIG_SOURCE)),
+        ..Workspace::default()
+    }
+}
+
+/// `name`, or `name (2)`, `name (3)` … if a workspace of that name exists already.
+fn unused_name(list: &[StoredWorkspace], name: &str) -> String {
+    let exists = |name: &str| list.iter().any(|workspace| workspace.name == name);
+    if !exists(name) {
+        return name.to_owned();
+    }
+    (2..)
+        .map(|idx| format!("{name} ({idx})"))
+        .find(|name| !exists(name))
+        .unwrap_or_default()
+}
+
+fn format_time(time: f64) -> String {
+    Date::new(&JsValue::from_f64(time))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into()
+}
+
+fn confirm(message: &str) -> bool {
+    window()
+        .and_then(|window| window.confirm_with_message(message).ok())
+        .unwrap_or_default()
+}
+
+/// All records of `store`, or `None` if the database is not available.
+fn get_all<T: DeserializeOwned>(store: &str, callback: impl FnOnce(Option<Vec<T>>) + 'static) {
+    let callback = Closure::once(move |records: Option<String>| {
+        callback(records.and_then(|records| serde_json::from_str(&records).ok()));
+    });
+    database_get_all(store, callback.into_js_value().unchecked_ref());
+}
+
+/// Adds or replaces `record`, and returns its ID.
+fn put<T: Serialize>(store: &str, record: &T, callback: impl FnOnce(Option<u32>) + 'static) {
+    let Ok(record) = serde_json::to_string(record) else {
+        return;
+    };
+    let callback = Closure::once(callback);
+    database_put(store, &record, callback.into_js_value().unchecked_ref());
+}
+
+fn delete(store: &str, id: u32, callback: impl FnOnce() + 'static) {
+    let callback = Closure::once(callback);
+    database_delete(store, id, callback.into_js_value().unchecked_ref());
+}
+
+#[wasm_bindgen]
+extern "C" {
+    fn database_get_all(store: &str, callback: &Function);
+    fn database_put(store: &str, record: &str, callback: &Function);
+    fn database_delete(store: &str, id: u32, callback: &Function);
+    fn toggle_element(event: MouseEvent, elementId: &str);
+    fn handle_blur(event: FocusEvent, elementId: &str);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(id: u32, name: &str, modified: f64, rust: &str) -> StoredWorkspace {
+        StoredWorkspace {
+            id: Some(id),
+            name: name.to_owned(),
+            modified,
+            content: Workspace {
+                rust: Some(Rc::from(rust)),
+                ..Workspace::default()
+            },
+        }
+    }
+
+    #[test]
+    fn link_in_new_tab() {
+        let list = [stored(1, "Old", 10.0, "struct Old;")];
+        let shared = Workspace {
+            rust: Some(Rc::from("struct Shared;")),
+            ..Workspace::default()
+        };
+        assert_eq!(
+            start(&list, Some(1), &shared, true, true, None),
+            Start::Create("Shared"),
+        );
+        // Also in a tab that had the workspace open already.
+        assert_eq!(
+            start(&list, Some(1), &shared, false, true, Some(5.0)),
+            Start::Create("Shared"),
+        );
+        assert_eq!(
+            start(&list, Some(1), &shared, true, false, None),
+            Start::Restore(list[0].clone()),
+        );
+    }
+
+    #[test]
+    fn reload() {
+        let list = [stored(1, "Old", 10.0, "struct Old;")];
+        let content = Workspace {
+            rust: Some(Rc::from("struct Edited;")),
+            ..Workspace::default()
+        };
+        assert_eq!(
+            start(&list, Some(1), &content, false, false, Some(10.0)),
+            Start::Save,
+        );
+        assert_eq!(
+            start(&list, Some(1), &content, false, false, Some(5.0)),
+            Start::Conflict("Old".to_owned()),
+        );
+        assert_eq!(
+            start(&list, Some(1), &list[0].content, false, false, Some(5.0)),
+            Start::Save,
+        );
+        assert_eq!(
+            start(&list, Some(2), &content, false, false, None),
+            Start::Create("Untitled"),
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Just enough of the ZIP format to exchange a few text files with a real crate.
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+/// Version 2.0, which introduced deflate
+const VERSION: u16 = 20;
+/// The names are encoded in UTF-8.
+const UTF8_NAMES: u16 = 1 << 11;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// The uncompressed size of all files that [`read_zip`] accepts
+const MAX_SIZE: usize = 16 << 20;
+
+/// A point in time as MS-DOS stores it, in local time and with a resolution of two seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DosDateTime {
+    date: u16,
+    time: u16,
+}
+
+impl DosDateTime {
+    /// Dates before 1980 cannot be represented and become 1980-01-01.
+    pub fn new(year: u32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Self {
+        if year < 1980 {
+            return Self {
+                date: (1 << 5) | 1,
+                time: 0,
+            };
+        }
+        let date = ((year - 1980).min(127) << 9) | (month << 5) | day;
+        let time = (hour << 11) | (minute << 5) | (second / 2);
+        Self {
+            date: date as u16,
+            time: time as u16,
+        }
+    }
+}
+
+/// Writes the files of an archive one after another, and the directory of the files at the end.
+#[derive(Debug)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    count: u16,
+    modified: DosDateTime,
+}
+
+impl ZipWriter {
+    /// All files get the same modification time.
+    pub fn new(modified: DosDateTime) -> Self {
+        Self {
+            data: Vec::new(),
+            directory: Vec::new(),
+            count: 0,
+            modified,
+        }
+    }
+
+    /// Adds a file. The content is deflated if that makes it smaller.
+    pub fn add(&mut self, name: &str, content: &[u8]) {
+        let deflated = compress_to_vec(content, 9);
+        let (method, stored) = match deflated.len() < content.len() {
+            true => (DEFLATED, deflated.as_slice()),
+            false => (STORED, content),
+        };
+        let header = Header {
+            modified: self.modified,
+            method,
+            crc: crc32(content),
+            stored: stored.len() as u32,
+            size: content.len() as u32,
+            name,
+        };
+        let offset = self.data.len() as u32;
+
+        let data = &mut self.data;
+        put_u32(data, LOCAL_HEADER);
+        put_u16(data, VERSION); // needed to extract
+        header.put(data);
+        put_u16(data, 0); // extra field length
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(stored);
+
+        let directory = &mut self.directory;
+        put_u32(directory, CENTRAL_HEADER);
+        put_u16(directory, VERSION); // made by
+        put_u16(directory, VERSION); // needed to extract
+        header.put(directory);
+        put_u16(directory, 0); // extra field length
+        put_u16(directory, 0); // comment length
+        put_u16(directory, 0); // disk number
+        put_u16(directory, 0); // internal attributes
+        put_u32(directory, 0); // external attributes
+        put_u32(directory, offset);
+        directory.extend_from_slice(name.as_bytes());
+        self.count += 1;
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+
+        let data = &mut self.data;
+        put_u32(data, END_OF_CENTRAL_DIRECTORY);
+        put_u16(data, 0); // number of this disk
+        put_u16(data, 0); // disk with the directory
+        put_u16(data, self.count);
+        put_u16(data, self.count);
+        put_u32(data, size);
+        put_u32(data, offset);
+        put_u16(data, 0); // comment length
+        self.data
+    }
+}
+
+/// The fields that the local header of a file and its entry in the directory share.
+struct Header<'a> {
+    modified: DosDateTime,
+    method: u16,
+    crc: u32,
+    /// The size in the archive
+    stored: u32,
+    size: u32,
+    name: &'a str,
+}
+
+impl Header<'_> {
+    fn put(&self, out: &mut Vec<u8>) {
+        put_u16(out, UTF8_NAMES);
+        put_u16(out, self.method);
+        put_u16(out, self.modified.time);
+        put_u16(out, self.modified.date);
+        put_u32(out, self.crc);
+        put_u32(out, self.stored);
+        put_u32(out, self.size);
+        put_u16(out, self.name.len() as u16);
+    }
+}
+
+/// The files in a zip archive, with their paths. Folders are left out.
+pub fn read_zip(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    // The end record is followed by a comment of up to 64 KiB.
+    let end = (0..=data.len().saturating_sub(22))
+        .rev()
+        .take(1 << 16)
+        .find(|&pos| get_u32(data, pos) == Ok(END_OF_CENTRAL_DIRECTORY))
+        .ok_or("The file is not a zip archive.")?;
+    let count = get_u16(data, end + 10)?;
+    let mut pos = get_u32(data, end + 16)? as usize;
+
+    let mut files = Vec::new();
+    let mut total = 0;
+    for _ in 0..count {
+        // The offsets come from the archive, so they are only added with a check: on wasm32, a
+        // `usize` is not larger than them.
+        let header = get(data, pos, 46)?;
+        if get_u32(header, 0)? != CENTRAL_HEADER {
+            return Err("The directory of the zip archive is damaged.".to_owned());
+        }
+        let flags = get_u16(header, 8)?;
+        let method = get_u16(header, 10)?;
+        let crc = get_u32(header, 16)?;
+        let stored = get_u32(header, 20)? as usize;
+        let size = get_u32(header, 24)? as usize;
+        let name_len = usize::from(get_u16(header, 28)?);
+        let extra_len = usize::from(get_u16(header, 30)?);
+        let comment_len = usize::from(get_u16(header, 32)?);
+        let offset = get_u32(header, 42)? as usize;
+        let name = get(data, add(pos, 46)?, name_len)?;
+        let name = String::from_utf8_lossy(name).replace('\\', "/");
+        pos = add(pos, 46 + name_len + extra_len + comment_len)?;
+
+        if name.ends_with('/') {
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err(format!("{name}: encrypted files are not supported."));
+        }
+        let local = get(data, offset, 30)?;
+        if get_u32(local, 0)? != LOCAL_HEADER {
+            return Err(format!("{name}: the zip archive is damaged."));
+        }
+        let name_len = usize::from(get_u16(local, 26)?);
+        let extra_len = usize::from(get_u16(local, 28)?);
+        let start = add(offset, 30 + name_len + extra_len)?;
+        let stored = get(data, start, stored)?;
+
+        total = size.saturating_add(total);
+        if total > MAX_SIZE {
+            return Err("The zip archive is too big.".to_owned());
+        }
+        let content = match method {
+            STORED => stored.to_vec(),
+            DEFLATED => decompress_to_vec_with_limit(stored, size)
+                .map_err(|_| format!("{name}: the compressed data is damaged."))?,
+            method => {
+                return Err(format!(
+                    "{name}: compression method {method} is not supported."
+                ));
+            }
+        };
+        if content.len() != size || crc32(&content) != crc {
+            return Err(format!("{name}: the checksum does not match."));
+        }
+        files.push((name, content));
+    }
+    Ok(files)
+}
+
+fn get(data: &[u8], pos: usize, len: usize) -> Result<&[u8], String> {
+    data.get(pos..pos.saturating_add(len)).ok_or_else(truncated)
+}
+
+/// `pos + len`, if that is not beyond any archive.
+fn add(pos: usize, len: usize) -> Result<usize, String> {
+    pos.checked_add(len).ok_or_else(truncated)
+}
+
+fn truncated() -> String {
+    "The zip archive is truncated.".to_owned()
+}
+
+fn get_u16(data: &[u8], pos: usize) -> Result<u16, String> {
+    Ok(u16::from_le_bytes(
+        get(data, pos, 2)?.try_into().unwrap_or_default(),
+    ))
+}
+
+fn get_u32(data: &[u8], pos: usize) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(
+        get(data, pos, 4)?.try_into().unwrap_or_default(),
+    ))
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// The CRC-32 of ZIP, PNG, Ethernet …
+pub fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut idx = 0;
+        while idx < 256 {
+            let mut crc = idx as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = match crc & 1 {
+                    1 => (crc >> 1) ^ 0xedb8_8320,
+                    _ => crc >> 1,
+                };
+                bit += 1;
+            }
+            table[idx] = crc;
+            idx += 1;
+        }
+        table
+    };
+
+    !data.iter().fold(!0, |crc, &byte| {
+        TABLE[usize::from((crc as u8) ^ byte)] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let files = [
+            (
+                "project/Cargo.toml",
+                "[package]\nname = \"project\"\n".to_owned(),
+            ),
+            ("project/src/main.rs", "fn main() {}\n".repeat(100)),
+            ("project/templates/ä.html", String::new()),
+        ];
+        let mut zip = ZipWriter::new(DosDateTime::new(2024, 5, 17, 13, 45, 30));
+        for (name, content) in &files {
+            zip.add(name, content.as_bytes());
+        }
+        let zip = zip.finish();
+        let read = read_zip(&zip).unwrap();
+        assert_eq!(read.len(), files.len());
+        for ((name, content), (read_name, read_content)) in files.iter().zip(read) {
+            assert_eq!(*name, read_name);
+            assert_eq!(content.as_bytes(), read_content);
+        }
+
+        assert!(read_zip(b"").is_err());
+        assert!(read_zip(&zip[..zip.len() - 1]).is_err());
+        for idx in (0..zip.len()).step_by(7) {
+            let mut damaged = zip.clone();
+            damaged[idx] ^= 0x55;
+            // Must not panic, the damage may be in a field that doesn't matter.
+            let _ = read_zip(&damaged);
+        }
+    }
+
+    #[test]
+    fn malformed_directory() {
+        let mut zip = ZipWriter::new(DosDateTime::new(2024, 5, 17, 13, 45, 30));
+        zip.add("a.txt", b"a");
+        let zip = zip.finish();
+        let end = zip.len() - 22;
+        let directory = get_u32(&zip, end + 16).unwrap() as usize;
+        let patched = |pos: usize, value: u32| {
+            let mut zip = zip.clone();
+            zip[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+            read_zip(&zip)
+        };
+        let truncated = Err(truncated());
+
+        // The offsets are as far away as they can be, so adding to them would overflow a 32 bit
+        // `usize`.
+        assert_eq!(patched(end + 16, u32::MAX), truncated);
+        assert_eq!(patched(end + 16, u32::MAX - 45), truncated);
+        assert_eq!(patched(directory + 42, u32::MAX), truncated);
+        assert_eq!(patched(directory + 42, u32::MAX - 29), truncated);
+        // The sizes, too.
+        assert_eq!(patched(directory + 20, u32::MAX), truncated);
+
+        // More entries than there are in the directory
+        let mut zip = zip.clone();
+        zip[end + 10] = 2;
+        assert_eq!(read_zip(&zip), truncated);
+    }
+}